@@ -0,0 +1,471 @@
+use crate::prelude::*;
+use lofty::file::AudioFile;
+use std::cmp::Reverse;
+use std::fs::{read_dir, remove_dir};
+use std::time::{Duration, SystemTime};
+use strum_macros::{AsRefStr, IntoStaticStr};
+use tokio::fs::remove_file;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+pub struct CacheCommand {
+    paths: PathProvider,
+    http: HttpClient,
+    podcasts: PodcastProvider,
+}
+
+impl CacheCommand {
+    #[must_use]
+    pub fn new(paths: PathProvider, http: HttpClient, podcasts: PodcastProvider) -> Self {
+        Self {
+            paths,
+            http,
+            podcasts,
+        }
+    }
+
+    /// Report cache usage and remove the selected entries.
+    pub async fn execute(&self, options: CacheOptions) -> Result<CacheReport, CacheError> {
+        let dir = self.paths.get_http_dir();
+        let mut entries = get_entries(&dir)?;
+        log_stats(&entries);
+        let mut report = CacheReport {
+            files: entries.len(),
+            size: entries.iter().map(|entry| entry.size).sum(),
+            dry_run: options.dry_run,
+            ..CacheReport::default()
+        };
+        let mut removals = Vec::new();
+        if let Some(cutoff) = options.older_than.and_then(get_cutoff) {
+            removals.append(&mut take_where(&mut entries, |entry| {
+                entry.modified < cutoff
+            }));
+        }
+        if options.gc {
+            let verified = self.get_verified_audio()?;
+            removals.append(&mut take_where(&mut entries, |entry| {
+                verified.contains(&entry.path)
+            }));
+        }
+        if let Some(budget) = options.max_size {
+            removals.append(&mut take_over_budget(&mut entries, budget));
+        }
+        if removals.is_empty() {
+            return Ok(report);
+        }
+        let size: u64 = removals.iter().map(|entry| entry.size).sum();
+        report.removed = removals.len();
+        report.removed_size = size;
+        if options.dry_run {
+            for entry in &removals {
+                debug!("{} {}", "Would remove".bold(), entry.path.display());
+            }
+            info!(
+                "{} {} files ({})",
+                "Would remove".bold(),
+                removals.len(),
+                format_bytes(size)
+            );
+            return Ok(report);
+        }
+        for entry in &removals {
+            trace!("{} {}", "Removing".bold(), entry.path.display());
+            remove_file(&entry.path)
+                .await
+                .map_err(|e| CacheError::Io(entry.path.clone(), e))?;
+            remove_empty_parents(&entry.path, &dir);
+        }
+        info!(
+            "{} {} files ({})",
+            "Removed".bold(),
+            removals.len(),
+            format_bytes(size)
+        );
+        Ok(report)
+    }
+
+    /// Get the cache paths of audio files that have a verified copy in the output directory.
+    fn get_verified_audio(&self) -> Result<Vec<PathBuf>, CacheError> {
+        let podcasts = self.podcasts.get_all().map_err(CacheError::GetPodcasts)?;
+        let mut paths = Vec::new();
+        for podcast in podcasts {
+            for episode in &podcast.episodes {
+                let source = self
+                    .http
+                    .get_cache_path(&episode.audio_url, Some(MP3_EXTENSION));
+                if !source.exists() {
+                    continue;
+                }
+                let output = self.paths.get_output_path_for_audio(&podcast.id, episode);
+                if is_verified_copy(&source, &output) {
+                    paths.push(source);
+                } else {
+                    trace!("{} unverified audio: {episode}", "Keeping".bold());
+                }
+            }
+        }
+        Ok(paths)
+    }
+}
+
+/// Usage of the HTTP cache and the entries removed from it
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct CacheReport {
+    /// Number of files in the cache before removal
+    pub files: usize,
+    /// Bytes used by the cache before removal
+    pub size: u64,
+    /// Number of files removed
+    pub removed: usize,
+    /// Bytes removed
+    pub removed_size: u64,
+    /// Were the files only reported rather than removed?
+    pub dry_run: bool,
+}
+
+/// A file in the HTTP cache.
+#[derive(Clone, Debug)]
+pub(crate) struct CacheEntry {
+    pub path: PathBuf,
+    pub domain: String,
+    pub kind: CacheKind,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+/// Kind of file in the HTTP cache.
+#[derive(AsRefStr, Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub(crate) enum CacheKind {
+    Audio,
+    Image,
+    Html,
+    Json,
+    Feed,
+    Head,
+    Other,
+}
+
+impl CacheKind {
+    fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "mp3" | "m4a" | "mp4" | "aac" | "ogg" | "opus" | "wav" | "flac" => CacheKind::Audio,
+            "jpg" | "jpeg" | "png" | "gif" | "webp" => CacheKind::Image,
+            HTML_EXTENSION => CacheKind::Html,
+            JSON_EXTENSION => CacheKind::Json,
+            RSS_EXTENSION | "xml" => CacheKind::Feed,
+            HEAD_EXTENSION => CacheKind::Head,
+            _ => CacheKind::Other,
+        }
+    }
+}
+
+/// Recursively get every file in the HTTP cache directory.
+pub(crate) fn get_entries(dir: &Path) -> Result<Vec<CacheEntry>, CacheError> {
+    let mut entries = Vec::new();
+    if dir.exists() {
+        add_entries(dir, dir, &mut entries)?;
+    }
+    Ok(entries)
+}
+
+fn add_entries(root: &Path, dir: &Path, entries: &mut Vec<CacheEntry>) -> Result<(), CacheError> {
+    let items = read_dir(dir).map_err(|e| CacheError::Io(dir.to_path_buf(), e))?;
+    for item in items {
+        let item = item.map_err(|e| CacheError::Io(dir.to_path_buf(), e))?;
+        let path = item.path();
+        let metadata = item
+            .metadata()
+            .map_err(|e| CacheError::Io(path.clone(), e))?;
+        if metadata.is_dir() {
+            add_entries(root, &path, entries)?;
+            continue;
+        }
+        if is_bookkeeping(&path) {
+            trace!("{} {}", "Skipping".bold(), path.display());
+            continue;
+        }
+        let domain = path
+            .strip_prefix(root)
+            .ok()
+            .and_then(|relative| relative.components().next())
+            .map(|component| component.as_os_str().to_string_lossy().to_string())
+            .unwrap_or_default();
+        entries.push(CacheEntry {
+            kind: CacheKind::from_path(&path),
+            domain,
+            size: metadata.len(),
+            modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            path,
+        });
+    }
+    Ok(())
+}
+
+/// Is the file a lock, partial download or moved marker rather than a cache entry?
+///
+/// These are in use by running downloads so must never be removed.
+fn is_bookkeeping(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    name.ends_with(PARTIAL_SUFFIX)
+        || name.ends_with(LOCK_SUFFIX)
+        || name.ends_with(&format!("{LOCK_SUFFIX}{TAKEOVER_SUFFIX}"))
+        || name.ends_with(&format!("{LOCK_SUFFIX}{REPLACEMENT_SUFFIX}"))
+        || name.ends_with(&format!(".{MOVED_EXTENSION}"))
+}
+
+fn log_stats(entries: &[CacheEntry]) {
+    let total: u64 = entries.iter().map(|entry| entry.size).sum();
+    info!(
+        "{} {} in {} files",
+        "Cache".bold(),
+        format_bytes(total),
+        entries.len()
+    );
+    let mut domains: HashMap<&str, (u64, usize)> = HashMap::new();
+    let mut kinds: HashMap<CacheKind, (u64, usize)> = HashMap::new();
+    for entry in entries {
+        let domain = domains.entry(&entry.domain).or_default();
+        domain.0 += entry.size;
+        domain.1 += 1;
+        let kind = kinds.entry(entry.kind).or_default();
+        kind.0 += entry.size;
+        kind.1 += 1;
+    }
+    let mut domains: Vec<_> = domains.into_iter().collect();
+    domains.sort_by_key(|(_, (size, _))| Reverse(*size));
+    for (domain, (size, count)) in domains {
+        info!("{domain}: {} in {count} files", format_bytes(size));
+    }
+    let mut kinds: Vec<_> = kinds.into_iter().collect();
+    kinds.sort_by_key(|(_, (size, _))| Reverse(*size));
+    for (kind, (size, count)) in kinds {
+        info!("{}: {} in {count} files", kind.as_ref(), format_bytes(size));
+    }
+}
+
+/// Get the time before which entries are older than a number of days.
+///
+/// Returns `None` if the cutoff is before the earliest representable time.
+fn get_cutoff(days: u64) -> Option<SystemTime> {
+    let seconds = days.checked_mul(SECONDS_PER_DAY)?;
+    SystemTime::now().checked_sub(Duration::from_secs(seconds))
+}
+
+/// Remove and return the entries matching the predicate.
+fn take_where<F: Fn(&CacheEntry) -> bool>(
+    entries: &mut Vec<CacheEntry>,
+    predicate: F,
+) -> Vec<CacheEntry> {
+    let (taken, kept) = take(entries)
+        .into_iter()
+        .partition(|entry| predicate(entry));
+    *entries = kept;
+    taken
+}
+
+/// Remove and return the oldest entries until the remaining entries are within the budget.
+fn take_over_budget(entries: &mut Vec<CacheEntry>, budget: u64) -> Vec<CacheEntry> {
+    entries.sort_by_key(|entry| entry.modified);
+    let mut total: u64 = entries.iter().map(|entry| entry.size).sum();
+    let mut count = 0;
+    for entry in entries.iter() {
+        if total <= budget {
+            break;
+        }
+        total -= entry.size;
+        count += 1;
+    }
+    entries.drain(..count).collect()
+}
+
+/// Check the output copy exists and has the same audio duration as the cached source.
+fn is_verified_copy(source: &Path, output: &Path) -> bool {
+    if !output.is_file() {
+        return false;
+    }
+    let Ok(output) = lofty::read_from_path(output) else {
+        return false;
+    };
+    let Ok(source) = lofty::read_from_path(source) else {
+        return false;
+    };
+    source.properties().duration() == output.properties().duration()
+}
+
+fn remove_empty_parents(path: &Path, root: &Path) {
+    let mut dir = path.parent();
+    while let Some(current) = dir {
+        if current == root || !current.starts_with(root) || remove_dir(current).is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+}
+
+#[allow(clippy::absolute_paths)]
+#[derive(Debug, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum CacheError {
+    Io(PathBuf, std::io::Error),
+    GetPodcasts(DatabaseError),
+}
+
+impl Display for CacheError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let reason = match self {
+            CacheError::Io(path, e) => {
+                format!("An I/O error occurred.\nPath: {}\n{e}", path.display())
+            }
+            CacheError::GetPodcasts(e) => format!("Unable to get podcasts\n{e}"),
+        };
+        write!(f, "{} to manage cache\n{reason}", "Failed".bold())
+    }
+}
+
+impl ErrorCode for CacheError {
+    fn get_code(&self) -> String {
+        format!("cache.{}", <&str>::from(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, write, File as SyncFile};
+
+    fn create_cache() -> PathBuf {
+        let cache_dir = TempDirectory::create("pura-cache");
+        let http_dir = cache_dir.join("http");
+        create_dir_all(http_dir.join("example.com/season-1")).expect("should create dir");
+        create_dir_all(http_dir.join("api.example.com")).expect("should create dir");
+        create_dir_all(cache_dir.join("podcasts")).expect("should create dir");
+        write(http_dir.join("example.com/__root.html"), [0; 100]).expect("should write");
+        write(http_dir.join("example.com/season-1/a.mp3"), [0; 1000]).expect("should write");
+        write(http_dir.join("api.example.com/b.json"), [0; 10]).expect("should write");
+        write(http_dir.join("example.com/c.mp3.part"), [0; 10]).expect("should write");
+        write(http_dir.join("example.com/c.mp3.lock"), "1").expect("should write");
+        write(http_dir.join("example.com/d.mp3.moved"), "").expect("should write");
+        let old = SystemTime::now() - Duration::from_secs(30 * SECONDS_PER_DAY);
+        SyncFile::options()
+            .write(true)
+            .open(http_dir.join("example.com/season-1/a.mp3"))
+            .and_then(|file| file.set_modified(old))
+            .expect("should set modified");
+        cache_dir
+    }
+
+    fn create_command(cache_dir: &Path) -> CacheCommand {
+        let options = AppOptions {
+            cache_dir: Some(cache_dir.to_path_buf()),
+            ..AppOptions::default()
+        };
+        let paths = PathProvider::new(options);
+        let http = HttpClient::new(paths.get_http_dir());
+        let podcasts = PodcastProvider::new(paths.get_podcast_dir());
+        CacheCommand::new(paths, http, podcasts)
+    }
+
+    #[test]
+    fn get_entries_by_domain_and_kind() {
+        // Arrange
+        let cache_dir = create_cache();
+
+        // Act
+        let entries = get_entries(&cache_dir.join("http")).assert_ok();
+
+        // Assert
+        assert_eq!(entries.len(), 3);
+        let audio = entries
+            .iter()
+            .find(|entry| entry.kind == CacheKind::Audio)
+            .expect("should be an audio entry");
+        assert_eq!(audio.domain, "example.com");
+        assert_eq!(audio.size, 1000);
+        let json = entries
+            .iter()
+            .find(|entry| entry.kind == CacheKind::Json)
+            .expect("should be a json entry");
+        assert_eq!(json.domain, "api.example.com");
+    }
+
+    #[test]
+    fn take_over_budget_removes_oldest() {
+        // Arrange
+        let cache_dir = create_cache();
+        let mut entries = get_entries(&cache_dir.join("http")).assert_ok();
+
+        // Act
+        let removed = take_over_budget(&mut entries, 500);
+
+        // Assert
+        assert_eq!(removed.len(), 1);
+        assert_eq!(
+            removed.first().map(|entry| entry.kind),
+            Some(CacheKind::Audio)
+        );
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn execute_dry_run_then_prune() {
+        // Arrange
+        let _ = init_logging();
+        let cache_dir = create_cache();
+        let command = create_command(&cache_dir);
+        let audio = cache_dir.join("http/example.com/season-1/a.mp3");
+
+        // Act
+        let dry_run = command
+            .execute(CacheOptions {
+                older_than: Some(7),
+                dry_run: true,
+                ..CacheOptions::default()
+            })
+            .await
+            .assert_ok();
+        let exists_after_dry_run = audio.exists();
+        command
+            .execute(CacheOptions {
+                older_than: Some(7),
+                ..CacheOptions::default()
+            })
+            .await
+            .assert_ok();
+
+        // Assert
+        assert_eq!(dry_run.removed, 1);
+        assert!(exists_after_dry_run);
+        assert!(!audio.exists());
+        assert!(!audio.parent().expect("should have parent").exists());
+        assert!(cache_dir.join("http/example.com/__root.html").exists());
+    }
+
+    #[tokio::test]
+    async fn execute_older_than_overflow() {
+        // Arrange
+        let _ = init_logging();
+        let cache_dir = create_cache();
+        let command = create_command(&cache_dir);
+
+        // Act
+        let report = command
+            .execute(CacheOptions {
+                older_than: Some(u64::MAX),
+                ..CacheOptions::default()
+            })
+            .await
+            .assert_ok();
+
+        // Assert
+        assert_eq!(report.removed, 0);
+        assert_eq!(get_cutoff(u64::MAX >> 1), None);
+        assert!(get_cutoff(7).is_some());
+    }
+}
@@ -0,0 +1,5 @@
+pub use cache::*;
+pub use options::*;
+
+mod cache;
+mod options;
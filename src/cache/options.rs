@@ -0,0 +1,19 @@
+use crate::prelude::*;
+
+#[derive(Debug, Default, Args)]
+pub struct CacheOptions {
+    /// Remove HTTP cache entries last modified more than this many days ago
+    #[arg(long, value_name = "DAYS")]
+    pub older_than: Option<u64>,
+    /// Remove the oldest HTTP cache entries until the cache is within this size
+    ///
+    /// Accepts units such as `500MB` or `20GB`
+    #[arg(long, value_name = "SIZE", value_parser = parse_bytes)]
+    pub max_size: Option<u64>,
+    /// Remove cached audio files that have a verified copy in the output directory
+    #[arg(long)]
+    pub gc: bool,
+    /// Report what would be removed without removing anything
+    #[arg(long)]
+    pub dry_run: bool,
+}
@@ -1,4 +1,5 @@
 use crate::prelude::*;
+use strum_macros::IntoStaticStr;
 
 const BANNER_WIDTH: u32 = 960;
 const BANNER_HEIGHT: u32 = 540;
@@ -20,19 +21,34 @@ impl CoverCommand {
         }
     }
 
-    pub async fn execute(&self, options: CoverOptions) -> Result<(), CoverError> {
+    /// Create the cover and banner images and return their paths.
+    pub async fn execute(&self, options: CoverOptions) -> Result<Vec<PathBuf>, CoverError> {
+        let _lock = self
+            .podcasts
+            .lock(&options.podcast_id)
+            .await
+            .map_err(CoverError::Lock)?;
         let podcast = self
             .podcasts
             .get(&options.podcast_id)
             .map_err(CoverError::GetPodcast)?;
-        let url = podcast.image_url.ok_or(CoverError::NoImage)?;
-        let src = self
+        let credentials = self
+            .podcasts
+            .get_credentials(&podcast.id)
+            .map_err(CoverError::GetPodcast)?;
+        let http = self
             .http
-            .get(&url, None)
+            .for_podcast_proxy(podcast.proxy.as_ref())
             .await
-            .map_err(CoverError::GetImage)?;
+            .map_err(CoverError::GetImage)?
+            .with_credentials(credentials);
+        let url = podcast.image_url.ok_or(CoverError::NoImage)?;
+        let src = http.get(&url, None).await.map_err(CoverError::GetImage)?;
         let banner = self.paths.get_output_path_for_banner(&options.podcast_id);
         let cover = self.paths.get_output_path_for_cover(&options.podcast_id);
+        create_parent_dir_if_not_exist(&cover)
+            .await
+            .map_err(|e| CoverError::Image(ImageError::IO(e)))?;
         let resize = Resize::new(&src).map_err(CoverError::Image)?;
         let banner = resize
             .to_file(&banner, BANNER_WIDTH, BANNER_HEIGHT)
@@ -43,16 +59,18 @@ impl CoverCommand {
         info!("{} cover and banner images", "Created".bold());
         trace!("{}", banner.display());
         trace!("{}", cover.display());
-        Ok(())
+        Ok(vec![cover, banner])
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
 pub enum CoverError {
     GetPodcast(DatabaseError),
     NoImage,
     GetImage(HttpError),
     Image(ImageError),
+    Lock(LockError),
 }
 
 impl Display for CoverError {
@@ -62,6 +80,7 @@ impl Display for CoverError {
             CoverError::NoImage => "Podcast does not have an image".to_owned(),
             CoverError::GetImage(e) => format!("Unable to get image:\n{e}"),
             CoverError::Image(e) => format!("Unable to create image:\n{e}"),
+            CoverError::Lock(e) => format!("Unable to lock podcast\n{e}"),
         };
         write!(
             f,
@@ -71,6 +90,12 @@ impl Display for CoverError {
     }
 }
 
+impl ErrorCode for CoverError {
+    fn get_code(&self) -> String {
+        format!("cover.{}", <&str>::from(self))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -79,12 +104,32 @@ mod tests {
     pub async fn cover_command() {
         // Arrange
         let _ = init_logging();
-        let services = ServiceProvider::create()
-            .await
-            .expect("ServiceProvider should not fail");
+        let services = create_fixture_services("pura-cover-simplecast").await;
+        scrape_simplecast_fixture(&services).await;
+        let command = CoverCommand::new(services.podcasts, services.http, services.paths);
+        let options = CoverOptions {
+            podcast_id: SIMPLECAST_FIXTURE_PODCAST_ID.to_owned(),
+        };
+
+        // Act
+        let result = command.execute(options).await;
+
+        // Assert
+        let images = result.assert_ok();
+        assert_eq!(images.len(), 2);
+        assert!(images.iter().all(|path| path.exists()));
+    }
+
+    #[tokio::test]
+    pub async fn cover_command_replay() {
+        // Arrange
+        let _ = init_logging();
+        let services = create_fixture_services("pura-cover").await;
+        scrape_fixture(&services).await;
+        let paths = services.paths.clone();
         let command = CoverCommand::new(services.podcasts, services.http, services.paths);
         let options = CoverOptions {
-            podcast_id: "irl".to_owned(),
+            podcast_id: FIXTURE_PODCAST_ID.to_owned(),
         };
 
         // Act
@@ -92,5 +137,9 @@ mod tests {
 
         // Assert
         result.assert_ok();
+        let cover = paths.get_output_path_for_cover(FIXTURE_PODCAST_ID);
+        let banner = paths.get_output_path_for_banner(FIXTURE_PODCAST_ID);
+        assert!(cover.with_extension("png").exists());
+        assert!(banner.with_extension("png").exists());
     }
 }
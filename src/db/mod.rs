@@ -0,0 +1,5 @@
+pub use db::*;
+pub use options::*;
+
+mod db;
+mod options;
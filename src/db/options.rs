@@ -0,0 +1,30 @@
+use crate::prelude::*;
+
+#[derive(Debug, Args)]
+pub struct DbOptions {
+    #[command(subcommand)]
+    pub action: DbAction,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DbAction {
+    /// Copy every podcast from another database into the configured database.
+    Import(DbTransferOptions),
+    /// Copy every podcast from the configured database into another database.
+    Export(DbTransferOptions),
+    /// Rewrite YAML podcasts written with an older schema, keeping a backup of each.
+    Migrate,
+}
+
+#[derive(Debug, Args)]
+pub struct DbTransferOptions {
+    /// Backend of the other database
+    #[arg(value_enum)]
+    pub database: DatabaseKind,
+    /// Path of the other database
+    ///
+    /// Defaults to the podcasts cache directory for `yaml` and `podcasts.db` in the
+    /// cache directory for `sqlite`.
+    #[arg(long)]
+    pub path: Option<PathBuf>,
+}
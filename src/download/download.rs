@@ -1,8 +1,18 @@
 use crate::prelude::*;
+use chrono::Utc;
 use lofty::picture::Picture;
+use reflink_copy::reflink_or_copy;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs::metadata;
+use std::io::Error as IoError;
+use std::time::Instant;
+use strum_macros::IntoStaticStr;
+use tokio::fs::{hard_link, remove_file, rename, write};
 use tokio::task::{spawn_blocking, JoinError};
 
-const CONCURRENCY: usize = 8;
+const BATCHES_PER_CONCURRENCY: usize = 4;
 const IMAGE_SIZE: u32 = 720;
 
 pub struct DownloadCommand {
@@ -21,111 +31,386 @@ impl DownloadCommand {
         }
     }
 
-    pub async fn execute(&self, options: DownloadOptions) -> Result<(), DownloadError> {
-        let podcast = self
+    /// Download the episodes of a podcast and save a report of the run.
+    ///
+    /// Episodes that fail are reported rather than failing the command.
+    /// The podcast is only locked while it is read and while the output and downloads
+    /// are changed so other commands aren't blocked while the audio is fetched.
+    pub async fn execute(
+        &self,
+        mut options: DownloadOptions,
+    ) -> Result<DownloadReport, DownloadError> {
+        if let Some(year) = options.year.take() {
+            warn!("{} positional year, use --year {year}", "Deprecated".bold());
+            options.filter.year = Some(year);
+        }
+        let (podcast, retry) = self.read(&options).await?;
+        if options.filter.dry_run {
+            return Ok(get_dry_run_report(
+                &podcast,
+                &options.filter,
+                retry.as_ref(),
+            ));
+        }
+        let credentials = self
             .podcasts
-            .get(&options.podcast_id)
+            .get_credentials(&podcast.id)
             .map_err(DownloadError::GetPodcast)?;
-        let results = self.process_episodes(podcast.clone(), options.year).await;
-        let mut episodes = Vec::new();
-        let mut errors = Vec::new();
-        for result in results {
-            match result {
-                Ok(episode) => episodes.push(episode),
-                Err(e) => errors.push(e),
-            }
-        }
+        let http = self
+            .http
+            .for_podcast_proxy(podcast.proxy.as_ref())
+            .await
+            .map_err(DownloadError::Client)?
+            .with_credentials(credentials);
+        let command = DownloadCommand::new(self.paths.clone(), http, self.podcasts.clone());
+        let mut report = DownloadReport::new(&podcast.id);
+        let aborted = command
+            .process_episodes(podcast.clone(), &options, retry.as_ref(), &mut report)
+            .await;
+        report.finished_at = Utc::now().naive_utc();
+        let downloaded = report.get_episodes(Outcome::Downloaded);
         info!(
             "{} audio files for {} episodes",
             "Downloaded".bold(),
-            episodes.len()
+            downloaded.len()
         );
-        if !errors.is_empty() {
+        let episodes: Vec<Episode> = podcast
+            .episodes
+            .iter()
+            .filter(|episode| {
+                downloaded
+                    .iter()
+                    .any(|outcome| outcome.episode == episode.id)
+            })
+            .cloned()
+            .collect();
+        let _lock = match self.podcasts.lock_waiting(&podcast.id).await {
+            Ok(lock) => Some(lock),
+            Err(e) => {
+                warn!("{} to lock podcast\n{e}", "Failed".bold());
+                None
+            }
+        };
+        self.record_downloads(&podcast.id, &episodes);
+        self.record_download_urls(&podcast.id, &report);
+        let failed = report.get_episodes(Outcome::Failed).len();
+        if failed > 0 {
+            warn!("{} {failed} episodes due to failures", "Skipped".bold());
+        }
+        let cancelled = report.get_episodes(Outcome::Cancelled).len();
+        if cancelled > 0 {
             warn!(
-                "{} {} episodes due to failures",
-                "Skipped".bold(),
-                errors.len()
+                "{} {cancelled} episodes as the run was cancelled",
+                "Skipped".bold()
             );
         }
-        Ok(())
+        if let Some(e) = aborted {
+            let remaining = report.get_episodes(Outcome::Aborted).len();
+            warn!(
+                "{} {remaining} episodes as the IP check failed",
+                "Skipped".bold()
+            );
+            let e = DownloadError::IpChanged(e);
+            report.aborted = Some(ErrorOutput::new(&e));
+            if episodes.is_empty() {
+                self.save_report(&report);
+                return Err(e);
+            }
+        }
+        self.save_report(&report);
+        Ok(report)
+    }
+
+    /// Read the podcast and the episodes to retry while the podcast is locked.
+    async fn read(
+        &self,
+        options: &DownloadOptions,
+    ) -> Result<(Podcast, Option<HashSet<String>>), DownloadError> {
+        let _lock = self
+            .podcasts
+            .lock(&options.podcast_id)
+            .await
+            .map_err(DownloadError::Lock)?;
+        let podcast = self
+            .podcasts
+            .get(&options.podcast_id)
+            .map_err(DownloadError::GetPodcast)?;
+        let retry = if options.retry_failed {
+            let report = get_last_report(&self.paths.get_reports_dir(), &podcast.id)
+                .map_err(DownloadError::Report)?
+                .ok_or_else(|| DownloadError::NoReport(podcast.id.clone()))?;
+            let ids = report.get_retry_ids();
+            info!(
+                "{} {} failed episodes from {}",
+                "Retrying".bold(),
+                ids.len(),
+                report.get_name()
+            );
+            Some(ids)
+        } else {
+            None
+        };
+        Ok((podcast, retry))
+    }
+
+    /// Save the report of a run.
+    ///
+    /// A failure is logged rather than failing the command as the files are already written.
+    fn save_report(&self, report: &DownloadReport) {
+        match put_report(&self.paths.get_reports_dir(), report) {
+            Ok(path) => debug!("{} report {}", "Saved".bold(), path.display()),
+            Err(e) => warn!("{} to save report\n{e}", "Failed".bold()),
+        }
+    }
+
+    /// Record the downloaded episodes in the database.
+    ///
+    /// A failure is logged rather than failing the command as the files are already written.
+    fn record_downloads(&self, podcast_id: &str, episodes: &[Episode]) {
+        let downloaded_at = Utc::now().naive_utc();
+        let downloads: Vec<DownloadRecord> = episodes
+            .iter()
+            .map(|episode| DownloadRecord {
+                episode_id: episode.id.clone(),
+                path: self.paths.get_output_path_for_audio(podcast_id, episode),
+                audio_url: episode.audio_url.clone(),
+                audio_file_size: episode.audio_file_size,
+                downloaded_at,
+            })
+            .collect();
+        if let Err(e) = self.podcasts.put_downloads(podcast_id, &downloads) {
+            warn!("{} to record downloads\n{e}", "Failed".bold());
+        }
     }
 
+    /// Record the URL and redirects each episode was fetched from on the saved podcast.
+    ///
+    /// The podcast is read again as it may have been scraped while the audio was fetched.
+    /// A failure is logged rather than failing the command as the files are already written.
+    fn record_download_urls(&self, podcast_id: &str, report: &DownloadReport) {
+        let fetched: HashMap<&str, &Vec<Url>> = report
+            .episodes
+            .iter()
+            .filter_map(|outcome| Some((outcome.episode.as_str(), outcome.redirects.as_ref()?)))
+            .collect();
+        if fetched.is_empty() {
+            return;
+        }
+        let result = self.podcasts.get(podcast_id).and_then(|mut podcast| {
+            for episode in &mut podcast.episodes {
+                let Some(redirects) = fetched.get(episode.id.as_str()) else {
+                    continue;
+                };
+                let url = redirects.last().unwrap_or(&episode.audio_url);
+                episode.download_url = Some(url.clone());
+                episode.download_redirects.clone_from(redirects);
+            }
+            self.podcasts.put(&podcast)
+        });
+        if let Err(e) = result {
+            warn!("{} to record download URLs\n{e}", "Failed".bold());
+        }
+    }
+
+    /// Process episodes in batches, re-checking the external IP after each batch.
+    ///
+    /// The outcome of every episode is added to the report. Only episodes matching the
+    /// filter are attempted. If `retry` is set only the matching retry episodes are
+    /// attempted, even if their audio is already in the output.
+    /// If the IP check fails the remaining episodes are aborted and the error returned.
+    /// If the run is cancelled the episodes not yet started are cancelled.
     #[allow(clippy::as_conversions)]
     async fn process_episodes(
         &self,
         mut podcast: Podcast,
-        year: Option<i32>,
-    ) -> Vec<Result<Episode, ProcessError>> {
-        let episodes: Vec<_> = take(&mut podcast.episodes)
-            .into_iter()
-            .filter(|episode| {
-                if let Some(year) = year {
-                    if episode.published_at.year() != year {
-                        return false;
-                    }
-                }
-                let exists = self
-                    .paths
-                    .get_output_path_for_audio(&podcast.id, episode)
-                    .exists();
-                if exists {
+        options: &DownloadOptions,
+        retry: Option<&HashSet<String>>,
+        report: &mut DownloadReport,
+    ) -> Option<HttpError> {
+        let matches = options.filter.get_matches(&podcast.episodes);
+        let mut episodes = Vec::new();
+        for episode in take(&mut podcast.episodes) {
+            if !is_selected(&episode, &matches, retry) {
+                report
+                    .episodes
+                    .push(EpisodeOutcome::new(&episode, Outcome::Filtered));
+                continue;
+            }
+            let path = self.paths.get_output_path_for_audio(&podcast.id, &episode);
+            if path.exists() && retry.is_none() {
+                if !options.keep_versions || !is_outdated(&episode, &path) {
                     trace!("{} existing episode: {episode}", "Skipping".bold());
+                    report
+                        .episodes
+                        .push(EpisodeOutcome::new(&episode, Outcome::Existing).with_path(path));
+                    continue;
                 }
-                !exists
-            })
-            .collect();
+                if let Err(e) = self.keep_version(&podcast.id, &episode, &path).await {
+                    warn!("{e}");
+                    report.episodes.push(get_failed_outcome(&episode, &e));
+                    continue;
+                }
+            } else if self.was_moved(&episode) {
+                debug!(
+                    "{} episode moved from cache but missing from output: {episode}",
+                    "Downloading".bold()
+                );
+            }
+            episodes.push(episode);
+        }
+        let episodes = apply_limits(episodes, options, report);
         debug!(
             "{} audio files for {} episodes",
             "Downloading".bold(),
             episodes.len()
         );
         let progress = Progress::new(episodes.len() as u64);
-        let results = stream::iter(episodes.into_iter().map(|episode| {
-            let this = self;
-            let podcast = podcast.clone();
-            let progress = progress.clone();
-            async move {
-                let result = this.process_episode(&podcast, episode).await;
-                progress.update();
-                if let Err(e) = &result {
-                    warn!("{e}");
+        let command = DownloadCommand::new(
+            self.paths.clone(),
+            self.http.clone().with_progress(progress.clone()),
+            self.podcasts.clone(),
+        );
+        let mut aborted = None;
+        let concurrency = options.concurrency.max(1);
+        let mut batches = episodes.chunks(concurrency * BATCHES_PER_CONCURRENCY);
+        for batch in batches.by_ref() {
+            if self.http.is_cancelled() {
+                report.episodes.extend(
+                    batch
+                        .iter()
+                        .map(|episode| EpisodeOutcome::new(episode, Outcome::Cancelled)),
+                );
+                continue;
+            }
+            let mut outcomes = stream::iter(batch.iter().cloned().map(|episode| {
+                let this = &command;
+                let podcast = podcast.clone();
+                let progress = progress.clone();
+                let transfer = options.transfer;
+                async move {
+                    if this.http.is_cancelled() {
+                        return EpisodeOutcome::new(&episode, Outcome::Cancelled);
+                    }
+                    let outcome = this.attempt_episode(&podcast, episode, transfer).await;
+                    progress.update();
+                    outcome
                 }
-                result
+            }))
+            .buffer_unordered(concurrency)
+            .collect::<Vec<_>>()
+            .await;
+            report.episodes.append(&mut outcomes);
+            if let Err(e) = self.http.check_ip().await {
+                aborted = Some(e);
+                break;
             }
-        }))
-        .buffer_unordered(CONCURRENCY)
-        .collect::<Vec<_>>()
-        .await;
+        }
+        for episode in batches.flatten() {
+            report
+                .episodes
+                .push(EpisodeOutcome::new(episode, Outcome::Aborted));
+        }
         progress.finish();
-        results
+        aborted
+    }
+
+    /// Process an episode and time the outcome.
+    async fn attempt_episode(
+        &self,
+        podcast: &Podcast,
+        episode: Episode,
+        transfer: TransferMode,
+    ) -> EpisodeOutcome {
+        let started_at = Utc::now().naive_utc();
+        let start = Instant::now();
+        let mut outcome = match self
+            .process_episode(podcast, episode.clone(), transfer)
+            .await
+        {
+            Ok((path, redirects)) => EpisodeOutcome::new(&episode, Outcome::Downloaded)
+                .with_path(path)
+                .with_redirects(redirects),
+            Err(e) => {
+                warn!("{e}");
+                get_failed_outcome(&episode, &e)
+            }
+        };
+        outcome.started_at = Some(started_at);
+        outcome.duration_ms = Some(u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX));
+        outcome
     }
 
     async fn process_episode(
         &self,
         podcast: &Podcast,
         episode: Episode,
-    ) -> Result<Episode, ProcessError> {
-        let path = self.download_episode(&episode).await?;
-        let audio_path = self.copy_episode(&podcast.id, &episode, &path).await?;
+        transfer: TransferMode,
+    ) -> Result<(PathBuf, Option<Vec<Url>>), ProcessError> {
+        let file = self.download_episode(&episode).await?;
         let cover = self.download_image(&episode).await?;
+        let _lock = self.lock(&podcast.id, &episode).await?;
+        let audio_path = self
+            .transfer_episode(&podcast.id, &episode, &file.path, transfer)
+            .await?;
         trace!("{} tags for {episode}", "Setting".bold());
         Tag::execute(podcast, &episode, cover, &audio_path)
             .map_err(|e| ProcessError::Tag(episode.get_file_stem(), audio_path.clone(), e))?;
-        Ok(episode)
+        Ok((audio_path, file.redirects))
     }
 
-    async fn download_episode(&self, episode: &Episode) -> Result<PathBuf, ProcessError> {
+    async fn download_episode(&self, episode: &Episode) -> Result<CachedFile, ProcessError> {
+        let stem = episode.get_file_stem();
         self.http
-            .get(&episode.audio_url, Some(MP3_EXTENSION))
+            .download(&episode.audio_url, Some(MP3_EXTENSION), &stem)
             .await
-            .map_err(|e| ProcessError::DownloadAudio(episode.get_file_stem(), e))
+            .map_err(|e| ProcessError::DownloadAudio(stem, e))
     }
 
-    async fn copy_episode(
+    /// Rename the output of a previous audio version and remove it from the cache
+    /// so the current version is downloaded.
+    async fn keep_version(
+        &self,
+        podcast_id: &str,
+        episode: &Episode,
+        path: &Path,
+    ) -> Result<(), ProcessError> {
+        let _lock = self.lock(podcast_id, episode).await?;
+        let version_path = get_version_path(path);
+        debug!(
+            "{} previous audio version of {episode}\nPath: {}",
+            "Keeping".bold(),
+            version_path.display()
+        );
+        rename(path, &version_path)
+            .await
+            .map_err(|e| ProcessError::IO(episode.get_file_stem(), path.to_path_buf(), e))?;
+        self.http
+            .remove(&episode.audio_url, Some(MP3_EXTENSION))
+            .await;
+        Ok(())
+    }
+
+    /// Lock the podcast while the output of an episode is changed.
+    async fn lock(&self, podcast_id: &str, episode: &Episode) -> Result<LockFile, ProcessError> {
+        self.podcasts
+            .lock_waiting(podcast_id)
+            .await
+            .map_err(|e| ProcessError::Lock(episode.get_file_stem(), e))
+    }
+
+    fn was_moved(&self, episode: &Episode) -> bool {
+        let source_path = self
+            .http
+            .get_cache_path(&episode.audio_url, Some(MP3_EXTENSION));
+        get_moved_path(&source_path).exists()
+    }
+
+    async fn transfer_episode(
         &self,
         podcast_id: &str,
         episode: &Episode,
         source_path: &PathBuf,
+        transfer: TransferMode,
     ) -> Result<PathBuf, ProcessError> {
         let destination_path = self.paths.get_output_path_for_audio(podcast_id, episode);
         create_parent_dir_if_not_exist(&destination_path)
@@ -141,12 +426,12 @@ impl DownloadCommand {
                 )
             })?;
         trace!(
-            "{} {episode}\nSource: {}\nTarget: {}",
-            "Copying".bold(),
+            "{} {episode} with {transfer:?}\nSource: {}\nTarget: {}",
+            "Transferring".bold(),
             source_path.display(),
             destination_path.display()
         );
-        copy(&source_path, &destination_path)
+        transfer_file(source_path, &destination_path, transfer)
             .await
             .map_err(|e| ProcessError::IO(episode.get_file_stem(), source_path.into(), e))?;
         Ok(destination_path)
@@ -158,11 +443,14 @@ impl DownloadCommand {
         };
         trace!("{} image for episode: {episode}", "Downloading".bold());
         let extension = url.get_extension();
-        let path = self
-            .http
-            .get(url, extension.as_deref())
-            .await
-            .map_err(|e| ProcessError::DownloadImage(episode.get_file_stem(), e))?;
+        let path = match self.http.get(url, extension.as_deref()).await {
+            Ok(path) => path,
+            Err(HttpError::Offline(_)) => {
+                debug!("{} image in offline mode: {episode}", "Skipping".bold());
+                return Ok(None);
+            }
+            Err(e) => return Err(ProcessError::DownloadImage(episode.get_file_stem(), e)),
+        };
         trace!("{} image for episode: {episode}", "Resizing".bold());
         let picture = spawn_blocking(move || -> Result<Picture, ImageError> {
             Resize::new(&path)?.to_picture(IMAGE_SIZE, IMAGE_SIZE)
@@ -175,22 +463,198 @@ impl DownloadCommand {
     }
 }
 
+/// Transfer a file from the cache to the output directory.
+async fn transfer_file(
+    source: &Path,
+    destination: &Path,
+    transfer: TransferMode,
+) -> Result<(), IoError> {
+    if transfer != TransferMode::Copy && destination.exists() {
+        remove_file(destination).await?;
+    }
+    match transfer {
+        TransferMode::Copy => {
+            copy(source, destination).await?;
+        }
+        TransferMode::Hardlink => {
+            if let Err(e) = hard_link(source, destination).await {
+                debug!("{} to hard link so copying instead: {e}", "Failed".bold());
+                copy(source, destination).await?;
+            }
+        }
+        TransferMode::Reflink => {
+            let source = source.to_path_buf();
+            let destination = destination.to_path_buf();
+            let copied = spawn_blocking(move || reflink_or_copy(source, destination))
+                .await
+                .map_err(IoError::other)??;
+            if copied.is_some() {
+                trace!("{} reflink so copied instead", "Unsupported".bold());
+            }
+        }
+        TransferMode::Move => {
+            if let Err(e) = rename(source, destination).await {
+                debug!("{} to rename so copying instead: {e}", "Failed".bold());
+                copy(source, destination).await?;
+                remove_file(source).await?;
+            }
+            let record = destination.to_string_lossy().to_string();
+            write(get_moved_path(source), record).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Has the audio URL or file size changed since the output file was written?
+fn is_outdated(episode: &Episode, path: &Path) -> bool {
+    let Some(changed_at) = episode.get_audio_changed_at() else {
+        return false;
+    };
+    let Ok(modified) = metadata(path).and_then(|metadata| metadata.modified()) else {
+        return false;
+    };
+    DateTime::<Utc>::from(modified).naive_utc() < changed_at
+}
+
+/// Get the first unused path with a ` (vN)` suffix for a previous audio version.
+fn get_version_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    let mut version = 1;
+    loop {
+        let version_path = path.with_file_name(format!("{stem} (v{version}).{extension}"));
+        if !version_path.exists() {
+            return version_path;
+        }
+        version += 1;
+    }
+}
+
+/// Get the path of the record left in the cache when an audio file is moved to the output.
+fn get_moved_path(source: &Path) -> PathBuf {
+    let mut path = OsString::from(source.as_os_str());
+    path.push(".");
+    path.push(MOVED_EXTENSION);
+    PathBuf::from(path)
+}
+
+/// Sort the episodes to download and defer those over the limit or byte budget.
+///
+/// Budgets use the audio size declared by the feed. An episode that doesn't fit is
+/// deferred but smaller episodes after it may still be downloaded.
+fn apply_limits(
+    mut episodes: Vec<Episode>,
+    options: &DownloadOptions,
+    report: &mut DownloadReport,
+) -> Vec<Episode> {
+    match options.order {
+        DownloadOrder::Feed => {}
+        DownloadOrder::Newest => episodes.sort_by_key(|episode| Reverse(episode.published_at)),
+        DownloadOrder::Oldest => episodes.sort_by_key(|episode| episode.published_at),
+        DownloadOrder::Smallest => episodes.sort_by_key(|episode| episode.audio_file_size),
+    }
+    let limit = options.limit.unwrap_or(usize::MAX);
+    let budget = options.max_bytes.unwrap_or(u64::MAX);
+    let mut bytes: u64 = 0;
+    let mut selected = Vec::new();
+    let mut deferred = 0;
+    for episode in episodes {
+        let total = bytes.saturating_add(episode.audio_file_size);
+        if selected.len() >= limit || total > budget {
+            trace!("{} episode: {episode}", "Deferring".bold());
+            report
+                .episodes
+                .push(EpisodeOutcome::new(&episode, Outcome::Deferred));
+            deferred += 1;
+            continue;
+        }
+        bytes = total;
+        selected.push(episode);
+    }
+    if deferred > 0 {
+        info!(
+            "{} {deferred} episodes over the limits of this run",
+            "Deferred".bold()
+        );
+    }
+    selected
+}
+
+/// Is an episode matched by the filter and, if retrying, in the retry IDs?
+fn is_selected(
+    episode: &Episode,
+    matches: &HashSet<String>,
+    retry: Option<&HashSet<String>>,
+) -> bool {
+    matches.contains(&episode.id) && retry.is_none_or(|ids| ids.contains(&episode.id))
+}
+
+/// Log the selected episodes and report them as matched without downloading.
+fn get_dry_run_report(
+    podcast: &Podcast,
+    filter: &EpisodeFilter,
+    retry: Option<&HashSet<String>>,
+) -> DownloadReport {
+    let matches = filter.get_matches(&podcast.episodes);
+    let mut report = DownloadReport::new(&podcast.id);
+    let mut selected = Vec::new();
+    for episode in &podcast.episodes {
+        let outcome = if is_selected(episode, &matches, retry) {
+            selected.push(episode.clone());
+            Outcome::Matched
+        } else {
+            Outcome::Filtered
+        };
+        report.episodes.push(EpisodeOutcome::new(episode, outcome));
+    }
+    EpisodeFilter::log_matches(&podcast.id, &selected);
+    report
+}
+
+fn get_failed_outcome(episode: &Episode, error: &ProcessError) -> EpisodeOutcome {
+    if let ProcessError::DownloadAudio(_, HttpError::Cancelled(_))
+    | ProcessError::DownloadImage(_, HttpError::Cancelled(_)) = error
+    {
+        return EpisodeOutcome::new(episode, Outcome::Cancelled);
+    }
+    let mut outcome = EpisodeOutcome::new(episode, Outcome::Failed);
+    outcome.error = Some(ErrorOutput::new(error).with_episode(episode));
+    outcome
+}
+
 #[allow(clippy::absolute_paths)]
-#[derive(Debug)]
+#[derive(Debug, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
 pub enum DownloadError {
     GetPodcast(DatabaseError),
+    Client(HttpError),
+    IpChanged(HttpError),
+    Report(DatabaseError),
+    NoReport(String),
+    Lock(LockError),
 }
 
 impl Display for DownloadError {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         let reason = match self {
             DownloadError::GetPodcast(e) => format!("Unable to get podcast\n{e}"),
+            DownloadError::Client(e) | DownloadError::IpChanged(e) => format!("{e}"),
+            DownloadError::Report(e) => format!("Unable to read the last report\n{e}"),
+            DownloadError::NoReport(id) => format!("No previous download report of {id} to retry"),
+            DownloadError::Lock(e) => format!("Unable to lock podcast\n{e}"),
         };
         write!(f, "{} to download\n{reason}", "Failed".bold())
     }
 }
 
-#[derive(Debug)]
+impl ErrorCode for DownloadError {
+    fn get_code(&self) -> String {
+        format!("download.{}", <&str>::from(self))
+    }
+}
+
+#[derive(Debug, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
 #[allow(clippy::absolute_paths)]
 pub enum ProcessError {
     DownloadAudio(String, HttpError),
@@ -199,6 +663,7 @@ pub enum ProcessError {
     DownloadImage(String, HttpError),
     Task(String, JoinError),
     ResizeImage(String, ImageError),
+    Lock(String, LockError),
 }
 
 impl Display for ProcessError {
@@ -228,43 +693,59 @@ impl Display for ProcessError {
             ProcessError::ResizeImage(id, e) => {
                 format!("Unable to resize image for episode: {id}\n{e}")
             }
+            ProcessError::Lock(id, e) => {
+                format!("Unable to lock podcast for episode: {id}\n{e}")
+            }
         };
         write!(f, "{message}")
     }
 }
 
+impl ErrorCode for ProcessError {
+    fn get_code(&self) -> String {
+        format!("download.{}", <&str>::from(self))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::TimeDelta;
+    use lofty::prelude::{Accessor, TaggedFileExt};
+    use std::time::Duration;
+    use tokio::fs::read;
+    use tokio::time::sleep;
 
     #[tokio::test]
     pub async fn download_command() {
         // Arrange
         let _ = init_logging();
-        let services = ServiceProvider::create()
-            .await
-            .expect("ServiceProvider should not fail");
+        let services = create_fixture_services("pura-download-simplecast").await;
+        scrape_simplecast_fixture(&services).await;
         let command = DownloadCommand::new(services.paths, services.http, services.podcasts);
         let options = DownloadOptions {
-            podcast_id: "irl".to_owned(),
-            year: Some(2019),
+            filter: EpisodeFilter {
+                year: Some(2019),
+                ..EpisodeFilter::default()
+            },
+            ..DownloadOptions::example(SIMPLECAST_FIXTURE_PODCAST_ID)
         };
 
         // Act
         let result = command.execute(options).await;
 
         // Assert
-        result.assert_ok();
+        let report = result.assert_ok();
+        assert_eq!(report.get_episodes(Outcome::Downloaded).len(), 2);
+        assert_eq!(report.get_episodes(Outcome::Filtered).len(), 1);
     }
 
     #[tokio::test]
     pub async fn process_episode() {
         // Arrange
         let _ = init_logging();
-        let services = ServiceProvider::create()
-            .await
-            .expect("ServiceProvider should not fail");
-        let podcast = services.podcasts.get("irl").expect("podcast should exist");
+        let services = create_fixture_services("pura-download-process").await;
+        let podcast = scrape_simplecast_fixture(&services).await;
         let command = DownloadCommand::new(services.paths, services.http, services.podcasts);
         let episode = podcast
             .episodes
@@ -273,9 +754,322 @@ mod tests {
             .clone();
 
         // Act
-        let result = command.process_episode(&podcast, episode).await;
+        let result = command
+            .process_episode(&podcast, episode, TransferMode::default())
+            .await;
+
+        // Assert
+        let (path, redirects) = result.assert_ok();
+        assert!(path.exists());
+        assert_eq!(redirects, None);
+    }
+
+    #[tokio::test]
+    pub async fn download_command_replay() {
+        // Arrange
+        let _ = init_logging();
+        let services = create_fixture_services("pura-download").await;
+        let podcast = scrape_fixture(&services).await;
+        let paths = services.paths.clone();
+        let podcasts = services.podcasts.clone();
+        let command = DownloadCommand::new(services.paths, services.http, services.podcasts);
+        let options = DownloadOptions::example(FIXTURE_PODCAST_ID);
+
+        // Act
+        let result = command.execute(options).await;
+
+        // Assert
+        let report = result.assert_ok();
+        assert_eq!(
+            report.get_episodes(Outcome::Downloaded).len(),
+            podcast.episodes.len()
+        );
+        assert!(report.get_errors().is_empty());
+        for episode in &podcast.episodes {
+            let path = paths.get_output_path_for_audio(&podcast.id, episode);
+            let file = lofty::read_from_path(&path).expect("audio should be readable");
+            let tag = file.primary_tag().expect("audio should be tagged");
+            assert_eq!(tag.title().as_deref(), Some(episode.title.as_str()));
+        }
+        let downloads = podcasts.get_downloads(&podcast.id).assert_ok_debug();
+        assert_eq!(downloads.len(), podcast.episodes.len());
+    }
+
+    #[tokio::test]
+    pub async fn download_command_retry_failed() {
+        // Arrange
+        let _ = init_logging();
+        let services = create_fixture_services("pura-download-retry").await;
+        let mut podcast = scrape_fixture(&services).await;
+        let paths = services.paths.clone();
+        let podcasts = services.podcasts.clone();
+        let command = DownloadCommand::new(services.paths, services.http, services.podcasts);
+        let options = |retry_failed| DownloadOptions {
+            retry_failed,
+            ..DownloadOptions::example(FIXTURE_PODCAST_ID)
+        };
+        let episode = podcast
+            .episodes
+            .first_mut()
+            .expect("should be at least one episode");
+        let audio_url = episode.audio_url.clone();
+        episode.audio_url =
+            Url::parse("https://media.example.com/fixture/missing.mp3").expect("URL should parse");
+        let id = episode.id.clone();
+        podcasts.put(&podcast).assert_ok_debug();
+        let no_report = command.execute(options(true)).await;
+        let failed = command.execute(options(false)).await.assert_ok_debug();
+        if let Some(episode) = podcast.episodes.first_mut() {
+            episode.audio_url = audio_url;
+        }
+        podcasts.put(&podcast).assert_ok_debug();
+
+        // Act
+        let retried = command.execute(options(true)).await;
+
+        // Assert
+        assert!(matches!(no_report, Err(DownloadError::NoReport(_))));
+        let errors = failed.get_errors();
+        assert_eq!(errors.len(), 1);
+        let error = errors.first().expect("should have an error");
+        assert_eq!(error.code, "download.download_audio");
+        assert_eq!(error.episode.as_ref(), Some(&id));
+        let retried = retried.assert_ok_debug();
+        let downloaded = retried.get_episodes(Outcome::Downloaded);
+        assert_eq!(downloaded.len(), 1);
+        assert_eq!(
+            downloaded.first().map(|outcome| &outcome.episode),
+            Some(&id)
+        );
+        assert!(downloaded
+            .first()
+            .is_some_and(|outcome| outcome.bytes > Some(0)));
+        assert_eq!(
+            retried.get_episodes(Outcome::Filtered).len(),
+            podcast.episodes.len() - 1
+        );
+        let last = get_last_report(&paths.get_reports_dir(), &podcast.id).assert_ok_debug();
+        assert_eq!(last, Some(retried));
+    }
+
+    #[tokio::test]
+    pub async fn download_command_dry_run() {
+        // Arrange
+        let _ = init_logging();
+        let services = create_fixture_services("pura-download-dry-run").await;
+        let podcast = scrape_fixture(&services).await;
+        let paths = services.paths.clone();
+        let command = DownloadCommand::new(services.paths, services.http, services.podcasts);
+        let options = DownloadOptions {
+            filter: EpisodeFilter {
+                expression: Some(EpisodeFilter::parse("latest:2").assert_ok_debug()),
+                dry_run: true,
+                ..EpisodeFilter::default()
+            },
+            ..DownloadOptions::example(FIXTURE_PODCAST_ID)
+        };
+
+        // Act
+        let result = command.execute(options).await;
+
+        // Assert
+        let report = result.assert_ok_debug();
+        assert_eq!(report.get_episodes(Outcome::Matched).len(), 2);
+        assert_eq!(
+            report.get_episodes(Outcome::Filtered).len(),
+            podcast.episodes.len() - 2
+        );
+        for episode in &podcast.episodes {
+            let path = paths.get_output_path_for_audio(&podcast.id, episode);
+            assert!(!path.exists());
+        }
+        let last = get_last_report(&paths.get_reports_dir(), &podcast.id).assert_ok_debug();
+        assert_eq!(last, None);
+    }
+
+    #[tokio::test]
+    pub async fn download_command_limits() {
+        // Arrange
+        let _ = init_logging();
+        let services = create_fixture_services("pura-download-limits").await;
+        let podcast = scrape_fixture(&services).await;
+        let command = DownloadCommand::new(services.paths, services.http, services.podcasts);
+        let options = |limit, max_bytes| DownloadOptions {
+            order: DownloadOrder::Oldest,
+            limit,
+            max_bytes,
+            concurrency: 1,
+            ..DownloadOptions::example(FIXTURE_PODCAST_ID)
+        };
+        let newest = podcast
+            .episodes
+            .iter()
+            .max_by_key(|episode| episode.published_at)
+            .expect("should be at least one episode");
+        let size = newest.audio_file_size;
+
+        // Act
+        let limited = command.execute(options(Some(2), None)).await;
+        let over_budget = command.execute(options(None, Some(size - 1))).await;
+        let within_budget = command.execute(options(None, Some(size))).await;
+
+        // Assert
+        let limited = limited.assert_ok_debug();
+        assert_eq!(limited.get_episodes(Outcome::Downloaded).len(), 2);
+        let deferred = limited.get_episodes(Outcome::Deferred);
+        assert_eq!(deferred.len(), 1);
+        assert_eq!(
+            deferred.first().map(|outcome| &outcome.episode),
+            Some(&newest.id)
+        );
+        let over_budget = over_budget.assert_ok_debug();
+        assert_eq!(over_budget.get_episodes(Outcome::Deferred).len(), 1);
+        assert!(over_budget.get_errors().is_empty());
+        let within_budget = within_budget.assert_ok_debug();
+        assert_eq!(within_budget.get_episodes(Outcome::Downloaded).len(), 1);
+        assert_eq!(within_budget.get_episodes(Outcome::Existing).len(), 2);
+    }
+
+    #[tokio::test]
+    pub async fn download_command_cancelled() {
+        // Arrange
+        let _ = init_logging();
+        let services = create_fixture_services("pura-download-cancelled").await;
+        let podcast = scrape_fixture(&services).await;
+        let paths = services.paths.clone();
+        services.cancellation.cancel();
+        let command = DownloadCommand::new(services.paths, services.http, services.podcasts);
+        let options = DownloadOptions::example(FIXTURE_PODCAST_ID);
+
+        // Act
+        let result = command.execute(options).await;
+
+        // Assert
+        let report = result.assert_ok_debug();
+        assert_eq!(
+            report.get_episodes(Outcome::Cancelled).len(),
+            podcast.episodes.len()
+        );
+        assert_eq!(report.get_retry_ids().len(), podcast.episodes.len());
+        let last = get_last_report(&paths.get_reports_dir(), &podcast.id).assert_ok_debug();
+        assert_eq!(last, Some(report));
+    }
+
+    #[tokio::test]
+    pub async fn download_command_unlocked_while_fetching() {
+        // Arrange
+        let _ = init_logging();
+        let services = ServiceProvider::from_options(create_temp_options("pura-download-unlocked"))
+            .await
+            .expect("ServiceProvider should not fail");
+        let body = read(get_fixtures_dir().join("media.example.com/fixture/episode-1.mp3"))
+            .await
+            .expect("should read fixture audio");
+        let (url, requests) =
+            TestServer::start_with_pause("audio/mpeg", body, Duration::from_millis(500)).await;
+        let mut podcast = Podcast::example();
+        let episode = podcast
+            .episodes
+            .first_mut()
+            .expect("should have an episode");
+        episode.audio_url = url.join("episode.mp3").expect("URL should be valid");
+        episode.image_url = None;
+        services.podcasts.put(&podcast).assert_ok_debug();
+        let podcasts = services.podcasts.clone();
+        let command = DownloadCommand::new(services.paths, services.http, services.podcasts);
+        let options = DownloadOptions::example(&podcast.id);
+
+        // Act
+        let download = tokio::spawn(async move { command.execute(options).await });
+        while requests
+            .lock()
+            .expect("lock should not be poisoned")
+            .is_empty()
+        {
+            sleep(Duration::from_millis(10)).await;
+        }
+        let fetching = podcasts.lock(&podcast.id).await.map(drop);
+        let result = download.await.expect("task should not panic");
+
+        // Assert
+        fetching.assert_ok_debug();
+        let report = result.assert_ok_debug();
+        assert_eq!(report.get_episodes(Outcome::Downloaded).len(), 1);
+        let saved = podcasts.get(&podcast.id).assert_ok_debug();
+        let saved = saved.episodes.first().expect("should have an episode");
+        assert_eq!(saved.download_url.as_ref(), Some(&saved.audio_url));
+        assert!(saved.download_redirects.is_empty());
+    }
+
+    #[tokio::test]
+    pub async fn download_command_keep_versions() {
+        // Arrange
+        let _ = init_logging();
+        let services = create_fixture_services("pura-download-versions").await;
+        let mut podcast = scrape_fixture(&services).await;
+        let paths = services.paths.clone();
+        let podcasts = services.podcasts.clone();
+        let command = DownloadCommand::new(services.paths, services.http, services.podcasts);
+        let options = || DownloadOptions {
+            keep_versions: true,
+            ..DownloadOptions::example(FIXTURE_PODCAST_ID)
+        };
+        command.execute(options()).await.assert_ok();
+        let episode = podcast
+            .episodes
+            .first_mut()
+            .expect("should be at least one episode");
+        episode.history.push(EpisodeChange {
+            changed_at: Utc::now().naive_utc() + TimeDelta::seconds(1),
+            field: "audio_file_size".to_owned(),
+            old: Some("1".to_owned()),
+            new: Some(episode.audio_file_size.to_string()),
+        });
+        let episode = episode.clone();
+        podcasts.put(&podcast).assert_ok_debug();
+
+        // Act
+        let result = command.execute(options()).await;
 
         // Assert
         result.assert_ok();
+        let path = paths.get_output_path_for_audio(&podcast.id, &episode);
+        assert!(path.exists());
+        assert!(get_version_path(&path)
+            .to_string_lossy()
+            .ends_with("(v2).mp3"));
+        let other = podcast.episodes.get(1).expect("should be two episodes");
+        let other_path = paths.get_output_path_for_audio(&podcast.id, other);
+        assert!(get_version_path(&other_path)
+            .to_string_lossy()
+            .ends_with("(v1).mp3"));
+    }
+
+    #[tokio::test]
+    pub async fn transfer_file_modes() {
+        // Arrange
+        let _ = init_logging();
+        let dir = TempDirectory::create("pura-transfer");
+        let modes = [
+            TransferMode::Copy,
+            TransferMode::Hardlink,
+            TransferMode::Reflink,
+            TransferMode::Move,
+        ];
+        for mode in modes {
+            let source = dir.join(format!("{mode:?}.mp3"));
+            let destination = dir.join(format!("{mode:?}-output.mp3"));
+            write(&source, b"audio").await.expect("should write source");
+
+            // Act
+            let result = transfer_file(&source, &destination, mode).await;
+
+            // Assert
+            result.assert_ok_debug();
+            let contents = read(&destination).await.expect("should read destination");
+            assert_eq!(contents, b"audio");
+            assert_eq!(source.exists(), mode != TransferMode::Move);
+            assert_eq!(get_moved_path(&source).exists(), mode == TransferMode::Move);
+        }
     }
 }
@@ -1,5 +1,7 @@
 pub use download::*;
 pub use options::*;
+pub use report::*;
 
 mod download;
 mod options;
+mod report;
@@ -1,5 +1,8 @@
 use crate::prelude::*;
 
+/// Default number of episodes downloaded concurrently.
+pub(crate) const DOWNLOAD_CONCURRENCY: usize = 8;
+
 #[derive(Debug, Args)]
 pub struct DownloadOptions {
     /// ID of the downloaded podcast
@@ -7,6 +10,102 @@ pub struct DownloadOptions {
     /// Must be alphanumeric and hyphenated
     #[arg(value_parser = Podcast::validate_id)]
     pub podcast_id: String,
-    /// Optional year filter
+    /// Only episodes published in a year
+    ///
+    /// Deprecated in favour of `--year` but kept so existing scripts still work.
+    #[arg(
+        id = "positional_year",
+        value_name = "YEAR",
+        hide = true,
+        conflicts_with = "year"
+    )]
     pub year: Option<i32>,
+    /// Which episodes to download
+    #[command(flatten)]
+    pub filter: EpisodeFilter,
+    /// How audio files are transferred from the cache to the output directory
+    #[arg(long, value_enum, default_value_t)]
+    pub transfer: TransferMode,
+    /// Keep the previous audio file when the publisher changes the enclosure URL or size
+    ///
+    /// The previous file is renamed with a version suffix and the new audio is downloaded.
+    #[arg(long)]
+    pub keep_versions: bool,
+    /// Only attempt the episodes that failed or were aborted in the last run
+    ///
+    /// Episodes are retried even if their audio is already in the output directory.
+    #[arg(long)]
+    pub retry_failed: bool,
+    /// Order in which episodes are downloaded
+    #[arg(long, value_enum, default_value_t)]
+    pub order: DownloadOrder,
+    /// Maximum number of episodes to download in this run
+    ///
+    /// Further episodes are deferred to a later run.
+    #[arg(long, value_name = "COUNT")]
+    pub limit: Option<usize>,
+    /// Maximum total size of the audio downloaded in this run
+    ///
+    /// Accepts units such as `500MB` or `20GB`. Sizes are taken from the feed.
+    /// Episodes that would exceed the budget are deferred and smaller episodes
+    /// later in the order may still be downloaded.
+    #[arg(long, value_name = "SIZE", value_parser = parse_bytes)]
+    pub max_bytes: Option<u64>,
+    /// Maximum number of episodes downloaded concurrently
+    #[arg(long, default_value_t = DOWNLOAD_CONCURRENCY)]
+    pub concurrency: usize,
+}
+
+impl DownloadOptions {
+    /// Options to download every episode of a podcast with the defaults of the CLI.
+    #[cfg(test)]
+    pub(crate) fn example(podcast_id: &str) -> Self {
+        Self {
+            podcast_id: podcast_id.to_owned(),
+            year: None,
+            filter: EpisodeFilter::default(),
+            transfer: TransferMode::default(),
+            keep_versions: false,
+            retry_failed: false,
+            order: DownloadOrder::default(),
+            limit: None,
+            max_bytes: None,
+            concurrency: DOWNLOAD_CONCURRENCY,
+        }
+    }
+}
+
+/// Order in which episodes are downloaded
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum DownloadOrder {
+    /// Order of the feed
+    #[default]
+    Feed,
+    /// Most recently published first
+    Newest,
+    /// Least recently published first
+    Oldest,
+    /// Smallest audio file first
+    Smallest,
+}
+
+/// How audio files are transferred from the cache to the output directory
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum TransferMode {
+    /// Copy the file, keeping the original in the cache
+    #[default]
+    Copy,
+    /// Hard link the file
+    ///
+    /// The cache and output share the same data so tagging the output also changes the cache.
+    /// Falls back to copy if the directories are on different file systems.
+    Hardlink,
+    /// Reflink the file where the file system supports copy-on-write
+    ///
+    /// Falls back to copy if the file system does not support reflinks.
+    Reflink,
+    /// Move the file and record the destination in the cache
+    ///
+    /// The tagged output file becomes the only copy of the audio.
+    Move,
 }
@@ -0,0 +1,283 @@
+use crate::prelude::*;
+use chrono::Utc;
+use std::collections::HashSet;
+use std::fs::{create_dir_all as create_dir_all_sync, read_dir, OpenOptions};
+use std::io::ErrorKind;
+
+const REPORT_PREFIX: &str = "download-";
+const REPORT_EXTENSION: &str = "json";
+const REPORT_NAME_FORMAT: &str = "%Y%m%dT%H%M%S%3f";
+
+/// Result of downloading the episodes of a podcast.
+///
+/// Each run is saved as JSON in the reports directory of the cache.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct DownloadReport {
+    /// ID of the podcast
+    pub podcast_id: String,
+    /// Date and time the run started
+    pub started_at: NaiveDateTime,
+    /// Date and time the run finished
+    pub finished_at: NaiveDateTime,
+    /// Outcome of every episode of the podcast in feed order
+    pub episodes: Vec<EpisodeOutcome>,
+    /// Error that stopped the run before every episode was attempted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aborted: Option<ErrorOutput>,
+}
+
+/// Outcome of an episode in a download run
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct EpisodeOutcome {
+    /// ID of the episode
+    pub episode: String,
+    /// What happened to the episode
+    pub outcome: Outcome,
+    /// Path of the audio file in the output directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    /// Size of the audio file in the output directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>,
+    /// Date and time processing of the episode started
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<NaiveDateTime>,
+    /// Milliseconds spent processing the episode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    /// Error of a failed episode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorOutput>,
+    /// Redirects followed to fetch the audio, the last being the URL it was fetched from
+    ///
+    /// Only set if the audio was fetched rather than already in the cache.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redirects: Option<Vec<Url>>,
+}
+
+/// What happened to an episode in a download run
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// Audio was downloaded, transferred and tagged
+    Downloaded,
+    /// Audio was already in the output directory
+    Existing,
+    /// Episode was excluded by the options
+    Filtered,
+    /// Episode matched the filter of a dry run
+    Matched,
+    /// Processing the episode failed
+    Failed,
+    /// Episode was not attempted as the run was aborted
+    Aborted,
+    /// Episode was not attempted or was stopped as the run was cancelled
+    Cancelled,
+    /// Episode was not attempted as it exceeded the limit or byte budget of the run
+    Deferred,
+}
+
+impl DownloadReport {
+    pub(crate) fn new(podcast_id: &str) -> Self {
+        let now = Utc::now().naive_utc();
+        Self {
+            podcast_id: podcast_id.to_owned(),
+            started_at: now,
+            finished_at: now,
+            episodes: Vec::new(),
+            aborted: None,
+        }
+    }
+
+    /// Get the episodes with an outcome.
+    #[must_use]
+    pub fn get_episodes(&self, outcome: Outcome) -> Vec<&EpisodeOutcome> {
+        self.episodes
+            .iter()
+            .filter(|episode| episode.outcome == outcome)
+            .collect()
+    }
+
+    /// Did the run fail without downloading or finding any episode?
+    ///
+    /// A run that only failed some episodes is a partial failure.
+    #[must_use]
+    pub fn is_failed(&self) -> bool {
+        let succeeded = self
+            .episodes
+            .iter()
+            .any(|episode| matches!(episode.outcome, Outcome::Downloaded | Outcome::Existing));
+        let failed = self.aborted.is_some()
+            || self
+                .episodes
+                .iter()
+                .any(|episode| episode.outcome == Outcome::Failed);
+        failed && !succeeded
+    }
+
+    /// Get the IDs of the episodes that failed, were aborted or were cancelled.
+    #[must_use]
+    pub fn get_retry_ids(&self) -> HashSet<String> {
+        self.episodes
+            .iter()
+            .filter(|episode| {
+                matches!(
+                    episode.outcome,
+                    Outcome::Failed | Outcome::Aborted | Outcome::Cancelled
+                )
+            })
+            .map(|episode| episode.episode.clone())
+            .collect()
+    }
+
+    /// Get the errors of the failed episodes and the error that aborted the run.
+    #[must_use]
+    pub fn get_errors(&self) -> Vec<ErrorOutput> {
+        self.episodes
+            .iter()
+            .filter_map(|episode| episode.error.clone())
+            .chain(self.aborted.clone())
+            .collect()
+    }
+
+    pub(crate) fn get_name(&self) -> String {
+        format!(
+            "{REPORT_PREFIX}{}",
+            self.started_at.format(REPORT_NAME_FORMAT)
+        )
+    }
+}
+
+impl EpisodeOutcome {
+    pub(crate) fn new(episode: &Episode, outcome: Outcome) -> Self {
+        Self {
+            episode: episode.id.clone(),
+            outcome,
+            path: None,
+            bytes: None,
+            started_at: None,
+            duration_ms: None,
+            error: None,
+            redirects: None,
+        }
+    }
+
+    /// Set the path and size of the audio file.
+    #[must_use]
+    pub(crate) fn with_path(mut self, path: PathBuf) -> Self {
+        self.bytes = path.metadata().ok().map(|metadata| metadata.len());
+        self.path = Some(path);
+        self
+    }
+
+    /// Set the redirects followed to fetch the audio.
+    #[must_use]
+    pub(crate) fn with_redirects(mut self, redirects: Option<Vec<Url>>) -> Self {
+        self.redirects = redirects;
+        self
+    }
+}
+
+/// Save a download report to `<dir>/<podcast_id>/download-<started_at>.json`.
+///
+/// A report is never overwritten. If a run started at the same millisecond a
+/// numbered suffix is added that sorts after the existing report.
+pub(crate) fn put_report(dir: &Path, report: &DownloadReport) -> Result<PathBuf, DatabaseError> {
+    let dir = dir.join(&report.podcast_id);
+    create_dir_all_sync(&dir).map_err(|e| DatabaseError::Io(dir.clone(), e))?;
+    let name = report.get_name();
+    let mut path = dir.join(&name).with_extension(REPORT_EXTENSION);
+    let mut suffix = 1;
+    let file = loop {
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => break file,
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                path = dir
+                    .join(format!("{name}_{suffix}"))
+                    .with_extension(REPORT_EXTENSION);
+                suffix += 1;
+            }
+            Err(e) => return Err(DatabaseError::Io(path, e)),
+        }
+    };
+    serde_json::to_writer_pretty(BufWriter::new(file), report)
+        .map_err(|e| DatabaseError::Json(report.podcast_id.clone(), e))?;
+    Ok(path)
+}
+
+/// Get the most recent download report of a podcast.
+pub(crate) fn get_last_report(
+    dir: &Path,
+    podcast_id: &str,
+) -> Result<Option<DownloadReport>, DatabaseError> {
+    let dir = dir.join(podcast_id);
+    if !dir.exists() {
+        return Ok(None);
+    }
+    let entries = read_dir(&dir).map_err(|e| DatabaseError::Io(dir.clone(), e))?;
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(|e| DatabaseError::Io(dir.clone(), e))?.path();
+        let is_report = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with(REPORT_PREFIX))
+            && path.extension() == Some(REPORT_EXTENSION.as_ref());
+        if is_report {
+            paths.push(path);
+        }
+    }
+    let Some(path) = paths.into_iter().max() else {
+        return Ok(None);
+    };
+    let file = File::open(&path).map_err(|e| DatabaseError::Io(path.clone(), e))?;
+    serde_json::from_reader(BufReader::new(file))
+        .map(Some)
+        .map_err(|e| DatabaseError::Json(podcast_id.to_owned(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    #[test]
+    fn put_report_never_overwrites() {
+        // Arrange
+        let dir = TempDirectory::create("pura-report");
+        let first = DownloadReport::new("example");
+        let mut second = first.clone();
+        second.finished_at += TimeDelta::seconds(1);
+
+        // Act
+        let first_path = put_report(&dir, &first).assert_ok_debug();
+        let second_path = put_report(&dir, &second).assert_ok_debug();
+        let last = get_last_report(&dir, "example").assert_ok_debug();
+
+        // Assert
+        assert_ne!(first_path, second_path);
+        assert_eq!(last, Some(second));
+    }
+
+    #[test]
+    fn report_is_failed() {
+        // Arrange
+        let episode = Episode::example();
+        let mut all_failed = DownloadReport::new("example");
+        all_failed
+            .episodes
+            .push(EpisodeOutcome::new(&episode, Outcome::Failed));
+        let mut partial = all_failed.clone();
+        partial
+            .episodes
+            .push(EpisodeOutcome::new(&episode, Outcome::Downloaded));
+
+        // Act
+        let empty = DownloadReport::new("example").is_failed();
+
+        // Assert
+        assert!(all_failed.is_failed());
+        assert!(!partial.is_failed());
+        assert!(!empty);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::prelude::*;
+use strum_macros::IntoStaticStr;
 
 pub struct EmulateCommand {
     podcasts: PodcastProvider,
@@ -11,17 +12,33 @@ impl EmulateCommand {
         Self { podcasts, paths }
     }
 
-    pub async fn execute(&self, options: EmulateOptions) -> Result<(), EmulateError> {
-        let podcast = self
+    /// Create the RSS feeds of a podcast and return their paths.
+    ///
+    /// A filter is only accepted for a dry run as the feeds must include every episode.
+    pub async fn execute(&self, options: EmulateOptions) -> Result<Vec<PathBuf>, EmulateError> {
+        if !options.filter.is_empty() && !options.filter.dry_run {
+            return Err(EmulateError::Filtered);
+        }
+        let _lock = self
+            .podcasts
+            .lock(&options.podcast_id)
+            .await
+            .map_err(EmulateError::Lock)?;
+        let mut podcast = self
             .podcasts
             .get(&options.podcast_id)
             .map_err(EmulateError::GetPodcast)?;
+        podcast.episodes = options.filter.apply(take(&mut podcast.episodes));
+        if options.filter.dry_run {
+            EpisodeFilter::log_matches(&podcast.id, &podcast.episodes);
+            return Ok(Vec::new());
+        }
         let feeds = self.save_feeds(&podcast).await?;
         info!("{} {} rss feeds", "Created".bold(), feeds.len());
-        Ok(())
+        Ok(feeds)
     }
 
-    async fn save_feeds(&self, podcast: &Podcast) -> Result<Vec<PathBuf>, EmulateError> {
+    pub(crate) async fn save_feeds(&self, podcast: &Podcast) -> Result<Vec<PathBuf>, EmulateError> {
         let mut paths = Vec::new();
         paths.push(self.save_feed(podcast, None, None).await?);
         let mut podcast = podcast.clone();
@@ -106,10 +123,13 @@ fn group_by_year(episodes: Vec<Episode>) -> HashMap<i32, Vec<Episode>> {
 }
 
 #[allow(clippy::absolute_paths)]
-#[derive(Debug)]
+#[derive(Debug, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
 pub enum EmulateError {
     GetPodcast(DatabaseError),
     Xml(PathBuf, std::io::Error),
+    Lock(LockError),
+    Filtered,
 }
 
 impl Display for EmulateError {
@@ -119,11 +139,21 @@ impl Display for EmulateError {
             EmulateError::Xml(path, e) => {
                 format!("Unable to write RSS\nPath: {}\n{e}", path.display())
             }
+            EmulateError::Lock(e) => format!("Unable to lock podcast\n{e}"),
+            EmulateError::Filtered => {
+                "A filter would remove episodes from the feeds\nUse --dry-run to preview the matching episodes".to_owned()
+            }
         };
         write!(f, "{} to create RSS feeds\n{reason}", "Failed".bold())
     }
 }
 
+impl ErrorCode for EmulateError {
+    fn get_code(&self) -> String {
+        format!("emulate.{}", <&str>::from(self))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -132,12 +162,13 @@ mod tests {
     pub async fn feeds_command() {
         // Arrange
         let _ = init_logging();
-        let services = ServiceProvider::create()
-            .await
-            .expect("ServiceProvider should not fail");
+        let services = create_fixture_services("pura-emulate-simplecast").await;
+        scrape_simplecast_fixture(&services).await;
+        let paths = services.paths.clone();
         let command = EmulateCommand::new(services.podcasts, services.paths);
         let options = EmulateOptions {
-            podcast_id: "irl".to_owned(),
+            podcast_id: SIMPLECAST_FIXTURE_PODCAST_ID.to_owned(),
+            filter: EpisodeFilter::default(),
         };
 
         // Act
@@ -145,5 +176,93 @@ mod tests {
 
         // Assert
         result.assert_ok();
+        assert!(paths
+            .get_output_path_for_rss(SIMPLECAST_FIXTURE_PODCAST_ID, None, None)
+            .exists());
+    }
+
+    #[tokio::test]
+    pub async fn emulate_command_replay() {
+        // Arrange
+        let _ = init_logging();
+        let services = create_fixture_services("pura-emulate").await;
+        scrape_fixture(&services).await;
+        let paths = services.paths.clone();
+        let command = EmulateCommand::new(services.podcasts, services.paths);
+        let options = EmulateOptions {
+            podcast_id: FIXTURE_PODCAST_ID.to_owned(),
+            filter: EpisodeFilter::default(),
+        };
+
+        // Act
+        let result = command.execute(options).await;
+
+        // Assert
+        let feeds = result.assert_ok();
+        let feed = paths.get_output_path_for_rss(FIXTURE_PODCAST_ID, None, None);
+        assert_eq!(feeds.first(), Some(&feed));
+        let file = File::open(feed).expect("feed should exist");
+        let channel = RssChannel::read_from(BufReader::new(file)).expect("feed should be valid");
+        assert_eq!(channel.items.len(), 3);
+        let season = paths.get_output_path_for_rss(FIXTURE_PODCAST_ID, Some(2), Some(2023));
+        assert!(season.exists());
+    }
+
+    #[tokio::test]
+    pub async fn emulate_command_refuses_filter() {
+        // Arrange
+        let _ = init_logging();
+        let services = create_fixture_services("pura-emulate-filter").await;
+        scrape_fixture(&services).await;
+        let paths = services.paths.clone();
+        let command = EmulateCommand::new(services.podcasts, services.paths);
+        let filter = EpisodeFilter {
+            latest: Some(1),
+            ..EpisodeFilter::default()
+        };
+        let options = |dry_run| EmulateOptions {
+            podcast_id: FIXTURE_PODCAST_ID.to_owned(),
+            filter: EpisodeFilter {
+                dry_run,
+                ..filter.clone()
+            },
+        };
+
+        // Act
+        let filtered = command.execute(options(false)).await;
+        let dry_run = command.execute(options(true)).await;
+
+        // Assert
+        assert!(matches!(filtered, Err(EmulateError::Filtered)));
+        assert_eq!(dry_run.assert_ok(), Vec::<PathBuf>::new());
+        let feed = paths.get_output_path_for_rss(FIXTURE_PODCAST_ID, None, None);
+        assert!(!feed.exists());
+    }
+
+    #[tokio::test]
+    pub async fn emulate_command_locked() {
+        // Arrange
+        let _ = init_logging();
+        let services = create_fixture_services("pura-emulate-locked").await;
+        scrape_fixture(&services).await;
+        let lock = services
+            .podcasts
+            .lock(FIXTURE_PODCAST_ID)
+            .await
+            .assert_ok_debug();
+        let command = EmulateCommand::new(services.podcasts.clone(), services.paths);
+        let options = || EmulateOptions {
+            podcast_id: FIXTURE_PODCAST_ID.to_owned(),
+            filter: EpisodeFilter::default(),
+        };
+
+        // Act
+        let locked = command.execute(options()).await;
+        drop(lock);
+        let unlocked = command.execute(options()).await;
+
+        // Assert
+        assert!(matches!(locked, Err(EmulateError::Lock(_))));
+        unlocked.assert_ok();
     }
 }
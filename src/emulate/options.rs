@@ -7,4 +7,9 @@ pub struct EmulateOptions {
     /// Must be alphanumeric and hyphenated
     #[arg(value_parser = Podcast::validate_id)]
     pub podcast_id: String,
+    /// Which episodes to preview with `--dry-run`
+    ///
+    /// Feeds always include every episode so a filter requires `--dry-run`.
+    #[command(flatten)]
+    pub filter: EpisodeFilter,
 }
@@ -0,0 +1,123 @@
+use crate::prelude::*;
+use strum_macros::IntoStaticStr;
+
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+pub struct HistoryCommand {
+    podcasts: PodcastProvider,
+}
+
+impl HistoryCommand {
+    #[must_use]
+    pub fn new(podcasts: PodcastProvider) -> Self {
+        Self { podcasts }
+    }
+
+    /// Log the timeline of an episode and return its recorded changes.
+    pub fn execute(&self, options: &HistoryOptions) -> Result<Vec<EpisodeChange>, HistoryError> {
+        let podcast = self
+            .podcasts
+            .get(&options.podcast_id)
+            .map_err(HistoryError::GetPodcast)?;
+        let episode = find_episode(podcast.episodes, &options.episode)?;
+        info!("{} of {episode}", "History".bold());
+        info!(
+            "{} Published",
+            episode.published_at.naive_utc().format(DATE_FORMAT)
+        );
+        for change in &episode.history {
+            info!("{} {change}", change.changed_at.format(DATE_FORMAT));
+        }
+        if episode.history.is_empty() {
+            info!("No changes have been seen since the episode was scraped");
+        }
+        Ok(episode.history)
+    }
+}
+
+/// Find an episode by GUID, then file stem, then case insensitive title.
+fn find_episode(episodes: Vec<Episode>, query: &str) -> Result<Episode, HistoryError> {
+    let query = query.trim();
+    let lower = query.to_lowercase();
+    let mut matches: Vec<Episode> = Vec::new();
+    for episode in episodes {
+        if episode.id == query || episode.get_file_stem() == query {
+            return Ok(episode);
+        }
+        if episode.title.to_lowercase().contains(&lower) {
+            matches.push(episode);
+        }
+    }
+    match matches.len() {
+        0 => Err(HistoryError::NotFound(query.to_owned())),
+        1 => Ok(matches.remove(0)),
+        count => Err(HistoryError::Ambiguous(query.to_owned(), count)),
+    }
+}
+
+#[derive(Debug, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum HistoryError {
+    GetPodcast(DatabaseError),
+    NotFound(String),
+    Ambiguous(String, usize),
+}
+
+impl Display for HistoryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let reason = match self {
+            HistoryError::GetPodcast(e) => format!("Unable to get podcast\n{e}"),
+            HistoryError::NotFound(query) => format!("No episode matches: {query}"),
+            HistoryError::Ambiguous(query, count) => {
+                format!("{count} episodes match: {query}\nUse the GUID or file name instead")
+            }
+        };
+        write!(f, "{} to get history\n{reason}", "Failed".bold())
+    }
+}
+
+impl ErrorCode for HistoryError {
+    fn get_code(&self) -> String {
+        format!("history.{}", <&str>::from(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_command() {
+        // Arrange
+        let _ = init_logging();
+        let podcasts = PodcastProvider::new(TempDirectory::create("pura-history"));
+        let mut podcast = Podcast::example();
+        let mut episode = Episode::example();
+        episode.history.push(EpisodeChange {
+            changed_at: NaiveDateTime::default(),
+            field: "title".to_owned(),
+            old: Some("Draft".to_owned()),
+            new: Some(episode.title.clone()),
+        });
+        let mut other = Episode::example();
+        other.id = "other".to_owned();
+        other.title = "Lorem ipsum bonus".to_owned();
+        podcast.episodes = vec![episode.clone(), other];
+        podcasts.put(&podcast).assert_ok_debug();
+        let command = HistoryCommand::new(podcasts);
+        let options = |episode: &str| HistoryOptions {
+            podcast_id: podcast.id.clone(),
+            episode: episode.to_owned(),
+        };
+
+        // Act
+        let by_id = command.execute(&options(&episode.id));
+        let by_title = command.execute(&options("DOLOR SIT"));
+        let ambiguous = command.execute(&options("lorem"));
+
+        // Assert
+        assert_eq!(by_id.assert_ok_debug(), episode.history);
+        assert_eq!(by_title.assert_ok_debug().len(), 1);
+        assert!(matches!(ambiguous, Err(HistoryError::Ambiguous(_, 2))));
+    }
+}
@@ -0,0 +1,5 @@
+pub use history::*;
+pub use options::*;
+
+mod history;
+mod options;
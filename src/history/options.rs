@@ -0,0 +1,10 @@
+use crate::prelude::*;
+
+#[derive(Debug, Args)]
+pub struct HistoryOptions {
+    /// ID of the podcast
+    #[arg(value_parser = Podcast::validate_id)]
+    pub podcast_id: String,
+    /// GUID, file name or part of the title of the episode
+    pub episode: String,
+}
@@ -1,8 +1,17 @@
+mod cache;
 mod cover;
+mod db;
 mod download;
 mod emulate;
+mod history;
+mod list;
 pub mod prelude;
+mod remove;
+mod rename;
+mod rescrape;
 mod schema;
 mod scrape;
 mod services;
+mod show;
+mod snapshots;
 mod utils;
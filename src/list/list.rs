@@ -0,0 +1,156 @@
+use crate::prelude::*;
+use strum_macros::IntoStaticStr;
+
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+pub struct ListCommand {
+    podcasts: PodcastProvider,
+    paths: PathProvider,
+}
+
+impl ListCommand {
+    #[must_use]
+    pub fn new(podcasts: PodcastProvider, paths: PathProvider) -> Self {
+        Self { podcasts, paths }
+    }
+
+    /// Log a line for each podcast in the library and return their summaries.
+    ///
+    /// Episode counts only include the episodes matching the filter.
+    pub fn execute(&self, options: &ListOptions) -> Result<Vec<PodcastSummary>, ListError> {
+        let mut podcasts = self.podcasts.get_all().map_err(ListError::GetPodcasts)?;
+        for podcast in &mut podcasts {
+            podcast.episodes = options.filter.apply(take(&mut podcast.episodes));
+        }
+        if options.filter.dry_run {
+            for podcast in &podcasts {
+                EpisodeFilter::log_matches(&podcast.id, &podcast.episodes);
+            }
+        }
+        let summaries: Vec<PodcastSummary> = podcasts
+            .iter()
+            .map(|podcast| self.get_summary(podcast))
+            .collect();
+        for summary in &summaries {
+            info!("{summary}");
+        }
+        info!("{} {} podcasts", "Found".bold(), summaries.len());
+        Ok(summaries)
+    }
+
+    fn get_summary(&self, podcast: &Podcast) -> PodcastSummary {
+        let downloaded = podcast
+            .episodes
+            .iter()
+            .filter(|episode| {
+                self.paths
+                    .get_output_path_for_audio(&podcast.id, episode)
+                    .exists()
+            })
+            .count();
+        PodcastSummary {
+            id: podcast.id.clone(),
+            title: podcast.title.clone(),
+            episodes: podcast.episodes.len(),
+            downloaded,
+            scraped_at: podcast.source.as_ref().map(|source| source.scraped_at),
+            size: get_dir_size(&self.paths.get_output_dir().join(&podcast.id)),
+        }
+    }
+}
+
+/// Overview of a podcast in the library
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PodcastSummary {
+    /// ID of the podcast
+    pub id: String,
+    /// Title of the podcast
+    pub title: String,
+    /// Number of scraped episodes
+    pub episodes: usize,
+    /// Number of episodes with audio in the output directory
+    pub downloaded: usize,
+    /// Date and time of the last scrape
+    pub scraped_at: Option<NaiveDateTime>,
+    /// Bytes used by the output directory of the podcast
+    pub size: u64,
+}
+
+impl Display for PodcastSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let scraped_at = self.scraped_at.map_or_else(
+            || "never".to_owned(),
+            |date| date.format(DATE_FORMAT).to_string(),
+        );
+        write!(
+            f,
+            "{} {}\n{}/{} downloaded, scraped {scraped_at}, {}",
+            self.id.bold(),
+            self.title,
+            self.downloaded,
+            self.episodes,
+            format_bytes(self.size)
+        )
+    }
+}
+
+#[derive(Debug, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum ListError {
+    GetPodcasts(DatabaseError),
+}
+
+impl Display for ListError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let reason = match self {
+            ListError::GetPodcasts(e) => format!("Unable to get podcasts\n{e}"),
+        };
+        write!(f, "{} to list podcasts\n{reason}", "Failed".bold())
+    }
+}
+
+impl ErrorCode for ListError {
+    fn get_code(&self) -> String {
+        format!("list.{}", <&str>::from(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, write};
+
+    #[test]
+    fn list_command() {
+        // Arrange
+        let _ = init_logging();
+        let options = create_temp_options("pura-list");
+        let paths = PathProvider::new(options);
+        let podcasts = PodcastProvider::new(paths.get_podcast_dir());
+        let mut podcast = Podcast::example();
+        let episode = Episode::example();
+        let mut other = Episode::example();
+        other.id = "other".to_owned();
+        other.title = "Other".to_owned();
+        podcast.episodes = vec![episode.clone(), other];
+        podcasts.put(&podcast).assert_ok_debug();
+        let path = paths.get_output_path_for_audio(&podcast.id, &episode);
+        create_dir_all(path.parent().expect("path should have a parent")).assert_ok_debug();
+        write(&path, [0; 10]).assert_ok_debug();
+        let command = ListCommand::new(podcasts, paths);
+
+        // Act
+        let result = command.execute(&ListOptions {
+            filter: EpisodeFilter::default(),
+        });
+
+        // Assert
+        let summaries = result.assert_ok_debug();
+        assert_eq!(summaries.len(), 1);
+        let summary = summaries.first().expect("should have a summary");
+        assert_eq!(summary.id, podcast.id);
+        assert_eq!(summary.episodes, 2);
+        assert_eq!(summary.downloaded, 1);
+        assert_eq!(summary.size, 10);
+    }
+}
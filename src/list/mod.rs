@@ -0,0 +1,5 @@
+pub use list::*;
+pub use options::*;
+
+mod list;
+mod options;
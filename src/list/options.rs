@@ -0,0 +1,8 @@
+use crate::prelude::*;
+
+#[derive(Debug, Args)]
+pub struct ListOptions {
+    /// Which episodes to count
+    #[command(flatten)]
+    pub filter: EpisodeFilter,
+}
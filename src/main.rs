@@ -1,3 +1,4 @@
+use colored::control::set_override;
 use pura::prelude::*;
 use std::process::exit;
 
@@ -5,41 +6,101 @@ use std::process::exit;
 async fn main() {
     let _ = init_logging();
     let cli = Cli::parse();
-    let services = match ServiceProvider::create().await {
-        Ok(services) => services,
-        Err(e) => {
-            error!("{e}");
-            exit(1);
+    let format = cli.global.output;
+    if format == OutputFormat::Json {
+        set_override(false);
+    }
+    let output = match ServiceProvider::create_with(&cli.global).await {
+        Ok(services) => {
+            if cli.command.is_cancellable() {
+                services.listen_for_signals();
+            }
+            let cancellation = services.cancellation.clone();
+            execute(cli.command, services)
+                .await
+                .with_cancelled(cancellation.is_cancelled())
         }
+        Err(e) => CommandOutput::new::<(), _>(Err(e)),
     };
-    match cli.command {
+    output.print(format);
+    exit(output.get_exit_code());
+}
+
+async fn execute(command: Command, services: ServiceProvider) -> CommandOutput {
+    match command {
+        Command::List(options) => {
+            let command = ListCommand::new(services.podcasts, services.paths);
+            CommandOutput::new(command.execute(&options))
+        }
+        Command::Show(options) => {
+            let command = ShowCommand::new(services.podcasts, services.paths);
+            CommandOutput::new(command.execute(&options))
+        }
+        Command::Remove(options) => {
+            let command = RemoveCommand::new(services.paths, services.http, services.podcasts);
+            CommandOutput::new(command.execute(&options).await)
+        }
+        Command::Rename(options) => {
+            let command = RenameCommand::new(services.paths, services.podcasts);
+            CommandOutput::new(command.execute(&options).await)
+        }
         Command::Scrape(options) => {
-            let command = ScrapeCommand::new(services.http, services.podcasts);
-            if let Err(e) = command.execute(options).await {
-                error!("{e}");
-                exit(1);
-            }
+            let command = ScrapeCommand::new(services.http, services.podcasts)
+                .with_tracking_prefixes(TrackingPrefixes::from_options(&services.options));
+            let result = command.execute(options).await;
+            let errors = result
+                .as_ref()
+                .map(|report| report.errors.clone())
+                .unwrap_or_default();
+            CommandOutput::new(result).with_errors(errors)
+        }
+        Command::Rescrape(options) => {
+            let command = RescrapeCommand::new(services.http, services.podcasts)
+                .with_tracking_prefixes(TrackingPrefixes::from_options(&services.options));
+            let result = command.execute(options).await;
+            let errors = result
+                .as_ref()
+                .map(|report| report.failed.clone())
+                .unwrap_or_default();
+            CommandOutput::new(result).with_errors(errors)
+        }
+        Command::History(options) => {
+            let command = HistoryCommand::new(services.podcasts);
+            CommandOutput::new(command.execute(&options))
         }
         Command::Download(options) => {
             let command = DownloadCommand::new(services.paths, services.http, services.podcasts);
-            if let Err(e) = command.execute(options).await {
-                error!("{e}");
-                exit(1);
-            }
+            let result = command.execute(options).await;
+            let errors = result
+                .as_ref()
+                .map(DownloadReport::get_errors)
+                .unwrap_or_default();
+            let failed = result.as_ref().is_ok_and(DownloadReport::is_failed);
+            CommandOutput::new(result)
+                .with_errors(errors)
+                .with_failed(failed)
         }
         Command::Emulate(options) => {
             let command = EmulateCommand::new(services.podcasts, services.paths);
-            if let Err(e) = command.execute(options).await {
-                error!("{e}");
-                exit(1);
-            }
+            CommandOutput::new(command.execute(options).await)
         }
         Command::Cover(options) => {
             let command = CoverCommand::new(services.podcasts, services.http, services.paths);
-            if let Err(e) = command.execute(options).await {
-                error!("{e}");
-                exit(1);
-            }
+            CommandOutput::new(command.execute(options).await)
+        }
+        Command::Snapshots(options) => {
+            let command = SnapshotsCommand::new(services.podcasts)
+                .with_tracking_prefixes(TrackingPrefixes::from_options(&services.options));
+            CommandOutput::new(command.execute(options).await)
+        }
+        Command::Db(options) => {
+            let database = services.options.database.unwrap_or_default();
+            let command = DbCommand::new(services.paths, services.podcasts, database);
+            CommandOutput::new(command.execute(options))
+        }
+        Command::Cache(options) => {
+            let command = CacheCommand::new(services.paths, services.http, services.podcasts);
+            CommandOutput::new(command.execute(options).await)
         }
     }
 }
@@ -48,16 +109,49 @@ async fn main() {
 struct Cli {
     #[command(subcommand)]
     command: Command,
+    #[command(flatten)]
+    global: GlobalOptions,
 }
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Subcommand)]
 enum Command {
+    /// List the podcasts in the library.
+    List(ListOptions),
+    /// Show the metadata and episodes of a podcast.
+    Show(ShowOptions),
+    /// Remove a podcast and optionally its output and cache.
+    Remove(RemoveOptions),
+    /// Change the ID of a podcast, moving its output and regenerating feeds.
+    Rename(RenameOptions),
     /// Scrape a podcast from an RSS feed or website.
     Scrape(ScrapeOptions),
+    /// Scrape a podcast again from its saved source.
+    Rescrape(RescrapeOptions),
+    /// Show the changes seen to an episode by each scrape.
+    History(HistoryOptions),
     /// Download episodes of a scraped podcast.
     Download(DownloadOptions),
     /// Create emulated RSS of a scraped podcast.
     Emulate(EmulateOptions),
     /// Download cover and banner images of a scraped podcast.
     Cover(CoverOptions),
+    /// List, diff or rebuild from the raw source documents saved by each scrape.
+    Snapshots(SnapshotsOptions),
+    /// Import or export podcasts between the `yaml` and `sqlite` databases.
+    Db(DbOptions),
+    /// Report HTTP cache usage and remove stale or duplicated entries.
+    Cache(CacheOptions),
+}
+
+impl Command {
+    /// Does the command stop gracefully when cancelled?
+    ///
+    /// Other commands keep the default signal handling so Ctrl-C exits immediately.
+    fn is_cancellable(&self) -> bool {
+        matches!(
+            self,
+            Command::Scrape(_) | Command::Rescrape(_) | Command::Download(_)
+        )
+    }
 }
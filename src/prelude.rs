@@ -1,9 +1,18 @@
+pub use crate::cache::*;
 pub use crate::cover::*;
+pub use crate::db::*;
 pub use crate::download::*;
 pub use crate::emulate::*;
+pub use crate::history::*;
+pub use crate::list::*;
+pub use crate::remove::*;
+pub use crate::rename::*;
+pub use crate::rescrape::*;
 pub(crate) use crate::schema::*;
 pub use crate::scrape::*;
 pub use crate::services::*;
+pub use crate::show::*;
+pub use crate::snapshots::*;
 pub use crate::utils::*;
 pub(crate) use chrono::{DateTime, Datelike, FixedOffset, NaiveDateTime};
 pub(crate) use colored::Colorize;
@@ -28,4 +37,4 @@ pub(crate) use tokio::fs::{copy, create_dir_all, File as AsyncFile};
 pub(crate) use tokio::io::AsyncWriteExt;
 pub use url::Url;
 
-pub use clap::{Args, Parser, Subcommand};
+pub use clap::{Args, Parser, Subcommand, ValueEnum};
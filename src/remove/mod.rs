@@ -0,0 +1,5 @@
+pub use options::*;
+pub use remove::*;
+
+mod options;
+mod remove;
@@ -0,0 +1,14 @@
+use crate::prelude::*;
+
+#[derive(Debug, Args)]
+pub struct RemoveOptions {
+    /// ID of the podcast
+    #[arg(value_parser = Podcast::validate_id)]
+    pub podcast_id: String,
+    /// Also delete the output directory of the podcast
+    #[arg(long)]
+    pub output: bool,
+    /// Also delete the cached audio, images and source documents of the podcast
+    #[arg(long)]
+    pub cache: bool,
+}
@@ -0,0 +1,165 @@
+use crate::prelude::*;
+use strum_macros::IntoStaticStr;
+use tokio::fs::{remove_dir_all, remove_file};
+
+pub struct RemoveCommand {
+    paths: PathProvider,
+    http: HttpClient,
+    podcasts: PodcastProvider,
+}
+
+impl RemoveCommand {
+    #[must_use]
+    pub fn new(paths: PathProvider, http: HttpClient, podcasts: PodcastProvider) -> Self {
+        Self {
+            paths,
+            http,
+            podcasts,
+        }
+    }
+
+    pub async fn execute(&self, options: &RemoveOptions) -> Result<(), RemoveError> {
+        let id = &options.podcast_id;
+        let _lock = self.podcasts.lock(id).await.map_err(RemoveError::Lock)?;
+        let podcast = self.podcasts.get(id).map_err(RemoveError::GetPodcast)?;
+        if options.cache {
+            let count = self.remove_cache(&podcast).await?;
+            info!("{} {count} cached files", "Removed".bold());
+        }
+        if options.output {
+            let dir = self.paths.get_output_dir().join(id);
+            if dir.exists() {
+                remove_dir_all(&dir)
+                    .await
+                    .map_err(|e| RemoveError::Io(dir.clone(), e))?;
+                info!("{} {}", "Removed".bold(), dir.display());
+            }
+        }
+        let reports = self.paths.get_reports_dir().join(id);
+        if reports.exists() {
+            remove_dir_all(&reports)
+                .await
+                .map_err(|e| RemoveError::Io(reports.clone(), e))?;
+        }
+        self.podcasts.remove(id).map_err(RemoveError::Remove)?;
+        info!("{} podcast {id}", "Removed".bold());
+        Ok(())
+    }
+
+    /// Remove the cached audio and images of every episode and the source documents
+    /// of the latest snapshot.
+    async fn remove_cache(&self, podcast: &Podcast) -> Result<usize, RemoveError> {
+        let mut count = 0;
+        if let Some(url) = &podcast.image_url {
+            count += usize::from(self.http.remove(url, None).await);
+        }
+        for episode in &podcast.episodes {
+            count += usize::from(
+                self.http
+                    .remove(&episode.audio_url, Some(MP3_EXTENSION))
+                    .await,
+            );
+            if let Some(url) = &episode.image_url {
+                let extension = url.get_extension();
+                count += usize::from(self.http.remove(url, extension.as_deref()).await);
+            }
+        }
+        let names = self
+            .podcasts
+            .get_snapshot_names(&podcast.id)
+            .map_err(RemoveError::GetPodcast)?;
+        if let Some(name) = names.last() {
+            let snapshot = self
+                .podcasts
+                .get_snapshot(&podcast.id, name)
+                .map_err(RemoveError::GetPodcast)?;
+            let dir = self.paths.get_http_dir();
+            for key in snapshot.documents.keys() {
+                let Some(path) = get_document_path(&dir, key) else {
+                    continue;
+                };
+                if path.exists() {
+                    remove_file(&path)
+                        .await
+                        .map_err(|e| RemoveError::Io(path.clone(), e))?;
+                    count += 1;
+                }
+            }
+        }
+        Ok(count)
+    }
+}
+
+#[allow(clippy::absolute_paths)]
+#[derive(Debug, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum RemoveError {
+    GetPodcast(DatabaseError),
+    Io(PathBuf, std::io::Error),
+    Remove(DatabaseError),
+    Lock(LockError),
+}
+
+impl Display for RemoveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let reason = match self {
+            RemoveError::GetPodcast(e) => format!("Unable to get podcast\n{e}"),
+            RemoveError::Io(path, e) => {
+                format!("An I/O error occurred.\nPath: {}\n{e}", path.display())
+            }
+            RemoveError::Remove(e) => format!("Unable to remove from the database\n{e}"),
+            RemoveError::Lock(e) => format!("Unable to lock podcast\n{e}"),
+        };
+        write!(f, "{} to remove podcast\n{reason}", "Failed".bold())
+    }
+}
+
+impl ErrorCode for RemoveError {
+    fn get_code(&self) -> String {
+        format!("remove.{}", <&str>::from(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    pub async fn remove_command() {
+        // Arrange
+        let _ = init_logging();
+        let services = create_fixture_services("pura-remove").await;
+        let podcast = scrape_fixture(&services).await;
+        let output = services.paths.get_output_dir().join(&podcast.id);
+        create_dir_all(&output).await.assert_ok_debug();
+        let feed = services.http.get_cache_path(
+            &Url::parse(FIXTURE_FEED_URL).expect("URL should parse"),
+            Some(RSS_EXTENSION),
+        );
+        assert!(feed.exists());
+        let command = RemoveCommand::new(
+            services.paths.clone(),
+            services.http.clone(),
+            services.podcasts.clone(),
+        );
+        let options = RemoveOptions {
+            podcast_id: podcast.id.clone(),
+            output: true,
+            cache: true,
+        };
+
+        // Act
+        let result = command.execute(&options).await;
+
+        // Assert
+        result.assert_ok_debug();
+        assert!(services.podcasts.get(&podcast.id).is_err());
+        assert!(services
+            .podcasts
+            .get_snapshot_names(&podcast.id)
+            .assert_ok_debug()
+            .is_empty());
+        assert!(!output.exists());
+        assert!(!feed.exists());
+    }
+}
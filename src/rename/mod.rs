@@ -0,0 +1,5 @@
+pub use options::*;
+pub use rename::*;
+
+mod options;
+mod rename;
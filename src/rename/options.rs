@@ -0,0 +1,13 @@
+use crate::prelude::*;
+
+#[derive(Debug, Args)]
+pub struct RenameOptions {
+    /// Current ID of the podcast
+    #[arg(value_parser = Podcast::validate_id)]
+    pub podcast_id: String,
+    /// New ID of the podcast
+    ///
+    /// Must be alphanumeric and hyphenated
+    #[arg(value_parser = Podcast::validate_id)]
+    pub new_id: String,
+}
@@ -0,0 +1,223 @@
+use crate::prelude::*;
+use strum_macros::IntoStaticStr;
+use tokio::fs::rename;
+
+pub struct RenameCommand {
+    paths: PathProvider,
+    podcasts: PodcastProvider,
+}
+
+impl RenameCommand {
+    #[must_use]
+    pub fn new(paths: PathProvider, podcasts: PodcastProvider) -> Self {
+        Self { paths, podcasts }
+    }
+
+    /// Move a podcast, its output directory and run reports to a new ID then
+    /// regenerate its feeds.
+    ///
+    /// If any move fails the moves already made are undone.
+    pub async fn execute(&self, options: &RenameOptions) -> Result<(), RenameError> {
+        let old = &options.podcast_id;
+        let new = &options.new_id;
+        let _old_lock = self.podcasts.lock(old).await.map_err(RenameError::Lock)?;
+        let _new_lock = self.podcasts.lock(new).await.map_err(RenameError::Lock)?;
+        if self.podcasts.get(new).is_ok() || self.paths.get_output_dir().join(new).exists() {
+            return Err(RenameError::Exists(new.clone()));
+        }
+        let mut moved = Vec::new();
+        let result = self.rename(old, new, &mut moved).await;
+        if result.is_err() {
+            undo_moves(&moved).await;
+        }
+        let podcast = result?;
+        let feeds = EmulateCommand::new(self.podcasts.clone(), self.paths.clone())
+            .save_feeds(&podcast)
+            .await
+            .map_err(RenameError::Emulate)?;
+        info!(
+            "{} {old} to {new} and created {} rss feeds",
+            "Renamed".bold(),
+            feeds.len()
+        );
+        Ok(())
+    }
+
+    /// Move the output directory and run reports then the podcast in the database.
+    async fn rename(
+        &self,
+        old: &str,
+        new: &str,
+        moved: &mut Vec<(PathBuf, PathBuf)>,
+    ) -> Result<Podcast, RenameError> {
+        let old_dir = self.paths.get_output_dir().join(old);
+        let new_dir = self.paths.get_output_dir().join(new);
+        let reports_dir = self.paths.get_reports_dir();
+        for (from, to) in [
+            (old_dir.clone(), new_dir.clone()),
+            (reports_dir.join(old), reports_dir.join(new)),
+        ] {
+            if from.exists() {
+                rename(&from, &to)
+                    .await
+                    .map_err(|e| RenameError::Io(from.clone(), e))?;
+                moved.push((from, to));
+            }
+        }
+        self.podcasts
+            .rename(old, new, |record| {
+                if let Ok(relative) = record.path.strip_prefix(&old_dir) {
+                    record.path = new_dir.join(relative);
+                }
+            })
+            .map_err(RenameError::Rename)
+    }
+}
+
+/// Move directories back to where they were, logging any failure.
+async fn undo_moves(moved: &[(PathBuf, PathBuf)]) {
+    for (from, to) in moved.iter().rev() {
+        if let Err(e) = rename(to, from).await {
+            warn!("{} to move back {}\n{e}", "Failed".bold(), to.display());
+        }
+    }
+}
+
+#[allow(clippy::absolute_paths)]
+#[derive(Debug, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum RenameError {
+    Exists(String),
+    Rename(DatabaseError),
+    Io(PathBuf, std::io::Error),
+    Emulate(EmulateError),
+    Lock(LockError),
+}
+
+impl Display for RenameError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let reason = match self {
+            RenameError::Exists(id) => format!("Podcast already exists: {id}"),
+            RenameError::Rename(e) => format!("Unable to rename in the database\n{e}"),
+            RenameError::Io(path, e) => {
+                format!("An I/O error occurred.\nPath: {}\n{e}", path.display())
+            }
+            RenameError::Emulate(e) => format!("Unable to regenerate feeds\n{e}"),
+            RenameError::Lock(e) => format!("Unable to lock podcast\n{e}"),
+        };
+        write!(f, "{} to rename podcast\n{reason}", "Failed".bold())
+    }
+}
+
+impl ErrorCode for RenameError {
+    fn get_code(&self) -> String {
+        format!("rename.{}", <&str>::from(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use std::collections::BTreeMap;
+    use std::fs::{create_dir_all, write};
+
+    #[tokio::test]
+    pub async fn rename_command() {
+        // Arrange
+        let _ = init_logging();
+        let paths = PathProvider::new(create_temp_options("pura-rename"));
+        let podcasts = PodcastProvider::new(paths.get_podcast_dir());
+        let mut podcast = Podcast::example();
+        let episode = Episode::example();
+        podcast.episodes = vec![episode.clone()];
+        podcasts.put(&podcast).assert_ok_debug();
+        let path = paths.get_output_path_for_audio(&podcast.id, &episode);
+        create_dir_all(path.parent().expect("path should have a parent")).assert_ok_debug();
+        write(&path, []).assert_ok_debug();
+        podcasts
+            .put_downloads(
+                &podcast.id,
+                &[DownloadRecord {
+                    episode_id: episode.id.clone(),
+                    path,
+                    audio_url: episode.audio_url.clone(),
+                    audio_file_size: episode.audio_file_size,
+                    downloaded_at: Utc::now().naive_utc(),
+                }],
+            )
+            .assert_ok_debug();
+        let report = paths
+            .get_reports_dir()
+            .join(&podcast.id)
+            .join("download.json");
+        create_dir_all(report.parent().expect("path should have a parent")).assert_ok_debug();
+        write(&report, "{}").assert_ok_debug();
+        let command = RenameCommand::new(paths.clone(), podcasts.clone());
+        let options = RenameOptions {
+            podcast_id: podcast.id.clone(),
+            new_id: "renamed".to_owned(),
+        };
+
+        // Act
+        let result = command.execute(&options).await;
+        let repeated = command.execute(&options).await;
+
+        // Assert
+        result.assert_ok_debug();
+        assert!(matches!(repeated, Err(RenameError::Exists(_))));
+        assert!(podcasts.get(&podcast.id).is_err());
+        assert_eq!(podcasts.get("renamed").assert_ok_debug().id, "renamed");
+        let path = paths.get_output_path_for_audio("renamed", &episode);
+        assert!(path.exists());
+        assert!(paths
+            .get_output_path_for_rss("renamed", None, None)
+            .exists());
+        let downloads = podcasts.get_downloads("renamed").assert_ok_debug();
+        assert_eq!(downloads.first().map(|record| &record.path), Some(&path));
+        assert!(paths
+            .get_reports_dir()
+            .join("renamed")
+            .join("download.json")
+            .exists());
+    }
+
+    #[tokio::test]
+    pub async fn rename_command_undo() {
+        // Arrange
+        let _ = init_logging();
+        let paths = PathProvider::new(create_temp_options("pura-rename-undo"));
+        let podcasts = PodcastProvider::new(paths.get_podcast_dir());
+        let mut podcast = Podcast::example();
+        podcast.episodes = vec![Episode::example()];
+        podcasts.put(&podcast).assert_ok_debug();
+        let snapshot = Snapshot::new(None, BTreeMap::new());
+        podcasts
+            .put_snapshot(&podcast.id, &snapshot)
+            .assert_ok_debug();
+        let output = paths.get_output_dir().join(&podcast.id);
+        create_dir_all(&output).assert_ok_debug();
+        let blocked = podcasts.get_snapshot_dir("renamed");
+        create_dir_all(&blocked).assert_ok_debug();
+        write(blocked.join("blocker"), []).assert_ok_debug();
+        let command = RenameCommand::new(paths.clone(), podcasts.clone());
+        let options = RenameOptions {
+            podcast_id: podcast.id.clone(),
+            new_id: "renamed".to_owned(),
+        };
+
+        // Act
+        let result = command.execute(&options).await;
+
+        // Assert
+        assert!(matches!(result, Err(RenameError::Rename(_))));
+        assert_eq!(podcasts.get(&podcast.id).assert_ok_debug(), podcast);
+        assert!(podcasts.get("renamed").is_err());
+        assert!(output.exists());
+        assert!(!paths.get_output_dir().join("renamed").exists());
+        assert_eq!(
+            podcasts.get_snapshot_names(&podcast.id).assert_ok_debug(),
+            vec![snapshot.get_name()]
+        );
+    }
+}
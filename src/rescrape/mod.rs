@@ -0,0 +1,5 @@
+pub use options::*;
+pub use rescrape::*;
+
+mod options;
+mod rescrape;
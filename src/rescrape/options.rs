@@ -0,0 +1,14 @@
+use crate::prelude::*;
+
+#[derive(Debug, Args)]
+pub struct RescrapeOptions {
+    /// ID of the podcast to refresh from its saved source
+    #[arg(value_parser = Podcast::validate_id, required_unless_present = "all")]
+    pub podcast_id: Option<String>,
+    /// Refresh every scraped podcast
+    #[arg(long, conflicts_with = "podcast_id")]
+    pub all: bool,
+    /// Maximum number of concurrent requests for the episodes of a website
+    #[arg(long, default_value_t = SCRAPE_CONCURRENCY)]
+    pub concurrency: usize,
+}
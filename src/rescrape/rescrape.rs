@@ -0,0 +1,279 @@
+use crate::prelude::*;
+use strum_macros::IntoStaticStr;
+
+pub struct RescrapeCommand {
+    http: HttpClient,
+    podcasts: PodcastProvider,
+    scrape: ScrapeCommand,
+}
+
+impl RescrapeCommand {
+    #[must_use]
+    pub fn new(http: HttpClient, podcasts: PodcastProvider) -> Self {
+        Self {
+            scrape: ScrapeCommand::new(http.clone(), podcasts.clone()),
+            http,
+            podcasts,
+        }
+    }
+
+    /// Set the tracking prefixes removed from enclosure URLs.
+    #[must_use]
+    pub fn with_tracking_prefixes(mut self, tracking: TrackingPrefixes) -> Self {
+        self.scrape = self.scrape.with_tracking_prefixes(tracking);
+        self
+    }
+
+    /// Rescrape one or every podcast.
+    ///
+    /// With `--all` the podcasts that fail are reported rather than failing the
+    /// command unless every podcast fails.
+    pub async fn execute(&self, options: RescrapeOptions) -> Result<RescrapeReport, RescrapeError> {
+        if !options.all {
+            let id = options.podcast_id.unwrap_or_default();
+            let report = self.rescrape(&id, options.concurrency).await?;
+            return Ok(RescrapeReport {
+                failed: report.errors.clone(),
+                podcasts: vec![report],
+            });
+        }
+        let ids = self
+            .podcasts
+            .get_ids()
+            .map_err(RescrapeError::GetPodcasts)?;
+        let mut report = RescrapeReport::default();
+        for id in &ids {
+            if self.http.is_cancelled() {
+                warn!("{} rescrape as the run was cancelled", "Stopping".bold());
+                break;
+            }
+            match self.rescrape(id, options.concurrency).await {
+                Ok(scraped) => {
+                    report.failed.extend(scraped.errors.iter().cloned());
+                    report.podcasts.push(scraped);
+                }
+                Err(e) => {
+                    warn!("{e}");
+                    report.failed.push(ErrorOutput::new(&e).with_podcast(id));
+                }
+            }
+        }
+        info!("{} {} podcasts", "Rescraped".bold(), report.podcasts.len());
+        if !ids.is_empty() && report.podcasts.is_empty() {
+            return Err(RescrapeError::Failures(report.failed.len(), ids.len()));
+        }
+        Ok(report)
+    }
+
+    async fn rescrape(&self, id: &str, concurrency: usize) -> Result<ScrapeReport, RescrapeError> {
+        let podcast = self.podcasts.get(id).map_err(RescrapeError::GetPodcasts)?;
+        let source = podcast
+            .source
+            .ok_or_else(|| RescrapeError::NoSource(id.to_owned()))?;
+        info!(
+            "{} {id} from {} source: {}",
+            "Rescraping".bold(),
+            source.kind.as_ref(),
+            source.url
+        );
+        self.remove_cached(&source).await;
+        let options = ScrapeOptions {
+            podcast_id: id.to_owned(),
+            url: source.url,
+            proxy: None,
+            username: None,
+            password: None,
+            auth_hosts: Vec::new(),
+            token: None,
+            concurrency,
+        };
+        self.scrape
+            .execute(options)
+            .await
+            .map_err(|e| RescrapeError::Scrape(id.to_owned(), e))
+    }
+
+    /// Remove the cached source responses so the latest episodes are fetched.
+    async fn remove_cached(&self, source: &PodcastSource) {
+        self.http.remove(&source.url, Some(HEAD_EXTENSION)).await;
+        match source.kind {
+            SourceKind::Rss => {
+                self.http
+                    .remove(&source.feed_url, Some(RSS_EXTENSION))
+                    .await;
+            }
+            SourceKind::Simplecast => {
+                self.scrape.remove_cached_simplecast(source).await;
+            }
+        }
+    }
+}
+
+/// Result of rescraping one or more podcasts
+#[derive(Debug, Default, Serialize)]
+pub struct RescrapeReport {
+    /// Podcasts that were rescraped
+    pub podcasts: Vec<ScrapeReport>,
+    /// Errors of the podcasts that failed and of the episodes that could not be fetched
+    ///
+    /// Printed with the errors of the command output rather than the result.
+    #[serde(skip)]
+    pub failed: Vec<ErrorOutput>,
+}
+
+#[derive(Debug, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum RescrapeError {
+    GetPodcasts(DatabaseError),
+    NoSource(String),
+    Scrape(String, ScrapeError),
+    Failures(usize, usize),
+}
+
+impl Display for RescrapeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let reason = match self {
+            RescrapeError::GetPodcasts(e) => format!("Unable to get podcast\n{e}"),
+            RescrapeError::NoSource(id) => format!(
+                "Podcast was scraped before sources were recorded: {id}\nScrape it again with its URL"
+            ),
+            RescrapeError::Scrape(id, e) => format!("Podcast: {id}\n{e}"),
+            RescrapeError::Failures(failures, total) => {
+                format!("{failures} of {total} podcasts failed")
+            }
+        };
+        write!(f, "{} to rescrape\n{reason}", "Failed".bold())
+    }
+}
+
+impl ErrorCode for RescrapeError {
+    fn get_code(&self) -> String {
+        format!("rescrape.{}", <&str>::from(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs::{create_dir_all, write};
+
+    #[tokio::test]
+    pub async fn rescrape_command_replay() {
+        // Arrange
+        let _ = init_logging();
+        let services = create_fixture_services("pura-rescrape").await;
+        let scraped = scrape_fixture(&services).await;
+        let mut legacy = Podcast::example();
+        legacy.id = "legacy".to_owned();
+        services.podcasts.put(&legacy).assert_ok();
+        let command = RescrapeCommand::new(services.http, services.podcasts);
+
+        // Act
+        let single = command
+            .execute(RescrapeOptions {
+                podcast_id: Some(FIXTURE_PODCAST_ID.to_owned()),
+                all: false,
+                concurrency: SCRAPE_CONCURRENCY,
+            })
+            .await;
+        let all = command
+            .execute(RescrapeOptions {
+                podcast_id: None,
+                all: true,
+                concurrency: SCRAPE_CONCURRENCY,
+            })
+            .await;
+
+        // Assert
+        let report = single.assert_ok();
+        let podcast = &report
+            .podcasts
+            .first()
+            .expect("should rescrape podcast")
+            .podcast;
+        let source = podcast.source.as_ref().expect("should have source");
+        let scraped_source = scraped.source.as_ref().expect("should have source");
+        assert_eq!(source.kind, SourceKind::Rss);
+        assert_eq!(source.url, scraped_source.url);
+        assert!(source.scraped_at >= scraped_source.scraped_at);
+        assert_eq!(podcast.episodes, scraped.episodes);
+        let all = all.assert_ok();
+        assert_eq!(all.podcasts.len(), 1);
+        let failed = all.failed.first().expect("legacy podcast should fail");
+        assert_eq!(failed.code, "rescrape.no_source");
+        assert_eq!(failed.podcast.as_deref(), Some("legacy"));
+    }
+
+    #[tokio::test]
+    pub async fn remove_cached_simplecast_pages() {
+        // Arrange
+        let _ = init_logging();
+        let http = HttpClient::new(TempDirectory::create("pura-rescrape-simplecast"));
+        let podcasts = PodcastProvider::new(TempDirectory::create("pura-rescrape-podcasts"));
+        let parse = |url: &str| Url::parse(url).expect("URL should be valid");
+        let feed_url = parse("https://api.simplecast.com/podcasts/p1");
+        let source = PodcastSource::new(
+            parse("https://example.com/"),
+            feed_url.clone(),
+            SourceKind::Simplecast,
+        )
+        .with_id("episode", "e0");
+        let first = parse("https://api.simplecast.com/podcasts/p1/playlist");
+        let second = parse("https://api.simplecast.com/podcasts/p1/playlist?page=2");
+        let page = |episode: &str, next: Option<&Url>| {
+            json!({
+                "href": "https://api.simplecast.com/podcasts/p1/playlist",
+                "type": "playlist",
+                "title": "Example",
+                "image_url": "https://example.com/image.jpg",
+                "feed_url": "https://example.com/feed",
+                "episodes": {
+                    "pages": {
+                        "total": 2,
+                        "previous": null,
+                        "next": next.map(|url| json!({ "href": url })),
+                        "limit": 1,
+                        "current": 1,
+                    },
+                    "collection": [{
+                        "type": "full",
+                        "title": episode,
+                        "season_number": null,
+                        "number": null,
+                        "image_url": null,
+                        "id": episode,
+                        "enclosure_url": "https://example.com/audio.mp3",
+                        "duration": 60,
+                    }],
+                },
+            })
+        };
+        let episode = |id: &str| parse(&format!("https://api.simplecast.com/episodes/{id}"));
+        let cached = [
+            (first.clone(), page("e1", Some(&second))),
+            (second.clone(), page("e2", None)),
+            (feed_url.clone(), json!({})),
+            (episode("e0"), json!({})),
+            (episode("e1"), json!({})),
+            (episode("e2"), json!({})),
+        ];
+        for (url, json) in &cached {
+            let path = http.get_cache_path(url, Some(JSON_EXTENSION));
+            create_dir_all(path.parent().expect("should have parent")).expect("should create");
+            write(path, json.to_string()).expect("should write");
+        }
+        let command = RescrapeCommand::new(http.clone(), podcasts);
+
+        // Act
+        command.remove_cached(&source).await;
+
+        // Assert
+        for (url, _) in &cached {
+            assert!(
+                !http.get_cache_path(url, Some(JSON_EXTENSION)).exists(),
+                "{url}"
+            );
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use crate::prelude::*;
+use std::fmt::Debug;
+
+/// Placeholder written in place of the token in stored URLs.
+const TOKEN_PLACEHOLDER: &str = "{token}";
+const TOKEN_PLACEHOLDER_ENCODED: &str = "%7Btoken%7D";
+
+/// Secrets for a private feed
+///
+/// Stored separately from the podcast so they are never written to the podcast YAML,
+/// cache paths or logs.
+#[derive(Clone, Default, Deserialize, PartialEq, Serialize)]
+pub struct Credentials {
+    /// Host that HTTP Basic auth is sent to
+    pub host: Option<String>,
+    /// HTTP Basic auth username
+    pub username: Option<String>,
+    /// HTTP Basic auth password
+    pub password: Option<String>,
+    /// Other hosts that are sent HTTP Basic auth if they ask for it
+    ///
+    /// For example the CDN serving the enclosures of a private feed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub auth_hosts: Vec<String>,
+    /// Secret token in feed and enclosure URLs
+    ///
+    /// For example the `auth` query value of a Patreon feed or the path segment of a
+    /// Supercast feed. Stored URLs contain `{token}` in its place.
+    pub token: Option<String>,
+    /// Password of the podcast proxy
+    ///
+    /// Stored URLs contain only the proxy username.
+    pub proxy_password: Option<String>,
+}
+
+impl Credentials {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.username.is_none() && self.password.is_none() && self.token.is_none()
+    }
+
+    /// Get the URL to store, cache or log with the token replaced and user info removed.
+    ///
+    /// The token is only replaced within path segments and query values.
+    pub(crate) fn strip(&self, url: &Url) -> Url {
+        let mut url = match self.get_token() {
+            Some(token) => replace_in_url(url, |value| value.replace(token, TOKEN_PLACEHOLDER)),
+            None => url.clone(),
+        };
+        let _ = url.set_username("");
+        let _ = url.set_password(None);
+        url
+    }
+
+    /// Get a copy of a document with every occurrence of the token replaced.
+    pub(crate) fn strip_bytes(&self, bytes: &[u8]) -> Vec<u8> {
+        let Some(token) = self.get_token() else {
+            return bytes.to_vec();
+        };
+        replace_bytes(bytes, token.as_bytes(), TOKEN_PLACEHOLDER.as_bytes())
+    }
+
+    /// Get the URL to request with the token restored.
+    pub(crate) fn apply(&self, url: &Url) -> Url {
+        let Some(token) = self.get_token() else {
+            return url.clone();
+        };
+        replace_in_url(url, |value| {
+            value
+                .replace(TOKEN_PLACEHOLDER_ENCODED, token)
+                .replace(TOKEN_PLACEHOLDER, token)
+        })
+    }
+
+    /// Get the Basic auth username and password if the URL is on the credential host.
+    pub(crate) fn get_basic_auth(&self, url: &Url) -> Option<(&str, Option<&str>)> {
+        let username = self.username.as_deref()?;
+        if self.host.as_deref() != url.host_str() {
+            return None;
+        }
+        Some((username, self.password.as_deref()))
+    }
+
+    /// Get the Basic auth username and password for an allowed host that asked for them.
+    ///
+    /// Enclosures of a private feed may be served from a different host such as a CDN.
+    /// Only hosts in `auth_hosts` are answered so redirects to trackers never receive them.
+    pub(crate) fn get_challenge_auth(&self, url: &Url) -> Option<(&str, Option<&str>)> {
+        let username = self.username.as_deref()?;
+        let host = url.host_str()?;
+        if self.host.as_deref() == Some(host)
+            || !self.auth_hosts.iter().any(|allowed| allowed == host)
+        {
+            return None;
+        }
+        Some((username, self.password.as_deref()))
+    }
+
+    fn get_token(&self) -> Option<&str> {
+        self.token.as_deref().filter(|token| !token.is_empty())
+    }
+}
+
+/// Replace text within the path segments and query values of a URL.
+///
+/// The scheme, host and query keys are left unchanged.
+fn replace_in_url(url: &Url, replace: impl Fn(&str) -> String) -> Url {
+    let mut url = url.clone();
+    let path = url
+        .path_segments()
+        .map(|segments| segments.map(&replace).collect::<Vec<_>>().join("/"))
+        .map(|path| format!("/{path}"));
+    if let Some(path) = path.filter(|path| path != url.path()) {
+        url.set_path(&path);
+    }
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    let replaced: Vec<(String, String)> = pairs
+        .iter()
+        .map(|(key, value)| (key.clone(), replace(value)))
+        .collect();
+    if replaced != pairs {
+        url.query_pairs_mut().clear().extend_pairs(replaced);
+    }
+    url
+}
+
+/// Replace every occurrence of `from` in `bytes` with `to`.
+fn replace_bytes(bytes: &[u8], from: &[u8], to: &[u8]) -> Vec<u8> {
+    let mut replaced = Vec::with_capacity(bytes.len());
+    let mut rest = bytes;
+    while let Some(index) = rest.windows(from.len()).position(|window| window == from) {
+        let (before, after) = rest.split_at(index);
+        replaced.extend_from_slice(before);
+        replaced.extend_from_slice(to);
+        rest = after.get(from.len()..).unwrap_or_default();
+    }
+    replaced.extend_from_slice(rest);
+    replaced
+}
+
+impl Debug for Credentials {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Credentials")
+            .field("host", &self.host)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| REDACTED))
+            .field("auth_hosts", &self.auth_hosts)
+            .field("token", &self.token.as_ref().map(|_| REDACTED))
+            .field(
+                "proxy_password",
+                &self.proxy_password.as_ref().map(|_| REDACTED),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_then_apply_query_token() {
+        // Arrange
+        let credentials = Credentials {
+            token: Some("s3cr3t-token".to_owned()),
+            ..Credentials::default()
+        };
+        let url = Url::parse("https://www.patreon.com/rss/example?auth=s3cr3t-token&show=1")
+            .expect("URL should parse");
+
+        // Act
+        let stripped = credentials.strip(&url);
+        let applied = credentials.apply(&stripped);
+
+        // Assert
+        assert!(!stripped.as_str().contains("s3cr3t"));
+        assert_eq!(applied, url);
+    }
+
+    #[test]
+    fn strip_then_apply_path_token() {
+        // Arrange
+        let credentials = Credentials {
+            token: Some("AbC123xyz".to_owned()),
+            ..Credentials::default()
+        };
+        let url =
+            Url::parse("https://example.supercast.com/feeds/AbC123xyz").expect("URL should parse");
+
+        // Act
+        let stripped = credentials.strip(&url);
+        let applied = credentials.apply(&stripped);
+
+        // Assert
+        assert_eq!(
+            stripped.as_str(),
+            "https://example.supercast.com/feeds/%7Btoken%7D"
+        );
+        assert_eq!(applied, url);
+        assert_eq!(credentials.strip(&stripped), stripped);
+    }
+
+    #[test]
+    fn strip_only_path_and_query() {
+        // Arrange
+        let credentials = Credentials {
+            token: Some("example".to_owned()),
+            ..Credentials::default()
+        };
+        let url = Url::parse("https://example.com/example/feed?example=example&q=a%20b")
+            .expect("URL should parse");
+
+        // Act
+        let stripped = credentials.strip(&url);
+        let applied = credentials.apply(&stripped);
+
+        // Assert
+        assert_eq!(
+            stripped.as_str(),
+            "https://example.com/%7Btoken%7D/feed?example=%7Btoken%7D&q=a+b"
+        );
+        assert_eq!(applied.host_str(), Some("example.com"));
+        assert_eq!(applied.path(), url.path());
+        assert_eq!(
+            applied.query_pairs().collect::<Vec<_>>(),
+            url.query_pairs().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn strip_bytes() {
+        // Arrange
+        let credentials = Credentials {
+            token: Some("s3cr3t".to_owned()),
+            ..Credentials::default()
+        };
+        let bytes = b"<url>https://example.com/s3cr3t/a.mp3?auth=s3cr3t</url>\xff";
+
+        // Act
+        let stripped = credentials.strip_bytes(bytes);
+
+        // Assert
+        assert_eq!(
+            stripped,
+            b"<url>https://example.com/{token}/a.mp3?auth={token}</url>\xff"
+        );
+    }
+
+    #[test]
+    fn get_basic_auth() {
+        // Arrange
+        let credentials = Credentials {
+            host: Some("private.example.com".to_owned()),
+            username: Some("user".to_owned()),
+            password: Some("pass".to_owned()),
+            ..Credentials::default()
+        };
+        let allowed = Credentials {
+            auth_hosts: vec!["cdn.example.net".to_owned()],
+            ..credentials.clone()
+        };
+        let feed = Url::parse("https://private.example.com/feed").expect("URL should parse");
+        let cdn = Url::parse("https://cdn.example.net/audio.mp3").expect("URL should parse");
+        let tracker = Url::parse("https://tracker.example.org/a.mp3").expect("URL should parse");
+
+        // Act
+        // Assert
+        assert_eq!(
+            credentials.get_basic_auth(&feed),
+            Some(("user", Some("pass")))
+        );
+        assert_eq!(credentials.get_basic_auth(&cdn), None);
+        assert_eq!(credentials.get_challenge_auth(&feed), None);
+        assert_eq!(credentials.get_challenge_auth(&cdn), None);
+        assert_eq!(
+            allowed.get_challenge_auth(&cdn),
+            Some(("user", Some("pass")))
+        );
+        assert_eq!(allowed.get_challenge_auth(&tracker), None);
+        assert!(!format!("{credentials:?}").contains("pass\""));
+    }
+}
@@ -0,0 +1,16 @@
+use crate::prelude::*;
+
+/// An episode audio file written to the output directory
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct DownloadRecord {
+    /// GUID of the episode
+    pub episode_id: String,
+    /// Path of the tagged output file
+    pub path: PathBuf,
+    /// URL the audio was downloaded from
+    pub audio_url: Url,
+    /// Size of the audio file in bytes according to the source
+    pub audio_file_size: u64,
+    /// Date and time the download completed
+    pub downloaded_at: NaiveDateTime,
+}
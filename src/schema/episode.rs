@@ -15,6 +15,23 @@ pub struct Episode {
     /// URL of media file including a file extension
     /// - Supported file formats include M4A, MP3, MOV, MP4, M4V, and PDF
     pub audio_url: Url,
+    /// Enclosure URLs with tracking prefixes that were unwrapped to get `audio_url`
+    ///
+    /// Starts with the original enclosure URL and each following URL has one tracking
+    /// prefix removed. These are the prefixes found in the feed rather than the HTTP
+    /// redirects followed when downloading.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tracked_audio_urls: Vec<Url>,
+    /// URL the audio was fetched from when it was last downloaded
+    ///
+    /// Differs from `audio_url` if the download was redirected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_url: Option<Url>,
+    /// HTTP redirects followed when the audio was last downloaded
+    ///
+    /// Each URL is where the previous request was redirected so the last is `download_url`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub download_redirects: Vec<Url>,
     /// Size of audio file in bytes
     pub audio_file_size: u64,
     /// Mime type of audio file
@@ -35,10 +52,18 @@ pub struct Episode {
     pub number: Option<usize>,
     /// Date and time episode was released
     pub published_at: DateTime<FixedOffset>,
+    /// Date and time the episode was no longer found in the source
+    ///
+    /// Removed episodes are kept for the archive.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub removed_at: Option<NaiveDateTime>,
+    /// Changes seen by previous scrapes, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<EpisodeChange>,
 }
 
 /// Episode type
-#[derive(AsRefStr, Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[derive(AsRefStr, Clone, Debug, Default, Deserialize, PartialEq, Serialize, ValueEnum)]
 pub enum EpisodeType {
     /// Complete content
     #[default]
@@ -84,6 +109,15 @@ impl Episode {
         format!("S{:02}", season.unwrap_or(0))
     }
 
+    /// Date and time the audio URL or file size last changed.
+    pub(crate) fn get_audio_changed_at(&self) -> Option<NaiveDateTime> {
+        self.history
+            .iter()
+            .filter(|change| change.is_audio())
+            .map(|change| change.changed_at)
+            .max()
+    }
+
     fn get_formatted_date(&self) -> String {
         self.published_at.format("%Y-%m-%d").to_string()
     }
@@ -100,6 +134,9 @@ impl Episode {
             description: "Aenean sit amet sem quis velit viverra vestibulum. Vivamus aliquam mattis ipsum, a dignissim elit pulvinar vitae. Aliquam neque risus, tincidunt sit amet elit quis, malesuada ultrices urna.".to_owned(),
             image_url: Some(Url::parse("https://example.com/image.jpg").expect("URL should be valid")),
             audio_url: Url::parse("https://example.com/season-1/episode-1.mp3").expect("URL should be valid"),
+            tracked_audio_urls: Vec::new(),
+            download_url: None,
+            download_redirects: Vec::new(),
             episode_type: EpisodeType::default(),
             season: Some(2),
             number: Some(3),
@@ -108,6 +145,8 @@ impl Episode {
             published_at: DateTime::default(),
             duration: None,
             explicit: false,
+            removed_at: None,
+            history: Vec::new(),
         }
     }
 }
@@ -196,6 +235,9 @@ impl TryFrom<RssItem> for Episode {
             description: item.description.unwrap_or_default(),
             audio_url: Url::parse(&enclosure.url)
                 .map_err(|e| PodcastConvertError::Url("audio url".to_owned(), e))?,
+            tracked_audio_urls: Vec::new(),
+            download_url: None,
+            download_redirects: Vec::new(),
             audio_file_size: enclosure
                 .length
                 .parse::<u64>()
@@ -212,6 +254,8 @@ impl TryFrom<RssItem> for Episode {
             number: itunes.episode.and_then(|n| n.parse::<usize>().ok()),
             published_at: DateTime::parse_from_rfc2822(&published_at)
                 .map_err(|e| PodcastConvertError::Date("published at".to_owned(), e))?,
+            removed_at: None,
+            history: Vec::new(),
         })
     }
 }
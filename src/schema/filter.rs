@@ -0,0 +1,348 @@
+use crate::prelude::*;
+use chrono::NaiveDate;
+use regex::{Regex, RegexBuilder};
+use std::cmp::Reverse;
+use std::collections::HashSet;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Select episodes by their fields.
+///
+/// Every set criterion must match. Terms of `--filter` are combined with the flags.
+#[derive(Clone, Debug, Default, Args)]
+pub struct EpisodeFilter {
+    /// Only episodes of a season
+    #[arg(long)]
+    pub season: Option<usize>,
+    /// Only episodes numbered in a range: `42`, `100-150`, `100-` or `-150`
+    #[arg(long, value_parser = NumberRange::parse)]
+    pub number: Option<NumberRange>,
+    /// Only episodes of a type
+    #[arg(long = "type", value_enum)]
+    pub episode_type: Option<EpisodeType>,
+    /// Only episodes published in a year
+    #[arg(long)]
+    pub year: Option<i32>,
+    /// Only episodes published on or after the start of a date: `2023`, `2023-06` or `2023-06-15`
+    #[arg(long, value_parser = parse_date)]
+    pub after: Option<NaiveDate>,
+    /// Only episodes published before the start of a date: `2023`, `2023-06` or `2023-06-15`
+    #[arg(long, value_parser = parse_date)]
+    pub before: Option<NaiveDate>,
+    /// Only episodes with a title matching a case insensitive regular expression
+    #[arg(long, value_parser = parse_title)]
+    pub title: Option<Regex>,
+    /// Only the latest episodes that match the other criteria
+    #[arg(long)]
+    pub latest: Option<usize>,
+    /// Filter expression of space separated `key:value` terms
+    ///
+    /// Keys are `season`, `number`, `type`, `year`, `after`, `before`, `title` and `latest`.
+    /// A title wrapped in slashes may contain spaces and an `i` suffix is accepted,
+    /// for example: `season:3 type:bonus after:2023-06 title:/the interview/i latest:10`
+    #[arg(long = "filter", value_parser = EpisodeFilter::parse)]
+    pub expression: Option<Box<EpisodeFilter>>,
+    /// Log the matching episodes without changing anything
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// Inclusive range of episode numbers
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct NumberRange {
+    pub start: Option<usize>,
+    pub end: Option<usize>,
+}
+
+impl NumberRange {
+    /// Parse `42`, `100-150`, `100-` or `-150`.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let parse = |value: &str| -> Result<Option<usize>, String> {
+            let value = value.trim();
+            if value.is_empty() {
+                return Ok(None);
+            }
+            value
+                .parse()
+                .map(Some)
+                .map_err(|_| format!("Invalid episode number: {value}"))
+        };
+        let range = if let Some((start, end)) = input.split_once(['-', '–']) {
+            Self {
+                start: parse(start)?,
+                end: parse(end)?,
+            }
+        } else {
+            let number = parse(input)?;
+            Self {
+                start: number,
+                end: number,
+            }
+        };
+        if range.start.is_none() && range.end.is_none() {
+            return Err(format!("Invalid episode range: {input}"));
+        }
+        Ok(range)
+    }
+
+    #[must_use]
+    pub fn contains(&self, number: usize) -> bool {
+        self.start.is_none_or(|start| number >= start) && self.end.is_none_or(|end| number <= end)
+    }
+}
+
+impl EpisodeFilter {
+    /// Parse a filter expression.
+    pub fn parse(input: &str) -> Result<Box<Self>, String> {
+        let mut filter = Self::default();
+        let mut rest = input.trim();
+        while !rest.is_empty() {
+            let (key, value) = rest
+                .split_once(':')
+                .ok_or_else(|| format!("Expected key:value at: {rest}"))?;
+            let (value, remaining) = split_value(value)?;
+            rest = remaining.trim_start();
+            match key.trim() {
+                "season" => {
+                    let season = value
+                        .parse()
+                        .map_err(|_| format!("Invalid season: {value}"))?;
+                    filter.season = Some(season);
+                }
+                "number" => filter.number = Some(NumberRange::parse(value)?),
+                "type" => filter.episode_type = Some(EpisodeType::from_str(value, true)?),
+                "year" => {
+                    let year = value
+                        .parse()
+                        .map_err(|_| format!("Invalid year: {value}"))?;
+                    filter.year = Some(year);
+                }
+                "after" => filter.after = Some(parse_date(value)?),
+                "before" => filter.before = Some(parse_date(value)?),
+                "title" => filter.title = Some(parse_title(value)?),
+                "latest" => {
+                    let latest = value
+                        .parse()
+                        .map_err(|_| format!("Invalid count: {value}"))?;
+                    filter.latest = Some(latest);
+                }
+                key => return Err(format!("Unknown filter key: {key}")),
+            }
+        }
+        Ok(Box::new(filter))
+    }
+
+    /// Does the filter have any criteria?
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.season.is_none()
+            && self.number.is_none()
+            && self.episode_type.is_none()
+            && self.year.is_none()
+            && self.after.is_none()
+            && self.before.is_none()
+            && self.title.is_none()
+            && self.latest.is_none()
+            && self
+                .expression
+                .as_ref()
+                .is_none_or(|filter| filter.is_empty())
+    }
+
+    /// Does an episode match every criterion other than `latest`?
+    fn is_match(&self, episode: &Episode) -> bool {
+        let published = episode.published_at.date_naive();
+        self.season
+            .is_none_or(|season| episode.season == Some(season))
+            && self
+                .number
+                .is_none_or(|range| episode.number.is_some_and(|number| range.contains(number)))
+            && self
+                .episode_type
+                .as_ref()
+                .is_none_or(|episode_type| episode.episode_type == *episode_type)
+            && self.year.is_none_or(|year| published.year() == year)
+            && self.after.is_none_or(|after| published >= after)
+            && self.before.is_none_or(|before| published < before)
+            && self
+                .title
+                .as_ref()
+                .is_none_or(|title| title.is_match(&episode.title))
+    }
+
+    /// Get the matching episodes.
+    ///
+    /// `latest` is applied after the criteria of the flags and the expression.
+    fn select<'a>(&self, episodes: Vec<&'a Episode>) -> Vec<&'a Episode> {
+        let mut matches: Vec<&Episode> = episodes
+            .into_iter()
+            .filter(|episode| self.is_match(episode))
+            .collect();
+        if let Some(expression) = &self.expression {
+            matches = expression.select(matches);
+        }
+        if let Some(latest) = self.latest {
+            matches.sort_by_key(|episode| Reverse(episode.published_at));
+            matches.truncate(latest);
+        }
+        matches
+    }
+
+    /// Get the IDs of the matching episodes.
+    #[must_use]
+    pub fn get_matches(&self, episodes: &[Episode]) -> HashSet<String> {
+        self.select(episodes.iter().collect())
+            .into_iter()
+            .map(|episode| episode.id.clone())
+            .collect()
+    }
+
+    /// Remove the episodes that don't match, keeping feed order.
+    #[must_use]
+    pub fn apply(&self, mut episodes: Vec<Episode>) -> Vec<Episode> {
+        if self.is_empty() {
+            return episodes;
+        }
+        let matches = self.get_matches(&episodes);
+        episodes.retain(|episode| matches.contains(&episode.id));
+        episodes
+    }
+
+    /// Log the matching episodes of a dry run.
+    pub fn log_matches(podcast_id: &str, episodes: &[Episode]) {
+        for episode in episodes {
+            info!(
+                "{} {} {}",
+                episode.published_at.format(DATE_FORMAT),
+                episode.get_file_stem(),
+                episode.title
+            );
+        }
+        info!(
+            "{} {} episodes of {podcast_id}",
+            "Matched".bold(),
+            episodes.len()
+        );
+    }
+}
+
+/// Split the value of a term from the rest of the expression.
+///
+/// A value starting with `/` runs to the closing `/` and an optional `i` suffix.
+fn split_value(input: &str) -> Result<(&str, &str), String> {
+    if let Some(pattern) = input.strip_prefix('/') {
+        let end = pattern
+            .find('/')
+            .ok_or_else(|| format!("Unterminated pattern: {input}"))?;
+        let mut end = end + 2;
+        if input.get(end..end + 1) == Some("i") {
+            end += 1;
+        }
+        let (value, rest) = input.split_at(end);
+        return Ok((value, rest));
+    }
+    Ok(input.split_once(' ').unwrap_or((input, "")))
+}
+
+/// Parse `2023`, `2023-06` or `2023-06-15` as the first day of the period.
+fn parse_date(input: &str) -> Result<NaiveDate, String> {
+    let input = input.trim();
+    let padded = match input.len() {
+        4 => format!("{input}-01-01"),
+        7 => format!("{input}-01"),
+        _ => input.to_owned(),
+    };
+    NaiveDate::parse_from_str(&padded, DATE_FORMAT)
+        .map_err(|e| format!("Invalid date {input}: {e}"))
+}
+
+/// Parse a title pattern as a case insensitive regular expression.
+///
+/// The pattern may be wrapped in slashes with an optional `i` suffix.
+fn parse_title(input: &str) -> Result<Regex, String> {
+    let pattern = input
+        .strip_prefix('/')
+        .and_then(|pattern| {
+            pattern
+                .strip_suffix("/i")
+                .or_else(|| pattern.strip_suffix('/'))
+        })
+        .unwrap_or(input);
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_episodes() -> Vec<Episode> {
+        let dates = ["2023-01-10", "2023-06-20", "2024-02-01", "2024-03-15"];
+        dates
+            .iter()
+            .enumerate()
+            .map(|(index, date)| {
+                let mut episode = Episode::example();
+                episode.id = format!("episode-{}", index + 1);
+                episode.number = Some(index + 1);
+                episode.season = Some(if index < 2 { 1 } else { 2 });
+                episode.title = format!("Episode {} interview", index + 1);
+                episode.published_at = DateTime::parse_from_rfc3339(&format!("{date}T12:00:00Z"))
+                    .expect("date should parse");
+                episode
+            })
+            .collect()
+    }
+
+    fn get_ids(episodes: &[Episode]) -> Vec<&str> {
+        episodes.iter().map(|episode| episode.id.as_str()).collect()
+    }
+
+    #[test]
+    fn filter_expression() {
+        // Arrange
+        let episodes = create_episodes();
+        let mut bonus = Episode::example();
+        bonus.id = "bonus".to_owned();
+        bonus.title = "The INTERVIEW outtakes".to_owned();
+        bonus.episode_type = EpisodeType::Bonus;
+        let mut with_bonus = episodes.clone();
+        with_bonus.push(bonus);
+
+        // Act
+        let season = EpisodeFilter::parse("season:2").assert_ok_debug();
+        let range = EpisodeFilter::parse("number:2-3").assert_ok_debug();
+        let after = EpisodeFilter::parse("after:2023-06 before:2024-03").assert_ok_debug();
+        let latest = EpisodeFilter::parse("latest:2 season:1").assert_ok_debug();
+        let title =
+            EpisodeFilter::parse("title:/interview outtakes/i type:bonus").assert_ok_debug();
+        let invalid = EpisodeFilter::parse("colour:red");
+
+        // Assert
+        let apply = |filter: &EpisodeFilter| filter.apply(episodes.clone());
+        assert_eq!(get_ids(&apply(&season)), ["episode-3", "episode-4"]);
+        assert_eq!(get_ids(&apply(&range)), ["episode-2", "episode-3"]);
+        assert_eq!(get_ids(&apply(&after)), ["episode-2", "episode-3"]);
+        assert_eq!(get_ids(&apply(&latest)), ["episode-1", "episode-2"]);
+        assert_eq!(get_ids(&title.apply(with_bonus)), ["bonus"]);
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn filter_flags_and_expression() {
+        // Arrange
+        let filter = EpisodeFilter {
+            year: Some(2024),
+            expression: Some(EpisodeFilter::parse("latest:1").assert_ok_debug()),
+            ..EpisodeFilter::default()
+        };
+
+        // Act
+        let result = filter.apply(create_episodes());
+
+        // Assert
+        assert_eq!(get_ids(&result), ["episode-4"]);
+    }
+}
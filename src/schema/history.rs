@@ -0,0 +1,50 @@
+use crate::prelude::*;
+
+/// Longest value shown before it is truncated.
+const MAX_DISPLAY_LENGTH: usize = 60;
+
+/// A field of an episode that changed between scrapes
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct EpisodeChange {
+    /// Date and time of the scrape that saw the change
+    pub changed_at: NaiveDateTime,
+    /// Name of the changed field
+    pub field: String,
+    /// Value before the change
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old: Option<String>,
+    /// Value after the change
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new: Option<String>,
+}
+
+impl EpisodeChange {
+    /// Does the change mean the audio file may differ from a previous download?
+    pub(crate) fn is_audio(&self) -> bool {
+        self.field == "audio_url" || self.field == "audio_file_size"
+    }
+}
+
+impl Display for EpisodeChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "{}: {} → {}",
+            self.field,
+            format_value(self.old.as_deref()),
+            format_value(self.new.as_deref())
+        )
+    }
+}
+
+fn format_value(value: Option<&str>) -> String {
+    let Some(value) = value else {
+        return "none".to_owned();
+    };
+    let value = value.replace('\n', " ");
+    if value.chars().count() <= MAX_DISPLAY_LENGTH {
+        return value;
+    }
+    let truncated: String = value.chars().take(MAX_DISPLAY_LENGTH).collect();
+    format!("{truncated}…")
+}
@@ -1,5 +1,17 @@
+pub(crate) use credentials::*;
+pub(crate) use download::*;
 pub(crate) use episode::*;
+pub(crate) use filter::*;
+pub(crate) use history::*;
 pub(crate) use podcast::*;
+pub(crate) use snapshot::*;
+pub(crate) use source::*;
 
+mod credentials;
+mod download;
 mod episode;
+mod filter;
+mod history;
 mod podcast;
+mod snapshot;
+mod source;
@@ -12,6 +12,11 @@ use strum_macros::AsRefStr;
 #[allow(clippy::struct_field_names)]
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Podcast {
+    /// Version of the schema the podcast was written with
+    ///
+    /// Documents without a version are version 0 and are migrated when read.
+    #[serde(default)]
+    pub schema_version: usize,
     /// Local file system ID
     pub id: String,
     /// GUID
@@ -47,6 +52,14 @@ pub struct Podcast {
     /// Copyright details
     pub copyright: Option<String>,
     pub created_at: Option<NaiveDateTime>,
+    /// Source the podcast was scraped from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<PodcastSource>,
+    /// Proxy URL for requests made for this podcast
+    ///
+    /// Takes precedence over the global proxy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<Url>,
     pub episodes: Vec<Episode>,
 }
 
@@ -64,6 +77,65 @@ impl Podcast {
             Err("Podcast ID must contain only lowercase letters and hyphens".to_owned())
         }
     }
+
+    /// Remove passwords from every URL before the podcast is written to disk.
+    ///
+    /// Returns `true` if anything was redacted.
+    pub(crate) fn redact(&mut self) -> bool {
+        let mut proxy = self.proxy.take();
+        let mut redacted = false;
+        for url in proxy.iter_mut().chain(self.get_urls_mut()) {
+            if let Some(redacted_url) = redact_url(url) {
+                *url = redacted_url;
+                redacted = true;
+            }
+        }
+        self.proxy = proxy;
+        redacted
+    }
+
+    /// Remove the password from the proxy URL so it can be stored with the credentials.
+    pub(crate) fn take_proxy_password(&mut self) -> Option<String> {
+        let proxy = self.proxy.as_mut()?;
+        let password = proxy.password()?.to_owned();
+        let _ = proxy.set_password(None);
+        Some(password)
+    }
+
+    /// Restore the proxy password stored with the credentials.
+    pub(crate) fn restore_proxy_password(&mut self, password: &str) {
+        if let Some(proxy) = self
+            .proxy
+            .as_mut()
+            .filter(|proxy| proxy.password().is_none())
+        {
+            let _ = proxy.set_password(Some(password));
+        }
+    }
+
+    /// Replace credential secrets in every URL except the proxy so the podcast can be stored.
+    pub(crate) fn strip_credentials(&mut self, credentials: &Credentials) {
+        for url in self.get_urls_mut() {
+            *url = credentials.strip(url);
+        }
+    }
+
+    fn get_urls_mut(&mut self) -> Vec<&mut Url> {
+        let mut urls: Vec<&mut Url> = vec![&mut self.link];
+        if let Some(source) = &mut self.source {
+            urls.push(&mut source.url);
+            urls.push(&mut source.feed_url);
+        }
+        urls.extend(self.image_url.as_mut());
+        for episode in &mut self.episodes {
+            urls.push(&mut episode.audio_url);
+            urls.extend(episode.tracked_audio_urls.iter_mut());
+            urls.extend(episode.download_url.as_mut());
+            urls.extend(episode.download_redirects.iter_mut());
+            urls.extend(episode.image_url.as_mut());
+        }
+        urls
+    }
 }
 
 /// Episodic or Serial
@@ -99,7 +171,10 @@ impl Podcast {
             link: Url::parse("https://example.com/").expect("URL should be valid"),
             podcast_type: PodcastType::default(),
             copyright: None,
+            schema_version: SCHEMA_VERSION,
             created_at: Some(Utc::now().naive_utc()),
+            source: None,
+            proxy: None,
             episodes: vec![Episode::example()],
         }
     }
@@ -170,7 +245,10 @@ impl TryFrom<RssChannel> for Podcast {
                 .map_err(|e| PodcastConvertError::Url("link".to_owned(), e))?,
             podcast_type: itunes.r#type.unwrap_or_default().into(),
             copyright: channel.copyright,
+            schema_version: SCHEMA_VERSION,
             created_at: None,
+            source: None,
+            proxy: None,
             episodes: channel
                 .items
                 .into_iter()
@@ -0,0 +1,79 @@
+use crate::prelude::*;
+use chrono::Utc;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+/// Format of snapshot names, sortable by the time they were taken.
+const NAME_FORMAT: &str = "%Y%m%dT%H%M%S%.6f";
+
+/// Raw source documents seen by one scrape
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Snapshot {
+    /// Date and time of the scrape
+    pub taken_at: NaiveDateTime,
+    /// Source the documents were scraped from
+    pub source: Option<PodcastSource>,
+    /// Response bodies by their path relative to the HTTP cache directory
+    pub documents: BTreeMap<String, Document>,
+}
+
+/// Response body of a source document
+///
+/// Stored as text unless the body is not valid UTF-8.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum Document {
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl Snapshot {
+    pub(crate) fn new(
+        source: Option<PodcastSource>,
+        documents: BTreeMap<String, Document>,
+    ) -> Self {
+        Self {
+            taken_at: Utc::now().naive_utc(),
+            source,
+            documents,
+        }
+    }
+
+    pub(crate) fn get_name(&self) -> String {
+        self.taken_at.format(NAME_FORMAT).to_string()
+    }
+
+    /// Replace the token of a private feed in every document.
+    pub(crate) fn strip_credentials(&mut self, credentials: &Credentials) {
+        for document in self.documents.values_mut() {
+            let stripped = credentials.strip_bytes(document.as_bytes());
+            *document = Document::from_bytes(stripped);
+        }
+    }
+
+    /// Do both snapshots have the same source and documents?
+    pub(crate) fn is_same(&self, other: &Snapshot) -> bool {
+        self.source == other.source && self.documents == other.documents
+    }
+}
+
+impl Document {
+    pub(crate) fn from_bytes(bytes: Vec<u8>) -> Self {
+        match String::from_utf8(bytes) {
+            Ok(text) => Document::Text(text),
+            Err(e) => Document::Bytes(e.into_bytes()),
+        }
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        match self {
+            Document::Text(text) => text.as_bytes(),
+            Document::Bytes(bytes) => bytes,
+        }
+    }
+
+    /// Get the text of the document with invalid UTF-8 replaced.
+    pub(crate) fn to_text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(self.as_bytes())
+    }
+}
@@ -0,0 +1,49 @@
+use crate::prelude::*;
+use chrono::Utc;
+use std::collections::BTreeMap;
+use strum_macros::AsRefStr;
+
+/// Where and when a podcast was scraped from
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PodcastSource {
+    /// URL passed to the scrape command
+    pub url: Url,
+    /// URL of the feed or API the episodes were read from
+    pub feed_url: Url,
+    /// Kind of source
+    pub kind: SourceKind,
+    /// Date and time of the last successful scrape
+    pub scraped_at: NaiveDateTime,
+    /// IDs of the podcast on the source
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub ids: BTreeMap<String, String>,
+}
+
+/// Kind of source a podcast was scraped from
+#[derive(AsRefStr, Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceKind {
+    /// RSS feed
+    Rss,
+    /// Website with a Simplecast player
+    Simplecast,
+}
+
+impl PodcastSource {
+    pub(crate) fn new(url: Url, feed_url: Url, kind: SourceKind) -> Self {
+        Self {
+            url,
+            feed_url,
+            kind,
+            scraped_at: Utc::now().naive_utc(),
+            ids: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn with_id(mut self, name: &str, id: &str) -> Self {
+        if !id.is_empty() {
+            self.ids.insert(name.to_owned(), id.to_owned());
+        }
+        self
+    }
+}
@@ -0,0 +1,368 @@
+use crate::prelude::*;
+use chrono::Utc;
+use serde_yaml::Value;
+use std::cmp::Reverse;
+use std::collections::VecDeque;
+
+/// Changes to episodes made by merging a scrape with the saved podcast.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct MergeSummary {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+    pub restored: Vec<String>,
+    pub unchanged: usize,
+}
+
+impl MergeSummary {
+    pub(crate) fn log(&self) {
+        for (verb, episodes) in [
+            ("Added", &self.added),
+            ("Updated", &self.updated),
+            ("Removed", &self.removed),
+            ("Restored", &self.restored),
+        ] {
+            for episode in episodes {
+                debug!("{} {episode}", verb.bold());
+            }
+        }
+        info!(
+            "{} {} added, {} updated, {} removed, {} restored, {} unchanged",
+            "Merged".bold(),
+            self.added.len(),
+            self.updated.len(),
+            self.removed.len(),
+            self.restored.len(),
+            self.unchanged
+        );
+    }
+}
+
+/// Merge a freshly scraped podcast with the saved podcast by episode GUID.
+///
+/// - New episodes are added
+/// - Changed episodes are updated from the source and the changes appended to their history
+/// - Optional values missing from the source are kept from the saved episode
+/// - Episodes missing from the source are kept, marked with `removed_at` and placed in
+///   `published_at` order
+/// - Removed episodes found in the source again are restored
+///
+/// Episodes that share a GUID are matched in the order they were saved.
+pub(crate) fn merge(existing: Option<Podcast>, podcast: &mut Podcast) -> MergeSummary {
+    let mut summary = MergeSummary::default();
+    let Some(existing) = existing else {
+        summary.added = podcast.episodes.iter().map(ToString::to_string).collect();
+        return summary;
+    };
+    let now = Utc::now().naive_utc();
+    let mut previous: HashMap<String, VecDeque<Episode>> = HashMap::new();
+    for episode in existing.episodes {
+        previous
+            .entry(episode.id.clone())
+            .or_default()
+            .push_back(episode);
+    }
+    for episode in &mut podcast.episodes {
+        let Some(before) = previous.get_mut(&episode.id).and_then(VecDeque::pop_front) else {
+            summary.added.push(episode.to_string());
+            continue;
+        };
+        keep_missing_values(&before, episode);
+        let changes = get_changes(&before, episode, now);
+        let restored = before.removed_at.is_some();
+        episode.history = before.history;
+        if restored {
+            summary.restored.push(episode.to_string());
+        } else if changes.is_empty() {
+            summary.unchanged += 1;
+        } else {
+            let fields: Vec<&str> = changes.iter().map(|change| change.field.as_str()).collect();
+            summary
+                .updated
+                .push(format!("{episode} ({})", fields.join(", ")));
+        }
+        episode.history.extend(changes);
+    }
+    let mut missing: Vec<Episode> = previous.into_values().flatten().collect();
+    missing.sort_by_key(|episode| Reverse(episode.published_at));
+    for mut episode in missing {
+        if episode.removed_at.is_none() {
+            summary.removed.push(episode.to_string());
+            episode.removed_at = Some(now);
+            episode.history.push(EpisodeChange {
+                changed_at: now,
+                field: "removed_at".to_owned(),
+                old: None,
+                new: Some(now.to_string()),
+            });
+        }
+        insert_by_date(&mut podcast.episodes, episode);
+    }
+    summary
+}
+
+/// Keep the saved values of optional fields the source did not supply.
+///
+/// The download URL and redirects are never in the source so are always kept.
+fn keep_missing_values(before: &Episode, episode: &mut Episode) {
+    episode.download_url.clone_from(&before.download_url);
+    episode
+        .download_redirects
+        .clone_from(&before.download_redirects);
+    if episode.duration.is_none() {
+        episode.duration = before.duration;
+    }
+    if episode.image_url.is_none() {
+        episode.image_url.clone_from(&before.image_url);
+    }
+    if episode.season.is_none() {
+        episode.season = before.season;
+    }
+    if episode.number.is_none() {
+        episode.number = before.number;
+    }
+}
+
+/// Insert an episode in `published_at` order.
+///
+/// Follows the order of the scraped episodes, which is newest first unless the
+/// first episode is older than the last.
+fn insert_by_date(episodes: &mut Vec<Episode>, episode: Episode) {
+    let ascending = match (episodes.first(), episodes.last()) {
+        (Some(first), Some(last)) => first.published_at < last.published_at,
+        _ => false,
+    };
+    let index = episodes.partition_point(|other| {
+        if ascending {
+            other.published_at <= episode.published_at
+        } else {
+            other.published_at >= episode.published_at
+        }
+    });
+    episodes.insert(index, episode);
+}
+
+/// Get the fields that differ between two versions of an episode.
+///
+/// The history itself is not compared.
+fn get_changes(before: &Episode, after: &Episode, now: NaiveDateTime) -> Vec<EpisodeChange> {
+    let (Ok(Value::Mapping(before)), Ok(Value::Mapping(after))) =
+        (serde_yaml::to_value(before), serde_yaml::to_value(after))
+    else {
+        return Vec::new();
+    };
+    let mut changes: Vec<EpisodeChange> = after
+        .iter()
+        .filter(|(key, value)| before.get(*key) != Some(*value))
+        .map(|(key, value)| (key, before.get(key), Some(value)))
+        .chain(
+            before
+                .iter()
+                .filter(|(key, _)| !after.contains_key(*key))
+                .map(|(key, value)| (key, Some(value), None)),
+        )
+        .filter_map(|(key, old, new)| {
+            let field = key.as_str()?;
+            if field == "history" {
+                return None;
+            }
+            Some(EpisodeChange {
+                changed_at: now,
+                field: field.to_owned(),
+                old: old.and_then(format_value),
+                new: new.and_then(format_value),
+            })
+        })
+        .collect();
+    changes.dedup_by(|a, b| a.field == b.field);
+    changes
+}
+
+/// Format a YAML value for the history, with `None` for null.
+fn format_value(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(value) => Some(value.clone()),
+        value => serde_yaml::to_string(value)
+            .ok()
+            .map(|yaml| yaml.trim_end().to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    fn create_episode(id: &str, title: &str) -> Episode {
+        let mut episode = Episode::example();
+        id.clone_into(&mut episode.id);
+        title.clone_into(&mut episode.title);
+        episode
+    }
+
+    fn create_podcast(episodes: Vec<Episode>) -> Podcast {
+        let mut podcast = Podcast::example();
+        podcast.episodes = episodes;
+        podcast
+    }
+
+    #[test]
+    fn merge_by_guid() {
+        // Arrange
+        let existing = create_podcast(vec![
+            create_episode("a", "A"),
+            create_episode("b", "B"),
+            create_episode("c", "C"),
+        ]);
+        let mut podcast = create_podcast(vec![
+            create_episode("d", "D"),
+            create_episode("a", "A (remastered)"),
+            create_episode("b", "B"),
+        ]);
+
+        // Act
+        let summary = merge(Some(existing), &mut podcast);
+
+        // Assert
+        assert_eq!(summary.added.len(), 1);
+        assert_eq!(summary.updated.len(), 1);
+        assert!(summary.updated.iter().all(|e| e.ends_with("(title)")));
+        assert_eq!(summary.removed.len(), 1);
+        assert_eq!(summary.unchanged, 1);
+        let ids: Vec<&str> = podcast.episodes.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, vec!["d", "a", "b", "c"]);
+        let removed = podcast
+            .episodes
+            .last()
+            .expect("should keep removed episode");
+        assert!(removed.removed_at.is_some());
+        assert_eq!(
+            removed.history.first().map(|change| change.field.as_str()),
+            Some("removed_at")
+        );
+    }
+
+    #[test]
+    fn merge_appends_history() {
+        // Arrange
+        let mut before = create_episode("a", "A");
+        before.history.push(EpisodeChange {
+            changed_at: NaiveDateTime::default(),
+            field: "title".to_owned(),
+            old: Some("Draft".to_owned()),
+            new: Some("A".to_owned()),
+        });
+        let existing = create_podcast(vec![before]);
+        let mut after = create_episode("a", "A (remastered)");
+        after.audio_file_size = 2048;
+        let mut podcast = create_podcast(vec![after]);
+
+        // Act
+        let _ = merge(Some(existing), &mut podcast);
+
+        // Assert
+        let episode = podcast.episodes.first().expect("should have episode");
+        let history: Vec<String> = episode.history.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            history,
+            vec![
+                "title: Draft → A",
+                "title: A → A (remastered)",
+                "audio_file_size: 1024 → 2048",
+            ]
+        );
+        assert!(episode.get_audio_changed_at().is_some());
+    }
+
+    #[test]
+    fn merge_keeps_missing_values_and_date_order() {
+        // Arrange
+        let dated = |id: &str, days: i64| {
+            let mut episode = create_episode(id, id);
+            episode.published_at += TimeDelta::days(days);
+            episode
+        };
+        let mut before = dated("b", 2);
+        before.duration = Some(600);
+        before.season = Some(3);
+        let existing = create_podcast(vec![dated("d", 4), dated("c", 3), before, dated("a", 1)]);
+        let mut after = dated("b", 2);
+        after.duration = None;
+        after.season = None;
+        let mut podcast = create_podcast(vec![dated("d", 4), after, dated("a", 1)]);
+        let mut ascending = create_podcast(vec![dated("a", 1), dated("b", 2), dated("d", 4)]);
+
+        // Act
+        let summary = merge(Some(existing.clone()), &mut podcast);
+        let _ = merge(Some(existing), &mut ascending);
+
+        // Assert
+        assert_eq!(summary.unchanged, 3);
+        assert!(summary.updated.is_empty());
+        let ids: Vec<&str> = podcast.episodes.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, vec!["d", "c", "b", "a"]);
+        let episode = podcast
+            .episodes
+            .iter()
+            .find(|e| e.id == "b")
+            .expect("should have episode");
+        assert_eq!(episode.duration, Some(600));
+        assert_eq!(episode.season, Some(3));
+        let ids: Vec<&str> = ascending.episodes.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn merge_duplicate_guids() {
+        // Arrange
+        let mut first = create_episode("a", "A");
+        first.duration = Some(600);
+        let mut second = create_episode("a", "A (part 2)");
+        second.history.push(EpisodeChange {
+            changed_at: NaiveDateTime::default(),
+            field: "title".to_owned(),
+            old: Some("Draft".to_owned()),
+            new: Some("A (part 2)".to_owned()),
+        });
+        let existing = create_podcast(vec![first, second]);
+        let mut after = create_episode("a", "A");
+        after.duration = None;
+        let mut podcast = create_podcast(vec![after]);
+
+        // Act
+        let summary = merge(Some(existing), &mut podcast);
+
+        // Assert
+        assert_eq!(summary.unchanged, 1);
+        assert_eq!(summary.removed.len(), 1);
+        assert_eq!(podcast.episodes.len(), 2);
+        let kept = podcast.episodes.first().expect("should have episode");
+        assert_eq!(kept.duration, Some(600));
+        let removed = podcast
+            .episodes
+            .iter()
+            .find(|e| e.removed_at.is_some())
+            .expect("should keep the duplicate");
+        assert_eq!(removed.title, "A (part 2)");
+        assert_eq!(removed.history.len(), 2);
+    }
+
+    #[test]
+    fn merge_restores() {
+        // Arrange
+        let mut removed = create_episode("a", "A");
+        removed.removed_at = Some(Utc::now().naive_utc());
+        let existing = create_podcast(vec![removed]);
+        let mut podcast = create_podcast(vec![create_episode("a", "A")]);
+
+        // Act
+        let summary = merge(Some(existing), &mut podcast);
+
+        // Assert
+        assert_eq!(summary.restored.len(), 1);
+        assert!(summary.removed.is_empty());
+        assert_eq!(podcast.episodes.len(), 1);
+        assert!(podcast.episodes.iter().all(|e| e.removed_at.is_none()));
+    }
+}
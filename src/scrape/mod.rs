@@ -1,8 +1,12 @@
+pub use merge::*;
 pub use options::*;
 pub use scrape::*;
 pub(super) use scrape_simplecast::*;
+pub use tracking::*;
 
+mod merge;
 mod options;
 pub mod scrape;
 mod scrape_simplecast;
 mod simplecast;
+mod tracking;
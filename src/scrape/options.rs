@@ -1,5 +1,8 @@
 use crate::prelude::*;
 
+/// Default number of concurrent requests for the episodes of a website.
+pub(crate) const SCRAPE_CONCURRENCY: usize = 8;
+
 #[derive(Debug, Args)]
 pub struct ScrapeOptions {
     /// ID of the downloaded podcast
@@ -9,4 +12,32 @@ pub struct ScrapeOptions {
     pub podcast_id: String,
     /// URL of the RSS feed or website
     pub url: Url,
+    /// Proxy URL for requests made for this podcast
+    ///
+    /// Saved with the podcast and used by later commands.
+    /// Supports `http`, `https`, `socks5` and `socks5h` schemes.
+    #[arg(long, value_parser = Validate::parse_proxy)]
+    pub proxy: Option<Url>,
+    /// HTTP Basic auth username for a private feed
+    ///
+    /// Credentials are saved separately from the podcast and used by later commands.
+    /// A username and password in the URL are used if not set.
+    #[arg(long)]
+    pub username: Option<String>,
+    /// HTTP Basic auth password for a private feed
+    #[arg(long)]
+    pub password: Option<String>,
+    /// Another host to send the Basic auth to if it asks, such as the CDN of the enclosures
+    ///
+    /// Repeat for each host. Other hosts never receive the credentials.
+    #[arg(long = "auth-host", value_name = "HOST")]
+    pub auth_hosts: Vec<String>,
+    /// Secret token in the URL of a private feed such as Patreon or Supercast
+    ///
+    /// Replaced with `{token}` in saved URLs, cache paths and logs.
+    #[arg(long)]
+    pub token: Option<String>,
+    /// Maximum number of concurrent requests for the episodes of a website
+    #[arg(long, default_value_t = SCRAPE_CONCURRENCY)]
+    pub concurrency: usize,
 }
@@ -1,33 +1,175 @@
 use crate::prelude::*;
 use rss::Channel;
+use strum_macros::IntoStaticStr;
 
 pub struct ScrapeCommand {
     pub(super) http: HttpClient,
     pub(super) podcasts: PodcastProvider,
+    tracking: TrackingPrefixes,
 }
 
 impl ScrapeCommand {
     #[must_use]
     pub fn new(http: HttpClient, podcasts: PodcastProvider) -> Self {
-        Self { http, podcasts }
+        Self {
+            http,
+            podcasts,
+            tracking: TrackingPrefixes::default(),
+        }
     }
 
-    pub async fn execute(&self, options: ScrapeOptions) -> Result<Podcast, ScrapeError> {
+    /// Set the tracking prefixes removed from enclosure URLs.
+    #[must_use]
+    pub fn with_tracking_prefixes(mut self, tracking: TrackingPrefixes) -> Self {
+        self.tracking = tracking;
+        self
+    }
+
+    pub async fn execute(&self, mut options: ScrapeOptions) -> Result<ScrapeReport, ScrapeError> {
+        let _lock = self
+            .podcasts
+            .lock(&options.podcast_id)
+            .await
+            .map_err(ScrapeError::Lock)?;
+        let existing = self.podcasts.get(&options.podcast_id).ok();
+        let proxy = options
+            .proxy
+            .clone()
+            .or_else(|| existing.as_ref().and_then(|podcast| podcast.proxy.clone()));
+        let credentials = self.get_credentials(&options)?;
+        options.url = credentials.strip(&options.url);
+        let recorder = SnapshotRecorder::default();
+        let http = self
+            .http
+            .for_podcast_proxy(proxy.as_ref())
+            .await
+            .map_err(ScrapeError::Client)?
+            .with_credentials(Some(credentials.clone()))
+            .with_recorder(recorder.clone());
+        let command = ScrapeCommand::new(http, self.podcasts.clone())
+            .with_tracking_prefixes(self.tracking.clone());
+        let (mut podcast, errors) = command.fetch(&options).await?;
+        if self.http.is_cancelled() {
+            return Err(ScrapeError::Cancelled);
+        }
+        podcast.strip_credentials(&credentials);
+        podcast.proxy = proxy;
+        info!("{} {} episodes", "Fetched".bold(), podcast.episodes.len());
+        let summary = merge(existing, &mut podcast);
+        self.podcasts.put(&podcast).map_err(ScrapeError::Save)?;
+        summary.log();
+        self.save_snapshot(&podcast, &recorder).await;
+        Ok(ScrapeReport {
+            podcast_id: podcast.id.clone(),
+            episodes: podcast.episodes.len(),
+            changes: summary,
+            podcast,
+            errors,
+        })
+    }
+
+    /// Save the raw source documents of the scrape.
+    ///
+    /// A failure is logged rather than failing the scrape as the podcast is already saved.
+    async fn save_snapshot(&self, podcast: &Podcast, recorder: &SnapshotRecorder) {
+        let documents = match recorder.read().await {
+            Ok(documents) => documents,
+            Err((path, e)) => {
+                warn!(
+                    "{} to read source documents for snapshot\nPath: {}\n{e}",
+                    "Failed".bold(),
+                    path.display()
+                );
+                return;
+            }
+        };
+        let snapshot = Snapshot::new(podcast.source.clone(), documents);
+        match self.podcasts.put_snapshot(&podcast.id, &snapshot) {
+            Ok(true) => debug!(
+                "{} snapshot {} of {} documents",
+                "Saved".bold(),
+                snapshot.get_name(),
+                snapshot.documents.len()
+            ),
+            Ok(false) => debug!(
+                "{} snapshot as the source documents are unchanged",
+                "Skipped".bold()
+            ),
+            Err(e) => warn!("{} to save snapshot\n{e}", "Failed".bold()),
+        }
+    }
+
+    /// Get credentials from the options, the URL or those saved by a previous scrape.
+    ///
+    /// New credentials are saved for later commands.
+    fn get_credentials(&self, options: &ScrapeOptions) -> Result<Credentials, ScrapeError> {
+        let url = &options.url;
+        let username = options
+            .username
+            .clone()
+            .or_else(|| Some(url.username().to_owned()).filter(|name| !name.is_empty()));
+        let password = options
+            .password
+            .clone()
+            .or_else(|| url.password().map(ToOwned::to_owned));
+        let credentials = Credentials {
+            host: url.host_str().map(ToOwned::to_owned),
+            username,
+            password,
+            auth_hosts: options.auth_hosts.clone(),
+            token: options.token.clone(),
+            ..Credentials::default()
+        };
+        if credentials.is_empty() {
+            let saved = self
+                .podcasts
+                .get_credentials(&options.podcast_id)
+                .map_err(ScrapeError::Credentials)?;
+            return Ok(saved.unwrap_or_default());
+        }
+        self.podcasts
+            .put_credentials(&options.podcast_id, &credentials)
+            .map_err(ScrapeError::Credentials)?;
+        Ok(credentials)
+    }
+
+    /// Fetch the podcast from its source with the errors of any episodes that were skipped.
+    pub(crate) async fn fetch(
+        &self,
+        options: &ScrapeOptions,
+    ) -> Result<(Podcast, Vec<ErrorOutput>), ScrapeError> {
+        let (mut podcast, errors) = self.fetch_source(options).await?;
+        for episode in &mut podcast.episodes {
+            self.tracking.unwrap_episode(episode);
+        }
+        Ok((podcast, errors))
+    }
+
+    async fn fetch_source(
+        &self,
+        options: &ScrapeOptions,
+    ) -> Result<(Podcast, Vec<ErrorOutput>), ScrapeError> {
         let content_type = self
             .http
             .head(&options.url)
             .await
-            .map_err(ScrapeError::Head)?;
-        let podcast = match content_type.as_str() {
-            "application/xml" => self.execute_rss(&options).await.map_err(ScrapeError::Rss)?,
+            .map_err(|e| get_unauthorized(&e).unwrap_or(ScrapeError::Head(e)))?;
+        match content_type.as_str() {
+            "application/xml" => self
+                .execute_rss(options)
+                .await
+                .map(|podcast| (podcast, Vec::new()))
+                .map_err(|e| match e {
+                    ScrapeRssError::Xml(e) => {
+                        get_unauthorized(&e).unwrap_or(ScrapeError::Rss(ScrapeRssError::Xml(e)))
+                    }
+                    e => ScrapeError::Rss(e),
+                }),
             _ => self
-                .execute_simplecast(&options)
+                .execute_simplecast(options)
                 .await
-                .map_err(ScrapeError::Simplecast)?,
-        };
-        info!("{} {} episodes", "Fetched".bold(), podcast.episodes.len());
-        self.podcasts.put(&podcast).map_err(ScrapeError::Save)?;
-        Ok(podcast)
+                .map_err(ScrapeError::Simplecast),
+        }
     }
 
     pub(super) async fn execute_rss(
@@ -45,16 +187,54 @@ impl ScrapeCommand {
         let channel = Channel::read_from(reader).map_err(ScrapeRssError::Parse)?;
         let mut podcast: Podcast = channel.try_into().map_err(ScrapeRssError::Convert)?;
         podcast.id = options.podcast_id.clone();
+        let source = PodcastSource::new(options.url.clone(), options.url.clone(), SourceKind::Rss)
+            .with_id("guid", &podcast.guid);
+        podcast.source = Some(source);
         Ok(podcast)
     }
 }
 
-#[derive(Debug)]
+/// Get an error for a response rejecting the credentials.
+fn get_unauthorized(error: &HttpError) -> Option<ScrapeError> {
+    match error {
+        HttpError::Response(url, status @ (401 | 403)) => {
+            Some(ScrapeError::Unauthorized(url.clone(), *status))
+        }
+        _ => None,
+    }
+}
+
+/// Result of a scrape
+#[derive(Debug, Serialize)]
+pub struct ScrapeReport {
+    /// ID of the scraped podcast
+    pub podcast_id: String,
+    /// Number of episodes after merging
+    pub episodes: usize,
+    /// Changes to episodes since the previous scrape
+    pub changes: MergeSummary,
+    /// The saved podcast
+    #[serde(skip)]
+    pub podcast: Podcast,
+    /// Errors of the episodes that could not be fetched
+    ///
+    /// Output as the errors of the command rather than part of the result.
+    #[serde(skip)]
+    pub errors: Vec<ErrorOutput>,
+}
+
+#[derive(Debug, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
 pub enum ScrapeError {
+    Client(HttpError),
+    Credentials(DatabaseError),
+    Unauthorized(Url, u16),
     Head(HttpError),
     Simplecast(ScrapeSimplecastError),
     Rss(ScrapeRssError),
     Save(DatabaseError),
+    Cancelled,
+    Lock(LockError),
 }
 
 #[derive(Debug)]
@@ -69,17 +249,32 @@ pub enum ScrapeRssError {
 impl Display for ScrapeError {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         let reason = match self {
+            ScrapeError::Client(e) => format!("{e}"),
+            ScrapeError::Credentials(e) => format!("Unable to get credentials: {e}"),
+            ScrapeError::Unauthorized(url, status) => format!(
+                "Credentials were rejected with status {status}\nURL: {url}\nCheck the --username, --password or --token options"
+            ),
             ScrapeError::Head(e) => format!("Unable to get content type:\n{e}"),
             ScrapeError::Simplecast(e) => format!("{e}"),
             ScrapeError::Rss(e) => {
-                format!("{e}",)
+                format!("{e}")
             }
             ScrapeError::Save(e) => format!("Unable to save: {e}"),
+            ScrapeError::Cancelled => {
+                "Scrape was cancelled before the podcast was saved".to_owned()
+            }
+            ScrapeError::Lock(e) => format!("Unable to lock podcast\n{e}"),
         };
         write!(f, "{} to scrape\n{reason}", "Failed".bold())
     }
 }
 
+impl ErrorCode for ScrapeError {
+    fn get_code(&self) -> String {
+        format!("scrape.{}", <&str>::from(self))
+    }
+}
+
 impl Display for ScrapeRssError {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         let reason = match self {
@@ -91,10 +286,10 @@ impl Display for ScrapeRssError {
                 )
             }
             ScrapeRssError::Parse(e) => {
-                format!("Unable to parse RSS\n{e}",)
+                format!("Unable to parse RSS\n{e}")
             }
             ScrapeRssError::Convert(e) => {
-                format!("Unable to convert RSS\n{e}",)
+                format!("Unable to convert RSS\n{e}")
             }
         };
         write!(f, "{} to scrape\n{reason}", "Failed".bold())
@@ -109,41 +304,101 @@ mod tests {
     pub async fn scrape_command_simplecast() {
         // Arrange
         let _ = init_logging();
-        let services = ServiceProvider::create()
-            .await
-            .expect("ServiceProvider should not fail");
+        let services = create_fixture_services("pura-scrape-simplecast").await;
         let command = ScrapeCommand::new(services.http, services.podcasts);
         let options = ScrapeOptions {
-            podcast_id: "irl".to_owned(),
-            url: Url::parse("https://irlpodcast.org").expect("URL should parse"),
+            podcast_id: SIMPLECAST_FIXTURE_PODCAST_ID.to_owned(),
+            url: Url::parse(SIMPLECAST_FIXTURE_URL).expect("URL should parse"),
+            proxy: None,
+            username: None,
+            password: None,
+            auth_hosts: Vec::new(),
+            token: None,
+            concurrency: SCRAPE_CONCURRENCY,
         };
 
         // Act
         let result = command.execute(options).await;
 
         // Assert
-        let podcast = result.assert_ok();
-        assert!(podcast.episodes.len() > 30);
+        let report = result.assert_ok();
+        assert!(report.errors.is_empty());
+        let podcast = report.podcast;
+        assert_eq!(podcast.title, "Simplecast Fixture");
+        assert_eq!(podcast.episodes.len(), 3);
+        let source = podcast.source.expect("should have source");
+        assert_eq!(source.kind, SourceKind::Simplecast);
     }
 
     #[tokio::test]
     pub async fn scrape_command_rss() {
         // Arrange
         let _ = init_logging();
-        let services = ServiceProvider::create()
-            .await
-            .expect("ServiceProvider should not fail");
+        let services = create_fixture_services("pura-scrape-rss").await;
+        let command = ScrapeCommand::new(services.http, services.podcasts);
+        let options = ScrapeOptions {
+            podcast_id: "simplecast-rss".to_owned(),
+            url: Url::parse(SIMPLECAST_FIXTURE_FEED_URL).expect("URL should parse"),
+            proxy: None,
+            username: None,
+            password: None,
+            auth_hosts: Vec::new(),
+            token: None,
+            concurrency: SCRAPE_CONCURRENCY,
+        };
+
+        // Act
+        let result = command.execute(options).await;
+
+        // Assert
+        let podcast = result.assert_ok().podcast;
+        assert_eq!(podcast.title, "Simplecast Fixture");
+        assert_eq!(podcast.episodes.len(), 3);
+        let source = podcast.source.expect("should have source");
+        assert_eq!(source.kind, SourceKind::Rss);
+    }
+
+    #[tokio::test]
+    pub async fn scrape_command_replay() {
+        // Arrange
+        let _ = init_logging();
+        let services = create_fixture_services("pura-scrape").await;
+        let podcasts = services.podcasts.clone();
         let command = ScrapeCommand::new(services.http, services.podcasts);
         let options = ScrapeOptions {
-            podcast_id: "irl-rss".to_owned(),
-            url: Url::parse("https://feeds.simplecast.com/lP7owBq8").expect("URL should parse"),
+            podcast_id: FIXTURE_PODCAST_ID.to_owned(),
+            url: Url::parse("https://feeds.example.com/fixture").expect("URL should parse"),
+            proxy: None,
+            username: None,
+            password: None,
+            auth_hosts: Vec::new(),
+            token: None,
+            concurrency: SCRAPE_CONCURRENCY,
         };
 
         // Act
         let result = command.execute(options).await;
 
         // Assert
-        let podcast = result.assert_ok();
-        assert!(podcast.episodes.len() > 30);
+        let report = result.assert_ok();
+        assert_eq!(report.changes.added.len(), 3);
+        let podcast = report.podcast;
+        assert_eq!(podcast.title, "Fixture Podcast");
+        assert_eq!(podcast.episodes.len(), 3);
+        assert_eq!(podcasts.get(FIXTURE_PODCAST_ID).assert_ok(), podcast);
+    }
+
+    #[test]
+    pub fn get_unauthorized_status() {
+        // Arrange
+        let url = Url::parse("https://feeds.example.com/private").expect("URL should parse");
+
+        // Act
+        let forbidden = get_unauthorized(&HttpError::Response(url.clone(), 403));
+        let not_found = get_unauthorized(&HttpError::Response(url, 404));
+
+        // Assert
+        assert!(matches!(forbidden, Some(ScrapeError::Unauthorized(_, 403))));
+        assert!(not_found.is_none());
     }
 }
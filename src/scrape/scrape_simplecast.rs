@@ -1,13 +1,16 @@
 use crate::prelude::*;
 use crate::scrape::simplecast::*;
-
-const CONCURRENCY: usize = 8;
+use std::collections::HashSet;
+use tokio::fs::read_to_string;
 
 impl ScrapeCommand {
+    /// Scrape a podcast from the Simplecast API.
+    ///
+    /// Episodes that can't be fetched are skipped and their errors returned with the podcast.
     pub(super) async fn execute_simplecast(
         &self,
         options: &ScrapeOptions,
-    ) -> Result<Podcast, ScrapeSimplecastError> {
+    ) -> Result<(Podcast, Vec<ErrorOutput>), ScrapeSimplecastError> {
         let player_id = self.get_player_id(&options.url).await?;
         let episode = self.get_episode(&player_id).await?;
         let podcast = self.get_podcast(&episode).await?;
@@ -18,12 +21,32 @@ impl ScrapeCommand {
             playlist.len(),
             episode.podcast.title
         );
-        let episodes = self.get_episodes(&playlist).await;
-        let diff = playlist.len() - episodes.len();
-        if diff > 0 {
-            warn!("{} {} episodes due to failures", "Skipped".bold(), diff);
+        let (episodes, errors) = self.get_episodes(&playlist, options.concurrency).await;
+        if !errors.is_empty() {
+            warn!(
+                "{} {} episodes due to failures",
+                "Skipped".bold(),
+                errors.len()
+            );
         }
-        Ok(convert(&options.podcast_id, podcast, episodes))
+        let errors = errors
+            .into_iter()
+            .map(|(id, e)| ErrorOutput {
+                episode: Some(id),
+                ..ErrorOutput::new(&ScrapeError::Simplecast(e)).with_podcast(&options.podcast_id)
+            })
+            .collect();
+        let feed_url = Url::parse(&format!(
+            "https://api.simplecast.com/podcasts/{}",
+            podcast.id
+        ))
+        .expect("URL should be valid");
+        let source = PodcastSource::new(options.url.clone(), feed_url, SourceKind::Simplecast)
+            .with_id("podcast", &podcast.id)
+            .with_id("episode", &player_id);
+        let mut podcast = convert(&options.podcast_id, podcast, episodes);
+        podcast.source = Some(source);
+        Ok((podcast, errors))
     }
 
     async fn get_player_id(&self, url: &Url) -> Result<String, ScrapeSimplecastError> {
@@ -41,9 +64,46 @@ impl ScrapeCommand {
         Ok(episode_guid)
     }
 
+    /// Remove every cached Simplecast response of a podcast so they are fetched again.
+    ///
+    /// The cached playlist pages are followed to find the later pages and the episodes.
+    pub(crate) async fn remove_cached_simplecast(&self, source: &PodcastSource) {
+        self.http.remove(&source.url, Some(HTML_EXTENSION)).await;
+        self.http
+            .remove(&source.feed_url, Some(JSON_EXTENSION))
+            .await;
+        if let Some(id) = source.ids.get("episode") {
+            self.http
+                .remove(&get_episode_url(id), Some(JSON_EXTENSION))
+                .await;
+        }
+        let mut next = Some(
+            Url::parse(&format!("{}/playlist", source.feed_url)).expect("URL should be valid"),
+        );
+        let mut visited = HashSet::new();
+        while let Some(url) = next.take() {
+            if !visited.insert(url.clone()) {
+                break;
+            }
+            let path = self.http.get_cache_path(&url, Some(JSON_EXTENSION));
+            let playlist = read_to_string(&path)
+                .await
+                .ok()
+                .and_then(|json| serde_json::from_str::<SimplecastPlaylist>(&json).ok());
+            if let Some(playlist) = playlist {
+                for episode in &playlist.episodes.collection {
+                    self.http
+                        .remove(&get_episode_url(&episode.id), Some(JSON_EXTENSION))
+                        .await;
+                }
+                next = playlist.episodes.pages.next.map(|link| link.href);
+            }
+            self.http.remove(&url, Some(JSON_EXTENSION)).await;
+        }
+    }
+
     async fn get_episode(&self, id: &str) -> Result<SimplecastEpisode, ScrapeSimplecastError> {
-        let episode_url = Url::parse(&format!("https://api.simplecast.com/episodes/{id}"))
-            .expect("URL should be valid");
+        let episode_url = get_episode_url(id);
         let episode: SimplecastEpisode = self
             .http
             .get_json(&episode_url)
@@ -102,40 +162,57 @@ impl ScrapeCommand {
         Ok(episodes)
     }
 
-    async fn get_episodes(&self, playlist: &[SimplecastPlaylistEpisode]) -> Vec<SimplecastEpisode> {
+    /// Get the episodes of a playlist and the errors of those that failed by episode ID.
+    #[allow(clippy::as_conversions)]
+    async fn get_episodes(
+        &self,
+        playlist: &[SimplecastPlaylistEpisode],
+        concurrency: usize,
+    ) -> (Vec<SimplecastEpisode>, Vec<(String, ScrapeSimplecastError)>) {
         debug!(
             "{} metadata for {} episodes",
             "Fetching".bold(),
             playlist.len()
         );
         let progress = Progress::new(playlist.len() as u64);
-        let result = stream::iter(playlist.iter().map(|episode| {
+        let results: Vec<_> = stream::iter(playlist.iter().map(|episode| {
             let this = self;
             let progress = progress.clone();
             async move {
-                let result = match this.get_episode(&episode.id).await {
-                    Ok(ep) => Some(ep),
-                    Err(e) => {
-                        warn!("{} to get episode {}", "Failed".bold(), episode.id);
-                        debug!("{e}");
-                        None
-                    }
-                };
+                if this.http.is_cancelled() {
+                    return None;
+                }
+                let result = this.get_episode(&episode.id).await;
+                if result.is_err() {
+                    warn!("{} to get episode {}", "Failed".bold(), episode.id);
+                }
                 progress.update();
-                result
+                Some(result.map_err(|e| (episode.id.clone(), e)))
             }
         }))
-        .buffer_unordered(CONCURRENCY)
+        .buffer_unordered(concurrency.max(1))
         .collect::<Vec<_>>()
         .await
         .into_iter()
         .flatten()
         .collect();
         progress.finish();
-        result
+        let mut episodes = Vec::new();
+        let mut errors = Vec::new();
+        for result in results {
+            match result {
+                Ok(episode) => episodes.push(episode),
+                Err(error) => errors.push(error),
+            }
+        }
+        (episodes, errors)
     }
 }
 
+fn get_episode_url(id: &str) -> Url {
+    Url::parse(&format!("https://api.simplecast.com/episodes/{id}")).expect("URL should be valid")
+}
+
 fn get_simplecast_episode_guid(html: &Html) -> Option<String> {
     let mut src = get_element_attr(html, "iframe", "src");
     src.append(&mut get_element_attr(html, "iframe", "data-src"));
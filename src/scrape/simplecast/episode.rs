@@ -72,6 +72,9 @@ impl From<SimplecastEpisode> for Episode {
             description: episode.description,
             image_url: episode.image_url,
             audio_url: episode.enclosure_url,
+            tracked_audio_urls: Vec::new(),
+            download_url: None,
+            download_redirects: Vec::new(),
             episode_type: episode.episode_type.into(),
             season: Some(episode.season.number),
             number: episode.number,
@@ -80,6 +83,8 @@ impl From<SimplecastEpisode> for Episode {
             published_at: episode.published_at,
             duration: episode.duration,
             explicit: episode.is_explicit,
+            removed_at: None,
+            history: Vec::new(),
         }
     }
 }
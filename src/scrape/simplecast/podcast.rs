@@ -34,7 +34,10 @@ impl From<SimplecastPodcast> for Podcast {
             link: podcast.site.external_website,
             podcast_type: podcast.podcast_type.into(),
             copyright: podcast.copyright,
+            schema_version: SCHEMA_VERSION,
             created_at: Some(podcast.created_at),
+            source: None,
+            proxy: None,
             episodes: vec![],
         }
     }
@@ -0,0 +1,211 @@
+use crate::prelude::*;
+
+/// Tracking prefixes removed by default.
+///
+/// Each pattern is a host and path prefix where `*` in a segment matches any characters.
+const DEFAULT_PREFIXES: &str = "dts.podtrac.com/redirect.*,\
+www.podtrac.com/pts/redirect.*,\
+chtbl.com/track/*,\
+pdst.fm/e,\
+op3.dev/e*,\
+prfx.byspotify.com/e,\
+pfx.vpixl.com/*,\
+mgln.ai/e/*,\
+arttrk.com/p/*,\
+pscrb.fm/rss/p,\
+verifi.podscribe.com/rss/p,\
+claritaspod.com/measure,\
+tracking.swap.fm/track/*";
+
+/// Limit to the number of nested prefixes removed from one URL.
+const MAX_DEPTH: usize = 10;
+
+/// Rules to unwrap analytics and tracking redirect prefixes from enclosure URLs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrackingPrefixes {
+    prefixes: Vec<TrackingPrefix>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct TrackingPrefix {
+    host: String,
+    segments: Vec<String>,
+}
+
+impl TrackingPrefixes {
+    /// Parse comma separated patterns such as `dts.podtrac.com/redirect.*`.
+    pub fn parse(patterns: &str) -> Result<Self, StringValidationError> {
+        let prefixes = patterns
+            .split(',')
+            .map(str::trim)
+            .filter(|pattern| !pattern.is_empty())
+            .map(TrackingPrefix::parse)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { prefixes })
+    }
+
+    #[must_use]
+    pub fn from_options(options: &AppOptions) -> Self {
+        options
+            .tracking_prefixes
+            .as_deref()
+            .map(Self::parse)
+            .and_then(Result::ok)
+            .unwrap_or_default()
+    }
+
+    /// Unwrap every tracking prefix from a URL.
+    ///
+    /// Returns the chain of URLs that were unwrapped, starting with the original URL,
+    /// followed by the origin URL. The chain is empty if no prefix matched.
+    pub(crate) fn unwrap(&self, url: &Url) -> (Vec<Url>, Url) {
+        let mut chain = Vec::new();
+        let mut url = url.clone();
+        while chain.len() < MAX_DEPTH {
+            let Some(origin) = self.prefixes.iter().find_map(|prefix| prefix.unwrap(&url)) else {
+                break;
+            };
+            chain.push(url);
+            url = origin;
+        }
+        (chain, url)
+    }
+
+    /// Replace the audio URL of an episode with its origin and record the unwrapped URLs.
+    pub(crate) fn unwrap_episode(&self, episode: &mut Episode) {
+        let (chain, origin) = self.unwrap(&episode.audio_url);
+        if chain.is_empty() {
+            return;
+        }
+        trace!(
+            "{} {} tracking prefixes from {episode}",
+            "Removed".bold(),
+            chain.len()
+        );
+        episode.tracked_audio_urls = chain;
+        episode.audio_url = origin;
+    }
+}
+
+impl Default for TrackingPrefixes {
+    fn default() -> Self {
+        Self::parse(DEFAULT_PREFIXES).expect("default tracking prefixes should be valid")
+    }
+}
+
+impl TrackingPrefix {
+    fn parse(pattern: &str) -> Result<Self, StringValidationError> {
+        let mut parts = pattern.trim_matches('/').split('/');
+        let host = parts.next().unwrap_or_default().to_lowercase();
+        let segments: Vec<String> = parts.map(ToOwned::to_owned).collect();
+        if host.is_empty() || host.contains('*') || segments.iter().any(String::is_empty) {
+            return Err(StringValidationError::Unsupported(pattern.to_owned()));
+        }
+        Ok(Self { host, segments })
+    }
+
+    fn unwrap(&self, url: &Url) -> Option<Url> {
+        if url.host_str()?.to_lowercase() != self.host {
+            return None;
+        }
+        let mut rest = url.path().strip_prefix('/')?;
+        for pattern in &self.segments {
+            let (segment, remainder) = rest.split_once('/')?;
+            if !matches_segment(pattern, segment) {
+                return None;
+            }
+            rest = remainder;
+        }
+        let (scheme, rest) = match rest.split_once(':') {
+            Some((scheme @ ("http" | "https"), rest)) => (scheme, rest.trim_start_matches('/')),
+            _ => (url.scheme(), rest),
+        };
+        if rest.is_empty() {
+            return None;
+        }
+        let mut origin = format!("{scheme}://{rest}");
+        if let Some(query) = url.query() {
+            origin.push('?');
+            origin.push_str(query);
+        }
+        let origin = Url::parse(&origin).ok()?;
+        origin.host_str()?.contains('.').then_some(origin)
+    }
+}
+
+/// Match a path segment against a pattern with at most one `*`.
+fn matches_segment(pattern: &str, segment: &str) -> bool {
+    match pattern.split_once('*') {
+        Some((start, end)) => {
+            segment.len() >= start.len() + end.len()
+                && segment.starts_with(start)
+                && segment.ends_with(end)
+        }
+        None => pattern == segment,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unwrap(url: &str) -> (Vec<String>, String) {
+        let url = Url::parse(url).expect("URL should parse");
+        let (chain, origin) = TrackingPrefixes::default().unwrap(&url);
+        (
+            chain.into_iter().map(String::from).collect(),
+            origin.to_string(),
+        )
+    }
+
+    #[test]
+    fn unwrap_chain() {
+        // Arrange
+        let url = "https://dts.podtrac.com/redirect.mp3/chtbl.com/track/ABC123/op3.dev/e,pg=abc/https://traffic.example.com/episode.mp3?updated=1";
+
+        // Act
+        let (chain, origin) = unwrap(url);
+
+        // Assert
+        assert_eq!(origin, "https://traffic.example.com/episode.mp3?updated=1");
+        assert_eq!(chain.len(), 3);
+        assert_eq!(chain.first().map(String::as_str), Some(url));
+        assert_eq!(
+            chain.get(1).map(String::as_str),
+            Some("https://chtbl.com/track/ABC123/op3.dev/e,pg=abc/https://traffic.example.com/episode.mp3?updated=1")
+        );
+    }
+
+    #[test]
+    fn unwrap_without_prefix() {
+        // Arrange
+        let url = "https://media.example.com/redirect.mp3/episode.mp3";
+
+        // Act
+        let (chain, origin) = unwrap(url);
+
+        // Assert
+        assert!(chain.is_empty());
+        assert_eq!(origin, url);
+    }
+
+    #[test]
+    fn parse_custom() {
+        // Arrange
+        let prefixes = TrackingPrefixes::parse("track.example.net/r/*").assert_ok_debug();
+        let url = Url::parse("http://track.example.net/r/xyz/cdn.example.com/a.mp3")
+            .expect("URL should parse");
+
+        // Act
+        let (chain, origin) = prefixes.unwrap(&url);
+
+        // Assert
+        assert_eq!(chain, vec![url]);
+        assert_eq!(origin.as_str(), "http://cdn.example.com/a.mp3");
+        assert!(TrackingPrefixes::parse("*/r").is_err());
+        assert_eq!(
+            TrackingPrefixes::parse("").assert_ok_debug(),
+            TrackingPrefixes { prefixes: vec![] }
+        );
+    }
+}
@@ -0,0 +1,67 @@
+use crate::prelude::*;
+
+/// Source of HTTP responses on a cache miss.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum HttpBackend {
+    /// Request responses from the network.
+    #[default]
+    Network,
+    /// Serve recorded responses from a fixture directory without using the network.
+    Replay(PathBuf),
+    /// Request responses from the network and record them to a fixture directory.
+    Record(PathBuf),
+}
+
+impl HttpBackend {
+    pub(crate) fn from_options(options: &AppOptions) -> Self {
+        if let Some(dir) = &options.replay_dir {
+            HttpBackend::Replay(dir.clone())
+        } else if let Some(dir) = &options.record_dir {
+            HttpBackend::Record(dir.clone())
+        } else {
+            HttpBackend::Network
+        }
+    }
+
+    /// Get the fixture path of a cache path.
+    ///
+    /// Fixtures use the same layout as the HTTP cache directory.
+    pub(crate) fn get_fixture_path(&self, cache_dir: &Path, cache_path: &Path) -> Option<PathBuf> {
+        let dir = match self {
+            HttpBackend::Network => return None,
+            HttpBackend::Replay(dir) | HttpBackend::Record(dir) => dir,
+        };
+        let relative = cache_path.strip_prefix(cache_dir).ok()?;
+        Some(dir.join(relative))
+    }
+
+    pub(crate) fn is_network(&self) -> bool {
+        !matches!(self, HttpBackend::Replay(_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_fixture_path() {
+        // Arrange
+        let replay = HttpBackend::Replay(PathBuf::from("fixtures/http"));
+        let cache_dir = PathBuf::from("cache/http");
+        let cache_path = cache_dir.join("example.com/feed.rss");
+
+        // Act
+        let result = replay.get_fixture_path(&cache_dir, &cache_path);
+
+        // Assert
+        assert_eq!(
+            result,
+            Some(PathBuf::from("fixtures/http/example.com/feed.rss"))
+        );
+        assert_eq!(
+            HttpBackend::Network.get_fixture_path(&cache_dir, &cache_path),
+            None
+        );
+    }
+}
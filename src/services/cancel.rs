@@ -0,0 +1,125 @@
+use crate::prelude::*;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::signal::ctrl_c;
+use tokio::sync::watch::Sender;
+use tokio::time::sleep;
+
+/// State of a run that can be stopped by a signal.
+#[derive(Clone, Copy, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+enum CancelState {
+    #[default]
+    Running,
+    /// No new work is scheduled but work in progress may finish
+    Cancelled,
+    /// Work in progress is stopped
+    Aborted,
+}
+
+/// Stop a run gracefully on Ctrl-C or SIGTERM.
+///
+/// The first signal stops new work from being scheduled. Work in progress is
+/// aborted after the grace period or on a second signal.
+#[derive(Clone, Debug, Default)]
+pub struct Cancellation {
+    state: Arc<Sender<CancelState>>,
+}
+
+impl Cancellation {
+    /// Listen for signals in the background.
+    pub(crate) fn listen(&self, grace: Duration) {
+        let this = self.clone();
+        tokio::spawn(async move {
+            wait_for_signal().await;
+            warn!(
+                "{} after the work in progress, waiting up to {}s. Press Ctrl-C again to abort",
+                "Stopping".bold(),
+                grace.as_secs()
+            );
+            this.cancel();
+            tokio::select! {
+                () = wait_for_signal() => {},
+                () = sleep(grace) => {},
+            }
+            warn!("{} the work in progress", "Aborting".bold());
+            this.abort();
+        });
+    }
+
+    /// Stop scheduling new work.
+    pub(crate) fn cancel(&self) {
+        self.set(CancelState::Cancelled);
+    }
+
+    /// Stop the work in progress.
+    pub(crate) fn abort(&self) {
+        self.set(CancelState::Aborted);
+    }
+
+    fn set(&self, state: CancelState) {
+        self.state.send_if_modified(|current| {
+            if *current >= state {
+                return false;
+            }
+            *current = state;
+            true
+        });
+    }
+
+    /// Has new work stopped being scheduled?
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        *self.state.borrow() >= CancelState::Cancelled
+    }
+
+    /// Wait until the work in progress is aborted.
+    pub(crate) async fn aborted(&self) {
+        let mut receiver = self.state.subscribe();
+        let _ = receiver
+            .wait_for(|state| *state == CancelState::Aborted)
+            .await;
+    }
+}
+
+/// Wait for Ctrl-C or, on unix, SIGTERM.
+async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                _ = ctrl_c() => {},
+                _ = terminate.recv() => {},
+            }
+            return;
+        }
+    }
+    let _ = ctrl_c().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn cancellation_states() {
+        // Arrange
+        let cancellation = Cancellation::default();
+        let clone = cancellation.clone();
+
+        // Act
+        let running = cancellation.is_cancelled();
+        cancellation.cancel();
+        let cancelled = clone.is_cancelled();
+        let not_aborted = timeout(Duration::from_millis(50), clone.aborted()).await;
+        cancellation.abort();
+        let aborted = timeout(Duration::from_millis(50), clone.aborted()).await;
+
+        // Assert
+        assert!(!running);
+        assert!(cancelled);
+        assert!(not_aborted.is_err());
+        assert!(aborted.is_ok());
+    }
+}
@@ -0,0 +1,297 @@
+use crate::prelude::*;
+use chrono::{DateTime, TimeDelta, Utc};
+use reqwest::header::{HeaderMap, SET_COOKIE};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::fs::{create_dir_all as create_dir_all_sync, read_to_string, write};
+use std::sync::{Arc, Mutex};
+
+type Cookies = BTreeMap<String, BTreeMap<String, Cookie>>;
+
+/// A stored cookie with the attributes that decide which requests it is sent with.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+struct Cookie {
+    value: String,
+    /// Only send to the host that set it rather than its subdomains too
+    host_only: bool,
+    /// Only send to URLs under this path
+    path: String,
+    /// Only send over `https`
+    secure: bool,
+    /// Stop sending after this time
+    expires: Option<NaiveDateTime>,
+}
+
+/// A `Set-Cookie` header parsed with its attributes.
+struct SetCookie {
+    name: String,
+    value: String,
+    domain: Option<String>,
+    path: Option<String>,
+    secure: bool,
+    expires: Option<NaiveDateTime>,
+}
+
+/// Cookies set by responses, stored by domain and sent with later matching requests.
+///
+/// The `Domain`, `Path`, `Secure`, `Expires` and `Max-Age` attributes are honoured.
+/// If a path is set the jar is read on creation and written whenever it changes
+/// so sessions persist between runs.
+#[derive(Clone, Default)]
+pub(crate) struct CookieJar {
+    path: Option<PathBuf>,
+    cookies: Arc<Mutex<Cookies>>,
+}
+
+impl CookieJar {
+    /// Read a persistent jar, starting empty if the file does not exist.
+    pub(crate) fn read(path: PathBuf) -> Result<Self, HeaderError> {
+        let cookies = if path.exists() {
+            let yaml = read_to_string(&path).map_err(|e| HeaderError::Io(path.clone(), e))?;
+            serde_yaml::from_str(&yaml)
+                .map_err(|e| HeaderError::Deserialization(path.clone(), e))?
+        } else {
+            Cookies::new()
+        };
+        Ok(Self {
+            path: Some(path),
+            cookies: Arc::new(Mutex::new(cookies)),
+        })
+    }
+
+    /// Get the `Cookie` header value for a URL.
+    ///
+    /// Secure cookies are only sent over `https` and expired cookies are never sent.
+    pub(crate) fn get(&self, url: &Url) -> Option<String> {
+        let host = url.host_str()?;
+        let is_https = url.scheme() == "https";
+        let now = Utc::now().naive_utc();
+        let cookies = self.cookies.lock().expect("lock should not be poisoned");
+        let values: Vec<String> = cookies
+            .iter()
+            .filter(|(domain, _)| is_domain_match(host, domain))
+            .flat_map(|(domain, cookies)| {
+                cookies
+                    .iter()
+                    .filter(move |(_, cookie)| !cookie.host_only || host == domain.as_str())
+            })
+            .filter(|(_, cookie)| is_https || !cookie.secure)
+            .filter(|(_, cookie)| cookie.expires.is_none_or(|expires| expires > now))
+            .filter(|(_, cookie)| is_path_match(url.path(), &cookie.path))
+            .map(|(name, cookie)| format!("{name}={}", cookie.value))
+            .collect();
+        if values.is_empty() {
+            None
+        } else {
+            Some(values.join("; "))
+        }
+    }
+
+    /// Store the `Set-Cookie` headers of a response.
+    ///
+    /// A cookie for a domain the host does not belong to is ignored. A cookie with
+    /// an empty value or that has already expired is removed.
+    pub(crate) fn store(&self, url: &Url, headers: &HeaderMap) {
+        let Some(host) = url.host_str() else {
+            return;
+        };
+        let now = Utc::now().naive_utc();
+        let mut changed = false;
+        let mut cookies = self.cookies.lock().expect("lock should not be poisoned");
+        for header in headers.get_all(SET_COOKIE) {
+            let Some(set_cookie) = header.to_str().ok().and_then(parse_set_cookie) else {
+                continue;
+            };
+            let domain = set_cookie.domain.unwrap_or_else(|| host.to_owned());
+            if !is_domain_match(host, &domain) {
+                trace!(
+                    "{} cookie {} for {domain} set by {host}",
+                    "Ignoring".bold(),
+                    set_cookie.name
+                );
+                continue;
+            }
+            let name = set_cookie.name;
+            let cookie = Cookie {
+                value: set_cookie.value,
+                host_only: domain == host,
+                path: set_cookie.path.unwrap_or_else(|| get_default_path(url)),
+                secure: set_cookie.secure,
+                expires: set_cookie.expires,
+            };
+            let domain_cookies = cookies.entry(domain.clone()).or_default();
+            if cookie.value.is_empty() || cookie.expires.is_some_and(|expires| expires <= now) {
+                changed |= domain_cookies.remove(&name).is_some();
+            } else if domain_cookies.get(&name) != Some(&cookie) {
+                trace!("{} cookie {name} for {domain}", "Storing".bold());
+                domain_cookies.insert(name, cookie);
+                changed = true;
+            }
+        }
+        if changed {
+            if let Err(e) = self.save(&cookies) {
+                warn!("{} to save cookie jar\n{e}", "Failed".bold());
+            }
+        }
+    }
+
+    fn save(&self, cookies: &Cookies) -> Result<(), HeaderError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            create_dir_all_sync(dir).map_err(|e| HeaderError::Io(dir.to_path_buf(), e))?;
+        }
+        let yaml = serde_yaml::to_string(cookies)
+            .map_err(|e| HeaderError::Serialization(path.clone(), e))?;
+        write(path, yaml).map_err(|e| HeaderError::Io(path.clone(), e))
+    }
+}
+
+impl Debug for CookieJar {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("CookieJar")
+            .field("path", &self.path)
+            .field("cookies", &REDACTED)
+            .finish()
+    }
+}
+
+/// Parse a `Set-Cookie` header.
+///
+/// `Max-Age` takes precedence over `Expires`. A `Domain` has its leading dot removed.
+fn parse_set_cookie(header: &str) -> Option<SetCookie> {
+    let mut parts = header.split(';');
+    let (name, value) = parts.next()?.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+    let mut cookie = SetCookie {
+        name: name.to_owned(),
+        value: value.trim().to_owned(),
+        domain: None,
+        path: None,
+        secure: false,
+        expires: None,
+    };
+    let mut max_age = None;
+    for attribute in parts {
+        let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+        let value = value.trim();
+        match key.trim().to_lowercase().as_str() {
+            "domain" => {
+                let domain = value.trim_start_matches('.').to_lowercase();
+                cookie.domain = (!domain.is_empty()).then_some(domain);
+            }
+            "path" if value.starts_with('/') => cookie.path = Some(value.to_owned()),
+            "secure" => cookie.secure = true,
+            "expires" => {
+                cookie.expires = DateTime::parse_from_rfc2822(value)
+                    .ok()
+                    .map(|expires| expires.naive_utc());
+            }
+            "max-age" => max_age = value.parse::<i64>().ok(),
+            _ => {}
+        }
+    }
+    if let Some(age) = max_age {
+        cookie.expires = Some(Utc::now().naive_utc() + TimeDelta::seconds(age.max(0)));
+    }
+    Some(cookie)
+}
+
+/// Is the host the domain or a subdomain of it?
+fn is_domain_match(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+/// Is the request path the cookie path or below it?
+fn is_path_match(path: &str, cookie_path: &str) -> bool {
+    path.strip_prefix(cookie_path)
+        .is_some_and(|rest| rest.is_empty() || cookie_path.ends_with('/') || rest.starts_with('/'))
+}
+
+/// Get the path a cookie without a `Path` applies to, which is the directory of the URL.
+fn get_default_path(url: &Url) -> String {
+    match url.path().rfind('/') {
+        Some(0) | None => "/".to_owned(),
+        Some(index) => url.path().get(..index).unwrap_or("/").to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn store_then_read() {
+        // Arrange
+        let path = TempDirectory::create("pura-cookies").join("cookies.yml");
+        let jar = CookieJar::read(path.clone()).assert_ok_debug();
+        let url = Url::parse("https://example.com/feed").expect("URL should parse");
+        let mut headers = HeaderMap::new();
+        headers.append(
+            SET_COOKIE,
+            HeaderValue::from_static("session=abc; Path=/; HttpOnly"),
+        );
+        headers.append(SET_COOKIE, HeaderValue::from_static("theme=dark"));
+        headers.append(SET_COOKIE, HeaderValue::from_static("old=1; Max-Age=0"));
+
+        // Act
+        jar.store(&url, &headers);
+        let result = CookieJar::read(path).assert_ok_debug();
+
+        // Assert
+        assert_eq!(result.get(&url).as_deref(), Some("session=abc; theme=dark"));
+        let other = Url::parse("https://example.org/").expect("URL should parse");
+        assert_eq!(result.get(&other), None);
+    }
+
+    #[test]
+    fn store_honours_attributes() {
+        // Arrange
+        let jar = CookieJar::default();
+        let url = Url::parse("https://www.example.com/shows/feed").expect("URL should parse");
+        let parse = |url: &str| Url::parse(url).expect("URL should parse");
+        let mut headers = HeaderMap::new();
+        headers.append(
+            SET_COOKIE,
+            HeaderValue::from_static("session=abc; Domain=.example.com; Path=/"),
+        );
+        headers.append(SET_COOKIE, HeaderValue::from_static("token=xyz; Secure"));
+        headers.append(
+            SET_COOKIE,
+            HeaderValue::from_static("other=1; Domain=example.org"),
+        );
+        headers.append(
+            SET_COOKIE,
+            HeaderValue::from_static("old=1; Expires=Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+
+        // Act
+        jar.store(&url, &headers);
+
+        // Assert
+        assert_eq!(jar.get(&url).as_deref(), Some("session=abc; token=xyz"));
+        assert_eq!(
+            jar.get(&parse("http://www.example.com/shows/feed"))
+                .as_deref(),
+            Some("session=abc")
+        );
+        assert_eq!(
+            jar.get(&parse("https://cdn.example.com/")).as_deref(),
+            Some("session=abc")
+        );
+        assert_eq!(
+            jar.get(&parse("https://www.example.com/other")).as_deref(),
+            Some("session=abc")
+        );
+        assert_eq!(jar.get(&parse("https://example.org/")), None);
+        assert_eq!(jar.get(&parse("https://badexample.com/")), None);
+    }
+}
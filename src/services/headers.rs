@@ -0,0 +1,226 @@
+use crate::prelude::*;
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, COOKIE, REFERER, USER_AGENT,
+};
+use std::fmt::Debug;
+
+/// Request headers applied to hosts matching a pattern.
+#[derive(Clone, Default, Deserialize, PartialEq, Serialize)]
+pub struct HeaderProfile {
+    /// Host pattern such as `feeds.example.com`, `*.example.com` or `*`
+    ///
+    /// `*.example.com` matches `example.com` and any of its subdomains.
+    pub host: String,
+    pub user_agent: Option<String>,
+    pub referer: Option<String>,
+    pub authorization: Option<String>,
+    pub cookie: Option<String>,
+}
+
+/// Header profiles read from the `header_profiles` YAML file.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct HeaderProfiles {
+    profiles: Vec<HeaderProfile>,
+    user_agent: Option<String>,
+}
+
+impl HeaderProfile {
+    fn matches(&self, host: &str) -> bool {
+        let pattern = self.host.trim().to_lowercase();
+        if pattern == "*" {
+            return true;
+        }
+        let host = host.to_lowercase();
+        match pattern.strip_prefix("*.") {
+            Some(domain) => host == domain || host.ends_with(&format!(".{domain}")),
+            None => host == pattern,
+        }
+    }
+
+    fn get_values(&self) -> [(HeaderName, Option<&String>); 4] {
+        [
+            (USER_AGENT, self.user_agent.as_ref()),
+            (REFERER, self.referer.as_ref()),
+            (AUTHORIZATION, self.authorization.as_ref()),
+            (COOKIE, self.cookie.as_ref()),
+        ]
+    }
+}
+
+impl HeaderProfiles {
+    pub(crate) fn new(profiles: Vec<HeaderProfile>, user_agent: Option<String>) -> Self {
+        Self {
+            profiles,
+            user_agent,
+        }
+    }
+
+    /// Read profiles from a YAML file with a list of [`HeaderProfile`].
+    pub(crate) fn read(path: &Path, user_agent: Option<String>) -> Result<Self, HeaderError> {
+        let file = File::open(path).map_err(|e| HeaderError::Io(path.to_path_buf(), e))?;
+        let reader = BufReader::new(file);
+        let profiles: Vec<HeaderProfile> = serde_yaml::from_reader(reader)
+            .map_err(|e| HeaderError::Deserialization(path.to_path_buf(), e))?;
+        let profiles = Self::new(profiles, user_agent);
+        profiles.validate()?;
+        Ok(profiles)
+    }
+
+    fn validate(&self) -> Result<(), HeaderError> {
+        for profile in &self.profiles {
+            for (name, value) in profile.get_values() {
+                let Some(value) = value else {
+                    continue;
+                };
+                if HeaderValue::from_str(value).is_err() {
+                    return Err(HeaderError::InvalidValue(
+                        profile.host.clone(),
+                        name.to_string(),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the headers for a URL.
+    ///
+    /// Each header is taken from the first matching profile that sets it so a
+    /// specific host can be listed before a `*` fallback.
+    pub(crate) fn get(&self, url: &Url) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let host = url.host_str().unwrap_or_default();
+        for profile in self.profiles.iter().filter(|profile| profile.matches(host)) {
+            for (name, value) in profile.get_values() {
+                if headers.contains_key(&name) {
+                    continue;
+                }
+                if let Some(value) = value.and_then(|value| HeaderValue::from_str(value).ok()) {
+                    headers.insert(name, value);
+                }
+            }
+        }
+        if !headers.contains_key(USER_AGENT) {
+            if let Some(value) = self
+                .user_agent
+                .as_ref()
+                .and_then(|value| HeaderValue::from_str(value).ok())
+            {
+                headers.insert(USER_AGENT, value);
+            }
+        }
+        headers
+    }
+}
+
+impl Debug for HeaderProfile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("HeaderProfile")
+            .field("host", &self.host)
+            .field("user_agent", &self.user_agent)
+            .field("referer", &self.referer)
+            .field(
+                "authorization",
+                &self.authorization.as_ref().map(|_| REDACTED),
+            )
+            .field("cookie", &self.cookie.as_ref().map(|_| REDACTED))
+            .finish()
+    }
+}
+
+#[allow(clippy::absolute_paths)]
+#[derive(Debug)]
+pub enum HeaderError {
+    Io(PathBuf, std::io::Error),
+    Deserialization(PathBuf, serde_yaml::Error),
+    Serialization(PathBuf, serde_yaml::Error),
+    InvalidValue(String, String),
+}
+
+impl Display for HeaderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let message = match self {
+            HeaderError::Io(path, e) => {
+                format!("An I/O error occurred.\nPath: {}\n{e}", path.display())
+            }
+            HeaderError::Deserialization(path, e) => {
+                format!(
+                    "A deserialization error occurred.\nPath: {}\n{e}",
+                    path.display()
+                )
+            }
+            HeaderError::Serialization(path, e) => {
+                format!(
+                    "A serialization error occurred.\nPath: {}\n{e}",
+                    path.display()
+                )
+            }
+            HeaderError::InvalidValue(host, name) => {
+                format!("Invalid {name} header value for host: {host}")
+            }
+        };
+        write!(f, "{message}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_first_matching() {
+        // Arrange
+        let profiles = HeaderProfiles::new(
+            vec![
+                HeaderProfile {
+                    host: "*.example.com".to_owned(),
+                    authorization: Some("Bearer secret".to_owned()),
+                    ..HeaderProfile::default()
+                },
+                HeaderProfile {
+                    host: "*".to_owned(),
+                    user_agent: Some("Mozilla/5.0".to_owned()),
+                    authorization: Some("Bearer fallback".to_owned()),
+                    ..HeaderProfile::default()
+                },
+            ],
+            Some("pura".to_owned()),
+        );
+        let feed = Url::parse("https://feeds.example.com/feed").expect("URL should parse");
+        let other = Url::parse("https://example.org/feed").expect("URL should parse");
+
+        // Act
+        let feed_headers = profiles.get(&feed);
+        let other_headers = profiles.get(&other);
+
+        // Assert
+        assert_eq!(
+            feed_headers
+                .get(AUTHORIZATION)
+                .and_then(|v| v.to_str().ok()),
+            Some("Bearer secret")
+        );
+        assert_eq!(
+            feed_headers.get(USER_AGENT).and_then(|v| v.to_str().ok()),
+            Some("Mozilla/5.0")
+        );
+        assert_eq!(
+            other_headers
+                .get(AUTHORIZATION)
+                .and_then(|v| v.to_str().ok()),
+            Some("Bearer fallback")
+        );
+        assert!(!format!("{profiles:?}").contains("secret"));
+    }
+
+    #[test]
+    fn matches() {
+        let profile = HeaderProfile {
+            host: "*.example.com".to_owned(),
+            ..HeaderProfile::default()
+        };
+        assert!(profile.matches("example.com"));
+        assert!(profile.matches("cdn.EXAMPLE.com"));
+        assert!(!profile.matches("badexample.com"));
+    }
+}
@@ -1,19 +1,180 @@
 use crate::prelude::*;
-use reqwest::header::CONTENT_TYPE;
-use reqwest::Response;
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, COOKIE, RANGE};
+use reqwest::{Method, Proxy, RequestBuilder, Response};
 use std::ffi::OsString;
-use tokio::fs::{read_to_string, remove_file};
+use std::time::Duration;
+use tokio::fs::{metadata, read_to_string, remove_file, rename, write, OpenOptions};
 use urlencoding::encode;
 
+/// Suffix of the file a download is streamed to before it is complete.
+pub(crate) const PARTIAL_SUFFIX: &str = ".part";
+/// Suffix of the lock file held while a cache entry is written.
+pub(crate) const LOCK_SUFFIX: &str = ".lock";
+
 /// A client for making HTTP requests and caching responses
 #[derive(Clone, Debug)]
 pub struct HttpClient {
     dir: PathBuf,
+    offline: bool,
+    backend: HttpBackend,
+    proxy: Option<Url>,
+    client: ReqwestClient,
+    watchdog: Option<IpWatchdog>,
+    headers: HeaderProfiles,
+    cookies: Option<CookieJar>,
+    credentials: Option<Credentials>,
+    recorder: Option<SnapshotRecorder>,
+    throttle: Throttle,
+    progress: Option<Progress>,
+    cancellation: Cancellation,
+    lock_timeout: Option<Duration>,
+    redirects: RedirectLog,
 }
 
 impl HttpClient {
     pub(crate) fn new(dir: PathBuf) -> Self {
-        Self { dir }
+        let redirects = RedirectLog::default();
+        Self {
+            dir,
+            offline: false,
+            backend: HttpBackend::default(),
+            proxy: None,
+            client: ReqwestClient::builder()
+                .redirect(redirects.policy())
+                .build()
+                .expect("client should build"),
+            watchdog: None,
+            headers: HeaderProfiles::default(),
+            cookies: None,
+            credentials: None,
+            recorder: None,
+            throttle: Throttle::default(),
+            progress: None,
+            cancellation: Cancellation::default(),
+            lock_timeout: None,
+            redirects,
+        }
+    }
+
+    /// Route every request through a proxy.
+    ///
+    /// Supports `http`, `https`, `socks5` and `socks5h` proxy URLs.
+    pub(crate) fn with_proxy(mut self, proxy: Option<Url>) -> Result<Self, HttpError> {
+        self.proxy = proxy;
+        self.client = self.build_client()?;
+        Ok(self)
+    }
+
+    /// Get a client for requests made for a podcast.
+    ///
+    /// The podcast proxy takes precedence over the global proxy. If the external IP
+    /// is checked it is validated through the podcast proxy, which gets its own watchdog
+    /// shared by every client of that proxy.
+    pub(crate) async fn for_podcast_proxy(&self, proxy: Option<&Url>) -> Result<Self, HttpError> {
+        let Some(proxy) = proxy else {
+            return Ok(self.clone());
+        };
+        let mut client = self.clone().with_proxy(Some(proxy.clone()))?;
+        if let Some(watchdog) = client.watchdog.take() {
+            let watchdog = watchdog
+                .for_proxy(proxy, client.clone())
+                .await
+                .map_err(|errors| HttpError::IpChanged(errors.log()))?;
+            client.watchdog = Some(watchdog);
+        }
+        Ok(client)
+    }
+
+    /// Set the source of responses on a cache miss.
+    pub(crate) fn with_backend(mut self, backend: HttpBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Add headers from the profiles matching each request host.
+    pub(crate) fn with_headers(mut self, headers: HeaderProfiles) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Send and store cookies using a cookie jar.
+    pub(crate) fn with_cookies(mut self, cookies: CookieJar) -> Self {
+        self.cookies = Some(cookies);
+        self
+    }
+
+    /// Authenticate requests for a private feed.
+    ///
+    /// Secrets are applied to requests but stripped from cache paths, logs and errors.
+    pub(crate) fn with_credentials(mut self, credentials: Option<Credentials>) -> Self {
+        self.credentials = credentials.filter(|credentials| !credentials.is_empty());
+        self
+    }
+
+    /// Refuse network requests once the IP watchdog trips.
+    pub(crate) fn with_watchdog(mut self, watchdog: IpWatchdog) -> Self {
+        self.watchdog = Some(watchdog);
+        self
+    }
+
+    /// Re-check the external IP if a watchdog is set.
+    ///
+    /// Call between batches of requests to catch a dropped VPN early.
+    pub(crate) async fn check_ip(&self) -> Result<(), HttpError> {
+        let Some(watchdog) = &self.watchdog else {
+            return Ok(());
+        };
+        watchdog.check().await.map_err(HttpError::IpChanged)
+    }
+
+    fn check_watchdog(&self) -> Result<(), HttpError> {
+        let Some(watchdog) = &self.watchdog else {
+            return Ok(());
+        };
+        watchdog.get_reason().map_err(HttpError::IpChanged)
+    }
+
+    /// Record every response served so the scrape can be stored as a snapshot.
+    pub(crate) fn with_recorder(mut self, recorder: SnapshotRecorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Limit the bandwidth and hours of downloads.
+    pub(crate) fn with_throttle(mut self, throttle: Throttle) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    /// Show the bytes of each download in flight as a bar of the progress.
+    pub(crate) fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Abort downloads in progress when a run is aborted.
+    pub(crate) fn with_cancellation(mut self, cancellation: Cancellation) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Set how long to wait for a cache entry being downloaded by another run.
+    ///
+    /// Without a timeout the wait is unlimited.
+    pub(crate) fn with_lock_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.lock_timeout = timeout;
+        self
+    }
+
+    /// Has the run been cancelled so no new work should be scheduled?
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    /// Only serve responses from the cache.
+    pub(crate) fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
     }
 
     pub(crate) async fn get_html(&self, url: &Url) -> Result<Html, HttpError> {
@@ -38,16 +199,22 @@ impl HttpClient {
     }
 
     pub(crate) async fn head(&self, url: &Url) -> Result<String, HttpError> {
+        let url = &self.strip(url);
         let path = self.get_cache_path(url, Some(HEAD_EXTENSION));
-        if path.exists() {
+        let content_type = if path.exists() {
             trace!("HEAD cache HIT: {url}");
             read_to_string(&path)
                 .await
-                .map_err(|e| HttpError::Io(path, e))
+                .map_err(|e| HttpError::Io(path.clone(), e))?
         } else {
             trace!("HEAD cache MISS: {url}");
-            self.head_to_cache(url, &path).await
-        }
+            if self.offline {
+                return Err(HttpError::Offline(url.clone()));
+            }
+            self.head_to_cache(url, &path).await?
+        };
+        self.record_snapshot(&path);
+        Ok(content_type)
     }
 
     pub(crate) async fn get(
@@ -55,14 +222,92 @@ impl HttpClient {
         url: &Url,
         extension: Option<&str>,
     ) -> Result<PathBuf, HttpError> {
+        let file = self.get_to_cache(url, extension, None).await?;
+        Ok(file.path)
+    }
+
+    /// Get a large file such as audio, only transferring while a download window is open.
+    ///
+    /// When a window closes the connection is dropped and the partial file kept so the
+    /// download resumes with a `Range` request once the next window opens.
+    ///
+    /// The progress bar is labelled with `name` so concurrent downloads can be told apart.
+    /// The redirects followed are returned if the file was fetched rather than cached.
+    pub(crate) async fn download(
+        &self,
+        url: &Url,
+        extension: Option<&str>,
+        name: &str,
+    ) -> Result<CachedFile, HttpError> {
+        self.get_to_cache(url, extension, Some(name)).await
+    }
+
+    /// Get a URL through the cache.
+    ///
+    /// A `name` marks a windowed download and labels its progress bar.
+    async fn get_to_cache(
+        &self,
+        url: &Url,
+        extension: Option<&str>,
+        name: Option<&str>,
+    ) -> Result<CachedFile, HttpError> {
+        let url = &self.strip(url);
         let path = self.get_cache_path(url, extension);
+        let mut redirects = None;
         if path.exists() {
             trace!("Cache HIT: {url}");
         } else {
             trace!("Cache MISS: {url}");
-            self.download_to_cache(url, &path).await?;
+            if self.offline {
+                return Err(HttpError::Offline(url.clone()));
+            }
+            let _lock = LockFile::acquire(get_sibling_path(&path, LOCK_SUFFIX), self.lock_timeout)
+                .await
+                .map_err(HttpError::Lock)?;
+            if path.exists() {
+                trace!("Cache HIT after waiting for lock: {url}");
+            } else {
+                redirects = self.download_to_cache(url, &path, name).await?;
+            }
         }
-        Ok(path)
+        self.record_snapshot(&path);
+        Ok(CachedFile { path, redirects })
+    }
+
+    /// Request a URL and read the body into memory without the cache.
+    ///
+    /// Use for responses that must always be fresh such as the external IP.
+    /// Fixtures are still replayed and recorded at the path the response would be cached.
+    pub(crate) async fn get_uncached(&self, url: &Url) -> Result<String, HttpError> {
+        if self.offline {
+            return Err(HttpError::Offline(url.clone()));
+        }
+        let path = self.get_cache_path(url, None);
+        let fixture = self.backend.get_fixture_path(&self.dir, &path);
+        if let (HttpBackend::Replay(_), Some(fixture)) = (&self.backend, &fixture) {
+            trace!("Replaying {url} from {}", fixture.display());
+            return read_to_string(fixture)
+                .await
+                .map_err(|_| HttpError::NoFixture(url.clone(), fixture.clone()));
+        }
+        trace!("GET {url} without cache");
+        let response = self.send(Method::GET, url).await?;
+        self.store_cookies(url, &response);
+        if !response.status().is_success() {
+            return Err(HttpError::Response(url.clone(), response.status().as_u16()));
+        }
+        let body = response
+            .text()
+            .await
+            .map_err(|e| HttpError::ResponseIo(url.clone(), e.without_url()))?;
+        if let Some(fixture) = fixture {
+            create_dir(&fixture).await?;
+            trace!("Recording {url} to {}", fixture.display());
+            write(&fixture, &body)
+                .await
+                .map_err(|e| HttpError::Io(fixture, e))?;
+        }
+        Ok(body)
     }
 
     pub(crate) async fn remove(&self, url: &Url, extension: Option<&str>) -> bool {
@@ -79,7 +324,8 @@ impl HttpClient {
         exists
     }
 
-    fn get_cache_path(&self, url: &Url, extension: Option<&str>) -> PathBuf {
+    pub(crate) fn get_cache_path(&self, url: &Url, extension: Option<&str>) -> PathBuf {
+        let url = &self.strip(url);
         let domain = url.domain().unwrap_or("__unknown");
         let mut segments: PathBuf = url
             .path_segments()
@@ -104,16 +350,27 @@ impl HttpClient {
         path
     }
 
-    #[allow(clippy::unused_self)]
     async fn head_to_cache(&self, url: &Url, path: &PathBuf) -> Result<String, HttpError> {
         create_dir(path).await?;
-        let client = ReqwestClient::new();
+        if let HttpBackend::Replay(_) = self.backend {
+            self.replay_to_cache(url, path).await?;
+            return read_to_string(path)
+                .await
+                .map_err(|e| HttpError::Io(path.clone(), e));
+        }
+        self.check_watchdog()?;
         trace!("HEAD {url} to {}", path.display());
-        let response = client
-            .head(url.as_str())
-            .send()
-            .await
-            .map_err(|e| HttpError::Request(url.clone(), e))?;
+        let mut response = self.send(Method::HEAD, url).await?;
+        self.store_cookies(url, &response);
+        if is_denied(response.status()) {
+            debug!("{} GET as HEAD was denied: {url}", "Trying".bold());
+            response = self.send(Method::GET, url).await?;
+            self.store_cookies(url, &response);
+        }
+        let status = response.status();
+        if is_denied(status) {
+            return Err(HttpError::Response(url.clone(), status.as_u16()));
+        }
         let content_type = get_content_type(response).unwrap_or_default();
         let mut file = AsyncFile::create(path)
             .await
@@ -121,36 +378,329 @@ impl HttpClient {
         file.write_all(content_type.as_bytes())
             .await
             .map_err(|e| HttpError::Io(path.clone(), e))?;
+        self.record(path).await?;
         Ok(content_type)
     }
 
-    #[allow(clippy::unused_self)]
-    async fn download_to_cache(&self, url: &Url, path: &PathBuf) -> Result<(), HttpError> {
+    /// Fetch a URL to the cache and get the redirects followed.
+    ///
+    /// The redirects are unknown if the response is replayed from a fixture.
+    async fn download_to_cache(
+        &self,
+        url: &Url,
+        path: &PathBuf,
+        name: Option<&str>,
+    ) -> Result<Option<Vec<Url>>, HttpError> {
         create_dir(path).await?;
-        let client = ReqwestClient::new();
-        trace!("Downloading {url} to {}", path.display());
-        let mut response = client
-            .get(url.as_str())
-            .send()
-            .await
-            .map_err(|e| HttpError::Request(url.clone(), e))?;
-        if !response.status().is_success() {
-            return Err(HttpError::Response(url.clone(), response.status().as_u16()));
+        if let HttpBackend::Replay(_) = self.backend {
+            self.replay_to_cache(url, path).await?;
+            return Ok(None);
         }
-        let mut file = AsyncFile::create(path)
+        let partial = get_sibling_path(path, PARTIAL_SUFFIX);
+        let result = tokio::select! {
+            result = self.stream_in_windows(url, &partial, name) => result,
+            () = self.cancellation.aborted() => Err(HttpError::Cancelled(url.clone())),
+        };
+        if result.is_err() && partial.exists() {
+            let _ = remove_file(&partial).await;
+        }
+        let redirects = result?;
+        rename(&partial, path)
             .await
             .map_err(|e| HttpError::Io(path.clone(), e))?;
+        self.record(path).await?;
+        Ok(Some(redirects))
+    }
+
+    /// Stream a response to a partial file, pausing while the download windows are closed.
+    ///
+    /// Returns the redirects followed by the request that completed the file.
+    async fn stream_in_windows(
+        &self,
+        url: &Url,
+        path: &PathBuf,
+        name: Option<&str>,
+    ) -> Result<Vec<Url>, HttpError> {
+        loop {
+            if name.is_some() {
+                self.throttle.wait_for_window().await;
+            }
+            match self.stream_to_cache(url, path, name).await? {
+                Streamed::Complete(redirects) => return Ok(redirects),
+                Streamed::Paused => {
+                    debug!("{} download as the window closed: {url}", "Pausing".bold());
+                }
+            }
+        }
+    }
+
+    /// Stream a response to a partial file in the cache.
+    ///
+    /// A partial file left by a paused download is resumed if the server supports
+    /// ranges. The file is incomplete if an error is returned.
+    async fn stream_to_cache(
+        &self,
+        url: &Url,
+        path: &PathBuf,
+        name: Option<&str>,
+    ) -> Result<Streamed, HttpError> {
+        self.check_watchdog()?;
+        let offset = metadata(path).await.map_or(0, |metadata| metadata.len());
+        trace!("Downloading {url} to {} from {offset}", path.display());
+        let (mut response, redirects) = self.send_from(Method::GET, url, offset).await?;
+        self.store_cookies(url, &response);
+        let status = response.status();
+        if offset > 0 && status == StatusCode::RANGE_NOT_SATISFIABLE {
+            return Ok(Streamed::Complete(redirects));
+        }
+        if !status.is_success() {
+            return Err(HttpError::Response(url.clone(), status.as_u16()));
+        }
+        let (file, offset) = if status == StatusCode::PARTIAL_CONTENT {
+            let file = OpenOptions::new().append(true).open(path).await;
+            (file, offset)
+        } else {
+            (AsyncFile::create(path).await, 0)
+        };
+        let mut file = file.map_err(|e| HttpError::Io(path.clone(), e))?;
+        let length = response.content_length().map(|length| length + offset);
+        let mut progress = self.progress.as_ref().map(|progress| {
+            let name = name.map_or_else(|| get_name(url), ToOwned::to_owned);
+            let mut download = progress.add_download(&name, length);
+            download.update(usize::try_from(offset).unwrap_or(usize::MAX));
+            download
+        });
         while let Some(chunk) = response
             .chunk()
             .await
-            .map_err(|e| HttpError::ResponseIo(url.clone(), e))?
+            .map_err(|e| HttpError::ResponseIo(url.clone(), e.without_url()))?
         {
+            if name.is_some() && !self.throttle.is_open() {
+                file.flush()
+                    .await
+                    .map_err(|e| HttpError::Io(path.clone(), e))?;
+                return Ok(Streamed::Paused);
+            }
+            self.throttle.consume(url, chunk.len()).await;
+            self.check_watchdog()?;
             file.write_all(&chunk)
                 .await
                 .map_err(|e| HttpError::Io(path.clone(), e))?;
+            if let Some(progress) = &mut progress {
+                progress.update(chunk.len());
+            }
         }
+        file.flush()
+            .await
+            .map_err(|e| HttpError::Io(path.clone(), e))?;
+        Ok(Streamed::Complete(redirects))
+    }
+
+    /// Send a request, retrying with Basic auth if another host asks for the feed credentials.
+    async fn send(&self, method: Method, url: &Url) -> Result<Response, HttpError> {
+        let (response, _) = self.send_from(method, url, 0).await?;
+        Ok(response)
+    }
+
+    /// Send a request for the bytes from an offset and get the redirects followed.
+    async fn send_from(
+        &self,
+        method: Method,
+        url: &Url,
+        offset: u64,
+    ) -> Result<(Response, Vec<Url>), HttpError> {
+        let with_range = |request: RequestBuilder| {
+            if offset > 0 {
+                request.header(RANGE, format!("bytes={offset}-"))
+            } else {
+                request
+            }
+        };
+        let response = with_range(self.request(method.clone(), url)).send().await;
+        let mut redirects = self.take_redirects(url);
+        let response = response.map_err(|e| HttpError::Request(url.clone(), e.without_url()))?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok((response, redirects));
+        }
+        let final_url = response.url().clone();
+        let challenge = self
+            .credentials
+            .as_ref()
+            .and_then(|credentials| credentials.get_challenge_auth(&final_url));
+        let Some((username, password)) = challenge else {
+            return Ok((response, redirects));
+        };
+        trace!(
+            "{} Basic auth to {}",
+            "Sending".bold(),
+            final_url.host_str().unwrap_or_default()
+        );
+        let response = with_range(self.request(method, &final_url))
+            .basic_auth(username, password)
+            .send()
+            .await;
+        redirects.extend(self.take_redirects(&final_url));
+        let response = response.map_err(|e| HttpError::Request(url.clone(), e.without_url()))?;
+        Ok((response, redirects))
+    }
+
+    /// Take the redirects followed by the last request for a URL with credential
+    /// secrets stripped.
+    fn take_redirects(&self, url: &Url) -> Vec<Url> {
+        let request_url = match &self.credentials {
+            Some(credentials) => credentials.apply(url),
+            None => url.clone(),
+        };
+        self.redirects
+            .take(&request_url)
+            .iter()
+            .map(|url| self.strip(url))
+            .collect()
+    }
+
+    fn request(&self, method: Method, url: &Url) -> RequestBuilder {
+        let request_url = match &self.credentials {
+            Some(credentials) => credentials.apply(url),
+            None => url.clone(),
+        };
+        let mut request = self
+            .client
+            .request(method, request_url.as_str())
+            .headers(self.get_headers(url));
+        let basic_auth = self
+            .credentials
+            .as_ref()
+            .and_then(|credentials| credentials.get_basic_auth(url));
+        if let Some((username, password)) = basic_auth {
+            request = request.basic_auth(username, password);
+        }
+        request
+    }
+
+    /// Get the URL with credential secrets stripped for cache paths and logs.
+    fn strip(&self, url: &Url) -> Url {
+        match &self.credentials {
+            Some(credentials) => credentials.strip(url),
+            None => url.clone(),
+        }
+    }
+
+    /// Get the profile headers for a URL with any cookies from the jar appended.
+    fn get_headers(&self, url: &Url) -> HeaderMap {
+        let mut headers = self.headers.get(url);
+        let jar = self.cookies.as_ref().and_then(|cookies| cookies.get(url));
+        if let Some(jar) = jar {
+            let cookie = match headers.get(COOKIE).and_then(|value| value.to_str().ok()) {
+                Some(profile) => format!("{profile}; {jar}"),
+                None => jar,
+            };
+            if let Ok(value) = HeaderValue::from_str(&cookie) {
+                headers.insert(COOKIE, value);
+            }
+        }
+        headers
+    }
+
+    fn store_cookies(&self, url: &Url, response: &Response) {
+        if let Some(cookies) = &self.cookies {
+            cookies.store(url, response.headers());
+        }
+    }
+
+    fn build_client(&self) -> Result<ReqwestClient, HttpError> {
+        let mut builder = ReqwestClient::builder().redirect(self.redirects.policy());
+        if let Some(proxy) = &self.proxy {
+            trace!(
+                "{} proxy: {}://{}",
+                "Using".bold(),
+                proxy.scheme(),
+                proxy.host_str().unwrap_or_default()
+            );
+            let proxy = Proxy::all(proxy.as_str()).map_err(HttpError::Client)?;
+            builder = builder.proxy(proxy);
+        }
+        builder.build().map_err(HttpError::Client)
+    }
+
+    /// Copy a recorded fixture into the cache.
+    async fn replay_to_cache(&self, url: &Url, path: &Path) -> Result<(), HttpError> {
+        let fixture = self
+            .backend
+            .get_fixture_path(&self.dir, path)
+            .ok_or_else(|| HttpError::NoFixture(url.clone(), path.to_path_buf()))?;
+        if !fixture.is_file() {
+            return Err(HttpError::NoFixture(url.clone(), fixture));
+        }
+        trace!("Replaying {url} from {}", fixture.display());
+        copy(&fixture, path)
+            .await
+            .map_err(|e| HttpError::Io(fixture, e))?;
         Ok(())
     }
+
+    fn record_snapshot(&self, path: &Path) {
+        if let Some(recorder) = &self.recorder {
+            recorder.record(&self.dir, path);
+        }
+    }
+
+    /// Copy a cached response to the fixture directory if recording.
+    async fn record(&self, path: &Path) -> Result<(), HttpError> {
+        let HttpBackend::Record(_) = self.backend else {
+            return Ok(());
+        };
+        let Some(fixture) = self.backend.get_fixture_path(&self.dir, path) else {
+            return Ok(());
+        };
+        create_dir(&fixture).await?;
+        trace!("Recording {} to {}", path.display(), fixture.display());
+        copy(path, &fixture)
+            .await
+            .map_err(|e| HttpError::Io(fixture, e))?;
+        Ok(())
+    }
+}
+
+/// Outcome of streaming a response to a partial file
+enum Streamed {
+    /// The file is complete, with the redirects followed by the request
+    Complete(Vec<Url>),
+    /// The download window closed so the rest of the file is still to be fetched
+    Paused,
+}
+
+/// A file fetched through the cache
+#[derive(Debug)]
+pub(crate) struct CachedFile {
+    /// Path of the file in the cache
+    pub(crate) path: PathBuf,
+    /// Redirects followed to fetch the file, the last being the URL it was fetched from
+    ///
+    /// Empty if the request was not redirected and `None` if the file was already
+    /// cached or was replayed from a fixture.
+    pub(crate) redirects: Option<Vec<Url>>,
+}
+
+/// Get a path next to a cache entry with a suffix added to the file name.
+fn get_sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// Get the last segment of a URL path to name a download.
+fn get_name(url: &Url) -> String {
+    url.path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|segment| !segment.is_empty())
+        .or_else(|| url.host_str())
+        .unwrap_or_default()
+        .to_owned()
+}
+
+/// Is the status a refusal that may only apply to the method?
+fn is_denied(status: StatusCode) -> bool {
+    status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN
 }
 
 async fn create_dir(path: &Path) -> Result<(), HttpError> {
@@ -189,6 +739,12 @@ pub enum HttpError {
     ResponseIo(Url, reqwest::Error),
     InvalidJson(PathBuf, serde_json::Error),
     NoContentType(Url),
+    Offline(Url),
+    NoFixture(Url, PathBuf),
+    Client(reqwest::Error),
+    IpChanged(String),
+    Cancelled(Url),
+    Lock(LockError),
 }
 
 impl Display for HttpError {
@@ -213,10 +769,25 @@ impl Display for HttpError {
                 )
             }
             HttpError::ResponseIo(url, e) => {
-                format!("A response I/O error occurred.\nURL: {url}\n{e}",)
+                format!("A response I/O error occurred.\nURL: {url}\n{e}")
             }
             HttpError::NoContentType(url) => {
-                format!("Response did not contain a Content-Type header:\nURL: {url}",)
+                format!("Response did not contain a Content-Type header:\nURL: {url}")
+            }
+            HttpError::NoFixture(url, path) => {
+                format!(
+                    "Response has not been recorded as a fixture\nURL: {url}\nPath: {}",
+                    path.display()
+                )
+            }
+            HttpError::Client(e) => format!("Unable to create HTTP client\n{e}"),
+            HttpError::IpChanged(reason) => {
+                format!("Request aborted as the external IP check failed{reason}")
+            }
+            HttpError::Cancelled(url) => format!("Download was cancelled\nURL: {url}"),
+            HttpError::Lock(e) => format!("Unable to lock cache entry\n{e}"),
+            HttpError::Offline(url) => {
+                format!("Response is not cached and the network is disabled in offline mode\nURL: {url}")
             }
         };
         write!(f, "{message}")
@@ -225,9 +796,7 @@ impl Display for HttpError {
 
 impl Default for HttpClient {
     fn default() -> Self {
-        Self {
-            dir: PathProvider::default().get_http_dir(),
-        }
+        Self::new(PathProvider::default().get_http_dir())
     }
 }
 
@@ -235,15 +804,21 @@ impl Default for HttpClient {
 mod tests {
     use super::super::*;
     use super::*;
+    use chrono::NaiveTime;
     use serde_json::Value;
+    use std::fs::read;
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+    use tokio::fs::write;
+    use tokio::time::sleep;
 
     #[tokio::test]
     pub async fn head() {
         // Arrange
         let _ = init_logging();
-        let http = HttpClient::default();
-        let url = Url::parse("https://example.com/?abc=123&def=456").expect("url should be valid");
-        http.remove(&url, Some(HEAD_EXTENSION)).await;
+        let base = TestServer::start("text/html", b"<html></html>".to_vec()).await;
+        let http = HttpClient::new(TempDirectory::create("pura-http"));
+        let url = base.join("?abc=123&def=456").expect("url should be valid");
 
         // Act
         let result = http.head(&url).await;
@@ -254,13 +829,12 @@ mod tests {
     }
 
     #[tokio::test]
-    #[ignore = "uses simplecast.com"]
     pub async fn head_xml() {
         // Arrange
         let _ = init_logging();
-        let http = HttpClient::default();
-        let url = Url::parse("https://feeds.simplecast.com/lP7owBq8").expect("url should be valid");
-        http.remove(&url, Some(HEAD_EXTENSION)).await;
+        let http = HttpClient::new(TempDirectory::create("pura-http"))
+            .with_backend(HttpBackend::Replay(get_fixtures_dir()));
+        let url = Url::parse(SIMPLECAST_FIXTURE_FEED_URL).expect("url should be valid");
 
         // Act
         let result = http.head(&url).await;
@@ -274,10 +848,10 @@ mod tests {
     pub async fn get() {
         // Arrange
         let _ = init_logging();
-        let http = HttpClient::default();
-        let url = Url::parse("https://example.com/?abc=123&def=456").expect("url should be valid");
+        let base = TestServer::start("text/html", b"<html></html>".to_vec()).await;
+        let http = HttpClient::new(TempDirectory::create("pura-http"));
+        let url = base.join("?abc=123&def=456").expect("url should be valid");
         let expected = http.get_cache_path(&url, Some(HTML_EXTENSION));
-        http.remove(&url, Some(HTML_EXTENSION)).await;
 
         // Act
         let result = http.get(&url, Some(HTML_EXTENSION)).await;
@@ -288,13 +862,68 @@ mod tests {
         assert!(path.exists());
     }
 
+    #[tokio::test]
+    pub async fn download_resumes_when_window_reopens() {
+        // Arrange
+        let _ = init_logging();
+        let body: Vec<u8> = (0..=u8::MAX).cycle().take(1024).collect();
+        let (base, requests) =
+            TestServer::start_with_pause("audio/mpeg", body.clone(), Duration::from_millis(500))
+                .await;
+        let url = base.join("episode.mp3").expect("url should be valid");
+        let feed = base.join("feed.rss").expect("url should be valid");
+        let open = NaiveTime::from_hms_opt(0, 30, 0).expect("time should be valid");
+        let closed = NaiveTime::from_hms_opt(2, 0, 0).expect("time should be valid");
+        let time = Arc::new(Mutex::new(open));
+        let windows = DownloadWindow::parse_all("00:00-01:00").assert_ok_debug();
+        let throttle = Throttle::new(None, None, windows).with_fixed_clock(time.clone());
+        let http =
+            HttpClient::new(TempDirectory::create("pura-http-window")).with_throttle(throttle);
+        let path = http.get_cache_path(&url, Some(MP3_EXTENSION));
+        let partial = get_sibling_path(&path, PARTIAL_SUFFIX);
+
+        // Act
+        let download = tokio::spawn({
+            let http = http.clone();
+            let url = url.clone();
+            async move { http.download(&url, Some(MP3_EXTENSION), "episode").await }
+        });
+        for _ in 0..100 {
+            if metadata(&partial)
+                .await
+                .is_ok_and(|metadata| metadata.len() > 0)
+            {
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        *time.lock().expect("lock should not be poisoned") = closed;
+        let result = download.await.expect("download should not panic");
+        *time.lock().expect("lock should not be poisoned") = closed;
+        let uncapped = http.get(&feed, Some(RSS_EXTENSION)).await;
+
+        // Assert
+        let file = result.assert_ok();
+        assert_eq!(file.path, path);
+        assert_eq!(file.redirects, Some(Vec::new()));
+        assert_eq!(read(&path).assert_ok_debug(), body);
+        uncapped.assert_ok();
+        let requests = requests
+            .lock()
+            .expect("lock should not be poisoned")
+            .clone();
+        assert_eq!(requests.len(), 3);
+        let resumed = requests.get(1).expect("should resume").to_lowercase();
+        assert!(resumed.contains("range: bytes="));
+    }
+
     #[tokio::test]
     pub async fn get_html() {
         // Arrange
         let _ = init_logging();
-        let http = HttpClient::default();
-        let url = Url::parse("https://example.com").expect("url should be valid");
-        http.remove(&url, Some(HTML_EXTENSION)).await;
+        let body = b"<html><head><title>Example</title></head></html>".to_vec();
+        let url = TestServer::start("text/html", body).await;
+        let http = HttpClient::new(TempDirectory::create("pura-http"));
 
         // Act
         let result = http.get_html(&url).await;
@@ -304,18 +933,422 @@ mod tests {
     }
 
     #[tokio::test]
-    #[ignore = "uses ipinfo.io"]
+    pub async fn get_offline() {
+        // Arrange
+        let _ = init_logging();
+        let http = HttpClient::new(TempDirectory::create("pura-http")).with_offline(true);
+        let url = Url::parse("https://example.com/feed").expect("url should be valid");
+        let path = http.get_cache_path(&url, Some(RSS_EXTENSION));
+
+        // Act
+        let miss = http.get(&url, Some(RSS_EXTENSION)).await;
+        create_dir(&path).await.assert_ok();
+        write(&path, "<rss></rss>")
+            .await
+            .expect("should write cache");
+        let hit = http.get(&url, Some(RSS_EXTENSION)).await;
+
+        // Assert
+        assert!(matches!(miss, Err(HttpError::Offline(_))));
+        assert_eq!(hit.assert_ok(), path);
+    }
+
+    #[tokio::test]
+    pub async fn get_after_watchdog_trips() {
+        // Arrange
+        let _ = init_logging();
+        let base = TestServer::start("audio/mpeg", vec![0; 16]).await;
+        let watchdog = IpWatchdog::new(IpInfoProvider::default(), Duration::ZERO);
+        let http =
+            HttpClient::new(TempDirectory::create("pura-http")).with_watchdog(watchdog.clone());
+        let before = base.join("before.mp3").expect("url should be valid");
+        let after = base.join("after.mp3").expect("url should be valid");
+
+        // Act
+        let allowed = http.get(&before, None).await;
+        watchdog.trip("\nIP address did not match".to_owned());
+        let aborted = http.get(&after, None).await;
+        let cached = http.get(&before, None).await;
+
+        // Assert
+        allowed.assert_ok();
+        assert!(matches!(aborted, Err(HttpError::IpChanged(_))));
+        assert!(matches!(
+            http.check_ip().await,
+            Err(HttpError::IpChanged(_))
+        ));
+        cached.assert_ok();
+    }
+
+    #[tokio::test]
+    pub async fn for_podcast_proxy_validates_ip() {
+        // Arrange
+        let _ = init_logging();
+        let options = AppOptions {
+            expect_ip: Some("203.0.113.1".to_owned()),
+            ip_providers: Some("text".to_owned()),
+            ip_text_url: Some(Url::parse("http://ip.invalid/").expect("url should be valid")),
+            ..AppOptions::default()
+        };
+        let (matching, requests) =
+            TestServer::start_with_headers("text/plain", b"203.0.113.1".to_vec(), "").await;
+        let changed = TestServer::start("text/plain", b"198.51.100.1".to_vec()).await;
+        let ip = IpInfoProvider::new(options, HttpClient::new(TempDirectory::create("pura-http")));
+        let http = HttpClient::new(TempDirectory::create("pura-http"))
+            .with_watchdog(IpWatchdog::new(ip, Duration::ZERO));
+
+        // Act
+        let proxied = http.for_podcast_proxy(Some(&matching)).await;
+        let again = http.for_podcast_proxy(Some(&matching)).await;
+        let mismatched = http.for_podcast_proxy(Some(&changed)).await;
+
+        // Assert
+        let proxied = proxied.assert_ok_debug();
+        assert!(proxied.watchdog.is_some());
+        again.assert_ok_debug();
+        let requests = requests.lock().expect("lock should not be poisoned");
+        let checks = requests
+            .iter()
+            .filter(|request| request.starts_with("GET http://ip.invalid/"))
+            .count();
+        assert_eq!(checks, 1);
+        assert!(matches!(mismatched, Err(HttpError::IpChanged(_))));
+    }
+
+    #[tokio::test]
+    pub async fn head_falls_back_to_get() {
+        // Arrange
+        let _ = init_logging();
+        let (url, requests) =
+            TestServer::start_with_status("audio/mpeg", vec![0; 16], "", |request| {
+                if request.starts_with("HEAD") {
+                    403
+                } else {
+                    200
+                }
+            })
+            .await;
+        let http = HttpClient::new(TempDirectory::create("pura-http"));
+
+        // Act
+        let result = http.head(&url).await;
+
+        // Assert
+        assert_eq!(result.assert_ok(), "audio/mpeg");
+        assert_eq!(
+            requests.lock().expect("lock should not be poisoned").len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    pub async fn get_with_challenged_credentials() {
+        // Arrange
+        let _ = init_logging();
+        let (base, requests) =
+            TestServer::start_with_status("audio/mpeg", vec![0; 16], "", |request| {
+                if request.to_lowercase().contains("authorization: basic") {
+                    200
+                } else {
+                    401
+                }
+            })
+            .await;
+        let credentials = Credentials {
+            host: Some("feeds.example.com".to_owned()),
+            username: Some("user".to_owned()),
+            password: Some("pass".to_owned()),
+            auth_hosts: base.host_str().map(ToOwned::to_owned).into_iter().collect(),
+            ..Credentials::default()
+        };
+        let http =
+            HttpClient::new(TempDirectory::create("pura-http")).with_credentials(Some(credentials));
+        let url = base.join("episode.mp3").expect("url should be valid");
+
+        // Act
+        let result = http.get(&url, None).await;
+
+        // Assert
+        result.assert_ok();
+        let requests = requests.lock().expect("lock should not be poisoned");
+        assert_eq!(requests.len(), 2);
+        assert!(!requests
+            .first()
+            .expect("should be a request")
+            .to_lowercase()
+            .contains("authorization"));
+    }
+
+    #[tokio::test]
+    pub async fn get_redirected_to_challenging_host() {
+        // Arrange
+        let _ = init_logging();
+        let (foreign, foreign_requests) =
+            TestServer::start_with_status("audio/mpeg", vec![0; 16], "", |_| 401).await;
+        let location = foreign.join("episode.mp3").expect("url should be valid");
+        let (mut base, _) = TestServer::start_with_status(
+            "audio/mpeg",
+            Vec::new(),
+            &format!("Location: {location}\r\n"),
+            |_| 302,
+        )
+        .await;
+        base.set_host(Some("localhost"))
+            .expect("host should be valid");
+        let credentials = Credentials {
+            host: Some("localhost".to_owned()),
+            username: Some("user".to_owned()),
+            password: Some("pass".to_owned()),
+            ..Credentials::default()
+        };
+        let http =
+            HttpClient::new(TempDirectory::create("pura-http")).with_credentials(Some(credentials));
+        let url = base.join("episode.mp3").expect("url should be valid");
+
+        // Act
+        let result = http.get(&url, None).await;
+
+        // Assert
+        assert!(matches!(result, Err(HttpError::Response(_, 401))));
+        let requests = foreign_requests
+            .lock()
+            .expect("lock should not be poisoned");
+        assert_eq!(requests.len(), 1);
+        assert!(requests
+            .iter()
+            .all(|request| !request.to_lowercase().contains("authorization")));
+    }
+
+    #[tokio::test]
+    pub async fn download_records_redirects() {
+        // Arrange
+        let _ = init_logging();
+        let body = vec![1; 16];
+        let origin = TestServer::start("audio/mpeg", body.clone()).await;
+        let origin = origin.join("episode.mp3").expect("url should be valid");
+        let redirect = |location: Url| async move {
+            let headers = format!("Location: {location}\r\n");
+            let (base, _) =
+                TestServer::start_with_status("audio/mpeg", Vec::new(), &headers, |_| 302).await;
+            base.join("episode.mp3").expect("url should be valid")
+        };
+        let middle = redirect(origin.clone()).await;
+        let url = redirect(middle.clone()).await;
+        let http = HttpClient::new(TempDirectory::create("pura-http-redirects"));
+
+        // Act
+        let fetched = http.download(&url, Some(MP3_EXTENSION), "episode").await;
+        let cached = http.download(&url, Some(MP3_EXTENSION), "episode").await;
+
+        // Assert
+        let fetched = fetched.assert_ok();
+        assert_eq!(fetched.redirects, Some(vec![middle, origin]));
+        assert_eq!(read(&fetched.path).assert_ok_debug(), body);
+        assert_eq!(cached.assert_ok().redirects, None);
+    }
+
+    #[tokio::test]
+    pub async fn record_then_replay() {
+        // Arrange
+        let _ = init_logging();
+        let fixtures = TempDirectory::create("pura-fixtures");
+        let base = TestServer::start("application/xml", b"<rss></rss>".to_vec()).await;
+        let url = base.join("feed").expect("url should be valid");
+        let record = HttpClient::new(TempDirectory::create("pura-http"))
+            .with_backend(HttpBackend::Record(fixtures.clone()));
+        let replay = HttpClient::new(TempDirectory::create("pura-http"))
+            .with_backend(HttpBackend::Replay(fixtures));
+
+        // Act
+        let recorded_head = record.head(&url).await.assert_ok();
+        record.get(&url, Some(RSS_EXTENSION)).await.assert_ok();
+        let recorded_uncached = record.get_uncached(&url).await.assert_ok();
+        let replayed_head = replay.head(&url).await;
+        let replayed = replay.get(&url, Some(RSS_EXTENSION)).await;
+        let replayed_uncached = replay.get_uncached(&url).await;
+        let missing = replay.get(&base, Some(HTML_EXTENSION)).await;
+
+        // Assert
+        assert_eq!(recorded_head, "application/xml");
+        assert_eq!(replayed_head.assert_ok(), recorded_head);
+        let contents = read_to_string(replayed.assert_ok())
+            .await
+            .expect("should read");
+        assert_eq!(contents, "<rss></rss>");
+        assert_eq!(replayed_uncached.assert_ok(), recorded_uncached);
+        assert!(matches!(missing, Err(HttpError::NoFixture(_, _))));
+    }
+
+    #[tokio::test]
+    pub async fn get_with_proxy() {
+        // Arrange
+        let _ = init_logging();
+        let proxy = TestServer::start("text/html", b"proxied".to_vec()).await;
+        let http = HttpClient::new(TempDirectory::create("pura-http"))
+            .with_proxy(Some(proxy))
+            .assert_ok();
+        let url = Url::parse("http://pura.invalid/page").expect("url should be valid");
+
+        // Act
+        let result = http.get(&url, Some(HTML_EXTENSION)).await;
+
+        // Assert
+        let contents = read_to_string(result.assert_ok())
+            .await
+            .expect("should read");
+        assert_eq!(contents, "proxied");
+    }
+
+    #[tokio::test]
+    pub async fn get_with_headers_and_cookies() {
+        // Arrange
+        let _ = init_logging();
+        let (base, requests) = TestServer::start_with_headers(
+            "text/html",
+            b"ok".to_vec(),
+            "Set-Cookie: session=abc; Path=/\r\n",
+        )
+        .await;
+        let headers = HeaderProfiles::new(
+            vec![HeaderProfile {
+                host: "127.0.0.1".to_owned(),
+                authorization: Some("Bearer token".to_owned()),
+                cookie: Some("consent=yes".to_owned()),
+                ..HeaderProfile::default()
+            }],
+            Some("pura-test".to_owned()),
+        );
+        let http = HttpClient::new(TempDirectory::create("pura-http"))
+            .with_headers(headers)
+            .with_cookies(CookieJar::default());
+        let first = base.join("first").expect("url should be valid");
+        let second = base.join("second").expect("url should be valid");
+
+        // Act
+        http.get(&first, Some(HTML_EXTENSION)).await.assert_ok();
+        http.get(&second, Some(HTML_EXTENSION)).await.assert_ok();
+
+        // Assert
+        let requests = requests
+            .lock()
+            .expect("lock should not be poisoned")
+            .clone();
+        let first = requests
+            .first()
+            .expect("should receive first request")
+            .to_lowercase();
+        let second = requests
+            .get(1)
+            .expect("should receive second request")
+            .to_lowercase();
+        assert!(first.contains("authorization: bearer token"));
+        assert!(first.contains("user-agent: pura-test"));
+        assert!(first.contains("cookie: consent=yes\r\n"));
+        assert!(second.contains("cookie: consent=yes; session=abc"));
+    }
+
+    #[tokio::test]
+    pub async fn get_with_credentials() {
+        // Arrange
+        let _ = init_logging();
+        let (base, requests) =
+            TestServer::start_with_headers("application/xml", b"<rss></rss>".to_vec(), "").await;
+        let credentials = Credentials {
+            host: base.host_str().map(ToOwned::to_owned),
+            username: Some("user".to_owned()),
+            password: Some("pass".to_owned()),
+            token: Some("s3cr3t".to_owned()),
+            ..Credentials::default()
+        };
+        let http =
+            HttpClient::new(TempDirectory::create("pura-http")).with_credentials(Some(credentials));
+        let url = base.join("feed?auth=s3cr3t").expect("url should be valid");
+
+        // Act
+        let path = http.get(&url, Some(RSS_EXTENSION)).await.assert_ok();
+
+        // Assert
+        assert!(!path.to_string_lossy().contains("s3cr3t"));
+        let requests = requests
+            .lock()
+            .expect("lock should not be poisoned")
+            .clone();
+        let request = requests
+            .first()
+            .expect("should receive request")
+            .to_lowercase();
+        assert!(request.starts_with("get /feed?auth=s3cr3t "));
+        assert!(request.contains("authorization: basic dxnlcjpwyxnz"));
+    }
+
+    #[tokio::test]
+    pub async fn get_with_rate_limits() {
+        // Arrange
+        let _ = init_logging();
+        let base = TestServer::start("audio/mpeg", vec![0; 50_000]).await;
+        let global = Throttle::new(Some(100_000), None, Vec::new());
+        let host = Throttle::new(None, Some(100_000), Vec::new());
+        let dir = TempDirectory::create("pura-http-throttle");
+        let url = |name: &str| base.join(name).expect("url should be valid");
+
+        // Act
+        let start = Instant::now();
+        HttpClient::new(dir.clone())
+            .with_throttle(global)
+            .get(&url("global.mp3"), None)
+            .await
+            .assert_ok();
+        let global_elapsed = start.elapsed();
+        let start = Instant::now();
+        HttpClient::new(dir)
+            .with_throttle(host)
+            .get(&url("host.mp3"), None)
+            .await
+            .assert_ok();
+        let host_elapsed = start.elapsed();
+
+        // Assert
+        assert!(global_elapsed >= Duration::from_millis(400));
+        assert!(host_elapsed >= Duration::from_millis(400));
+    }
+
+    #[tokio::test]
+    pub async fn get_aborted() {
+        // Arrange
+        let _ = init_logging();
+        let base = TestServer::start("audio/mpeg", vec![0; 50_000]).await;
+        let cancellation = Cancellation::default();
+        let http = HttpClient::new(TempDirectory::create("pura-http-aborted"))
+            .with_throttle(Throttle::new(Some(10_000), None, Vec::new()))
+            .with_cancellation(cancellation.clone());
+        let url = base.join("aborted.mp3").expect("url should be valid");
+        let path = http.get_cache_path(&url, None);
+        tokio::spawn(async move {
+            sleep(Duration::from_millis(200)).await;
+            cancellation.abort();
+        });
+
+        // Act
+        let result = http.get(&url, None).await;
+
+        // Assert
+        assert!(matches!(result, Err(HttpError::Cancelled(_))));
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
     pub async fn get_json() {
         // Arrange
         let _ = init_logging();
-        let http = HttpClient::default();
+        let http = HttpClient::new(TempDirectory::create("pura-http"))
+            .with_backend(HttpBackend::Replay(get_fixtures_dir()));
         let url = Url::parse("https://ipinfo.io").expect("url should be valid");
-        http.remove(&url, Some(JSON_EXTENSION)).await;
 
         // Act
         let result = http.get_json::<Value>(&url).await;
 
         // Assert
-        let _json = result.assert_ok();
+        let json = result.assert_ok();
+        assert_eq!(json.get("ip").and_then(Value::as_str), Some("198.51.100.7"));
     }
 }
@@ -1,6 +1,6 @@
 use crate::prelude::*;
 
-#[derive(Default)]
+#[derive(Debug, Default)]
 pub(crate) struct IpInfoProvider {
     options: AppOptions,
     http: HttpClient,
@@ -11,27 +11,61 @@ impl IpInfoProvider {
         Self { options, http }
     }
 
-    async fn get(&self) -> Result<IpInfo, HttpError> {
-        let ip_url = Url::parse("https://ipinfo.io").expect("URL should be valid");
-        self.http.remove(&ip_url, Some(JSON_EXTENSION)).await;
-        self.http.get_json(&ip_url).await
+    /// Create a provider with the same expectations that requests through another client.
+    pub(crate) fn for_client(&self, http: HttpClient) -> Self {
+        Self::new(self.options.clone(), http)
+    }
+
+    /// Get the external IP from the first source that succeeds.
+    async fn get(&self, sources: &[IpSource]) -> Result<IpAddressInfo, Vec<ValidationError>> {
+        let needs_country = self.options.expect_country.is_some();
+        let mut errors = Vec::new();
+        for source in sources {
+            let name = source.get_name();
+            trace!("{} external IP from {name}", "Checking".bold());
+            let error = match source.get(&self.http).await {
+                Ok(info) if needs_country && info.country.is_none() => {
+                    IpSourceError::MissingCountry
+                }
+                Ok(info) => {
+                    debug!("{} external IP from {name}: {info}", "Found".bold());
+                    return Ok(info);
+                }
+                Err(e) => e,
+            };
+            debug!(
+                "{} to get external IP from {name}\n{error}",
+                "Failed".bold()
+            );
+            errors.push(ValidationError::IpSource(name.to_owned(), error));
+        }
+        Err(errors)
     }
 
     pub(crate) async fn validate(&self) -> Result<(), Vec<ValidationError>> {
         if self.options.expect_ip.is_none() && self.options.expect_country.is_none() {
             return Ok(());
         }
-        let info = self
-            .get()
-            .await
-            .map_err(|e| vec![ValidationError::Http(e)])?;
+        let info = self.get_info().await?;
+        self.compare(info)
+    }
+
+    /// Get the external IP from the configured sources.
+    pub(crate) async fn get_info(&self) -> Result<IpAddressInfo, Vec<ValidationError>> {
+        let sources = IpSource::from_options(&self.options)
+            .map_err(|e| vec![ValidationError::String("IP providers".to_owned(), e)])?;
+        self.get(&sources).await
+    }
+
+    /// Compare the external IP to the expected IP and country.
+    pub(crate) fn compare(&self, info: IpAddressInfo) -> Result<(), Vec<ValidationError>> {
         let mut errors = Vec::new();
         let values = vec![
             ("IP address", self.options.expect_ip.clone(), info.ip),
             (
                 "Geolocated country",
                 self.options.expect_country.clone(),
-                info.country,
+                info.country.unwrap_or_default(),
             ),
         ];
         for (name, expected, actual) in values {
@@ -47,50 +81,35 @@ impl IpInfoProvider {
     }
 }
 
-#[allow(dead_code)]
-#[derive(Debug, Deserialize)]
-struct IpInfo {
-    ip: String,
-    hostname: String,
-    city: String,
-    region: String,
-    country: String,
-    loc: String,
-    org: String,
-    postal: String,
-    timezone: String,
-}
-
-impl Display for IpInfo {
-    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        write!(
-            f,
-            "{} ({}, {}, {})",
-            self.ip, self.city, self.region, self.country
-        )
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Create a provider that replays the external IP from the fixtures.
+    fn create_replay_provider() -> IpInfoProvider {
+        let http = HttpClient::new(TempDirectory::create("pura-ipinfo"))
+            .with_backend(HttpBackend::Replay(get_fixtures_dir()));
+        IpInfoProvider::new(AppOptions::default(), http)
+    }
+
     #[tokio::test]
-    #[ignore = "uses ipinfo.io"]
     async fn validate_env() {
         // Arrange
+        let mut ipinfo = create_replay_provider();
+        ipinfo.options.expect_ip = Some("198.51.100.7".to_owned());
+        ipinfo.options.expect_country = Some("GB".to_owned());
+
         // Act
-        let result = ServiceProvider::create().await;
+        let result = ipinfo.validate().await;
 
         // Assert
-        let _services = result.assert_ok();
+        result.assert_ok_debug();
     }
 
     #[tokio::test]
-    #[ignore = "uses ipinfo.io"]
     async fn validate_none() {
         // Arrange
-        let mut ipinfo = IpInfoProvider::default();
+        let mut ipinfo = create_replay_provider();
         ipinfo.options.expect_ip = None;
         ipinfo.options.expect_country = None;
 
@@ -102,10 +121,9 @@ mod tests {
     }
 
     #[tokio::test]
-    #[ignore = "uses ipinfo.io"]
     async fn validate_invalid() {
         // Arrange
-        let mut ipinfo = IpInfoProvider::default();
+        let mut ipinfo = create_replay_provider();
         ipinfo.options.expect_ip = Some("203.0.113.1".to_owned());
         ipinfo.options.expect_country = Some("INVALID".to_owned());
 
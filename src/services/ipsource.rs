@@ -0,0 +1,316 @@
+use crate::prelude::*;
+use serde_json::Value;
+
+const IPINFO_URL: &str = "https://ipinfo.io/json";
+const IFCONFIG_URL: &str = "https://ifconfig.co/json";
+const TEXT_URL: &str = "https://icanhazip.com";
+const DEFAULT_SOURCES: &str = "ipinfo,ifconfig";
+
+/// A service that reports the external IP address and optionally its country.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum IpSource {
+    /// `ipinfo.io` JSON
+    IpInfo(Url),
+    /// `ifconfig.co` style JSON with an ISO country code
+    Ifconfig(Url),
+    /// Plain text response containing only the IP address
+    Text(Url),
+    /// JSON response with fields selected by a path such as `$.data.ip`
+    Custom {
+        url: Url,
+        ip_field: String,
+        country_field: String,
+    },
+}
+
+/// External IP address reported by an [`IpSource`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct IpAddressInfo {
+    pub ip: String,
+    pub country: Option<String>,
+    pub city: Option<String>,
+    pub region: Option<String>,
+}
+
+impl IpSource {
+    /// Get the sources to try in order from the `ip_providers` option.
+    pub(crate) fn from_options(options: &AppOptions) -> Result<Vec<Self>, StringValidationError> {
+        let names = options.ip_providers.as_deref().unwrap_or(DEFAULT_SOURCES);
+        let mut sources = Vec::new();
+        for name in names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            let source = match name {
+                "ipinfo" => IpSource::IpInfo(parse_url(IPINFO_URL)),
+                "ifconfig" => IpSource::Ifconfig(parse_url(IFCONFIG_URL)),
+                "text" => IpSource::Text(
+                    options
+                        .ip_text_url
+                        .clone()
+                        .unwrap_or_else(|| parse_url(TEXT_URL)),
+                ),
+                "custom" => IpSource::Custom {
+                    url: options
+                        .ip_custom_url
+                        .clone()
+                        .ok_or(StringValidationError::Required)?,
+                    ip_field: options
+                        .ip_custom_ip_field
+                        .clone()
+                        .unwrap_or_else(|| "ip".to_owned()),
+                    country_field: options
+                        .ip_custom_country_field
+                        .clone()
+                        .unwrap_or_else(|| "country".to_owned()),
+                },
+                name => return Err(StringValidationError::Unsupported(name.to_owned())),
+            };
+            sources.push(source);
+        }
+        if sources.is_empty() {
+            return Err(StringValidationError::Required);
+        }
+        Ok(sources)
+    }
+
+    pub(crate) fn get_name(&self) -> &'static str {
+        match self {
+            IpSource::IpInfo(_) => "ipinfo",
+            IpSource::Ifconfig(_) => "ifconfig",
+            IpSource::Text(_) => "text",
+            IpSource::Custom { .. } => "custom",
+        }
+    }
+
+    fn get_url(&self) -> &Url {
+        match self {
+            IpSource::IpInfo(url)
+            | IpSource::Ifconfig(url)
+            | IpSource::Text(url)
+            | IpSource::Custom { url, .. } => url,
+        }
+    }
+
+    /// Request the external IP without the cache.
+    pub(crate) async fn get(&self, http: &HttpClient) -> Result<IpAddressInfo, IpSourceError> {
+        let body = http
+            .get_uncached(self.get_url())
+            .await
+            .map_err(IpSourceError::Http)?;
+        self.parse(&body)
+    }
+
+    pub(crate) fn parse(&self, body: &str) -> Result<IpAddressInfo, IpSourceError> {
+        match self {
+            IpSource::Text(_) => parse_text(body),
+            IpSource::IpInfo(_) => parse_json(body, "ip", "country", "city", "region"),
+            IpSource::Ifconfig(_) => parse_json(body, "ip", "country_iso", "city", "region_name"),
+            IpSource::Custom {
+                ip_field,
+                country_field,
+                ..
+            } => parse_json(body, ip_field, country_field, "", ""),
+        }
+    }
+}
+
+impl Display for IpAddressInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let location: Vec<&str> = [&self.city, &self.region, &self.country]
+            .into_iter()
+            .filter_map(Option::as_deref)
+            .collect();
+        if location.is_empty() {
+            write!(f, "{}", self.ip)
+        } else {
+            write!(f, "{} ({})", self.ip, location.join(", "))
+        }
+    }
+}
+
+/// Select a string or number from JSON by a path such as `$.data.ip` or `results[0].country`.
+///
+/// The leading `$` is optional. An empty path selects nothing.
+pub(crate) fn select_string(json: &Value, path: &str) -> Option<String> {
+    let path = path.trim();
+    let path = path.strip_prefix('$').unwrap_or(path);
+    let path = path.strip_prefix('.').unwrap_or(path);
+    if path.is_empty() {
+        return None;
+    }
+    let mut value = json;
+    for segment in path.split('.') {
+        let (key, indexes) = match segment.find('[') {
+            Some(start) => segment.split_at(start),
+            None => (segment, ""),
+        };
+        if !key.is_empty() {
+            value = value.get(key)?;
+        }
+        for index in indexes.split('[').filter(|index| !index.is_empty()) {
+            let index: usize = index.strip_suffix(']')?.parse().ok()?;
+            value = value.get(index)?;
+        }
+    }
+    match value {
+        Value::String(value) if !value.is_empty() => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+fn parse_text(body: &str) -> Result<IpAddressInfo, IpSourceError> {
+    let ip = body.trim();
+    if ip.is_empty() || ip.contains(char::is_whitespace) {
+        return Err(IpSourceError::InvalidResponse(ip.to_owned()));
+    }
+    Ok(IpAddressInfo {
+        ip: ip.to_owned(),
+        ..IpAddressInfo::default()
+    })
+}
+
+fn parse_json(
+    body: &str,
+    ip_field: &str,
+    country_field: &str,
+    city_field: &str,
+    region_field: &str,
+) -> Result<IpAddressInfo, IpSourceError> {
+    let json: Value = serde_json::from_str(body).map_err(IpSourceError::InvalidJson)?;
+    let ip = select_string(&json, ip_field)
+        .ok_or_else(|| IpSourceError::MissingField(ip_field.to_owned()))?;
+    Ok(IpAddressInfo {
+        ip,
+        country: select_string(&json, country_field),
+        city: select_string(&json, city_field),
+        region: select_string(&json, region_field),
+    })
+}
+
+fn parse_url(url: &str) -> Url {
+    Url::parse(url).expect("URL should be valid")
+}
+
+#[allow(clippy::absolute_paths)]
+#[derive(Debug)]
+pub enum IpSourceError {
+    Http(HttpError),
+    InvalidJson(serde_json::Error),
+    InvalidResponse(String),
+    MissingField(String),
+    MissingCountry,
+}
+
+impl Display for IpSourceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            IpSourceError::Http(e) => write!(f, "{e}"),
+            IpSourceError::InvalidJson(e) => write!(f, "Response is not valid JSON\n{e}"),
+            IpSourceError::InvalidResponse(body) => {
+                write!(f, "Response is not an IP address: {body}")
+            }
+            IpSourceError::MissingField(field) => {
+                write!(f, "Response does not contain field: {field}")
+            }
+            IpSourceError::MissingCountry => {
+                write!(f, "Response does not contain a country")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ipinfo_without_optional_fields() {
+        // Arrange
+        let source = IpSource::IpInfo(parse_url(IPINFO_URL));
+        let body = r#"{"ip": "203.0.113.1", "country": "GB", "bogon": false}"#;
+
+        // Act
+        let info = source.parse(body).assert_ok_debug();
+
+        // Assert
+        assert_eq!(info.ip, "203.0.113.1");
+        assert_eq!(info.country.as_deref(), Some("GB"));
+        assert_eq!(info.city, None);
+    }
+
+    #[test]
+    fn parse_ifconfig() {
+        // Arrange
+        let source = IpSource::Ifconfig(parse_url(IFCONFIG_URL));
+        let body = r#"{"ip": "203.0.113.1", "country": "United Kingdom", "country_iso": "GB"}"#;
+
+        // Act
+        let info = source.parse(body).assert_ok_debug();
+
+        // Assert
+        assert_eq!(info.country.as_deref(), Some("GB"));
+    }
+
+    #[test]
+    fn parse_custom() {
+        // Arrange
+        let source = IpSource::Custom {
+            url: parse_url("https://example.com/ip"),
+            ip_field: "$.data.addresses[1]".to_owned(),
+            country_field: "data.geo.code".to_owned(),
+        };
+        let body = r#"{"data": {"addresses": ["::1", "203.0.113.1"], "geo": {"code": "GB"}}}"#;
+
+        // Act
+        let info = source.parse(body).assert_ok_debug();
+        let missing = source.parse(r#"{"data": {}}"#);
+
+        // Assert
+        assert_eq!(info.ip, "203.0.113.1");
+        assert_eq!(info.country.as_deref(), Some("GB"));
+        assert!(matches!(missing, Err(IpSourceError::MissingField(_))));
+    }
+
+    #[tokio::test]
+    async fn get_text() {
+        // Arrange
+        let url = TestServer::start("text/plain", b"203.0.113.1\n".to_vec()).await;
+        let http = HttpClient::new(TempDirectory::create("pura-ip"));
+        let source = IpSource::Text(url);
+
+        // Act
+        let info = source.get(&http).await.assert_ok_debug();
+
+        // Assert
+        assert_eq!(info.ip, "203.0.113.1");
+        assert_eq!(info.country, None);
+    }
+
+    #[test]
+    fn from_options() {
+        // Arrange
+        let mut options = AppOptions {
+            ip_providers: Some("text, ifconfig".to_owned()),
+            ..AppOptions::default()
+        };
+
+        // Act
+        let sources = IpSource::from_options(&options).assert_ok_debug();
+        options.ip_providers = Some("custom".to_owned());
+        let custom = IpSource::from_options(&options);
+        options.ip_providers = Some("unknown".to_owned());
+        let unknown = IpSource::from_options(&options);
+
+        // Assert
+        assert_eq!(sources.len(), 2);
+        assert_eq!(sources.first().map(IpSource::get_name), Some("text"));
+        assert!(matches!(custom, Err(StringValidationError::Required)));
+        assert!(matches!(
+            unknown,
+            Err(StringValidationError::Unsupported(_))
+        ));
+    }
+}
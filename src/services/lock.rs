@@ -0,0 +1,505 @@
+use crate::prelude::*;
+use std::fs::{
+    create_dir_all as create_dir_all_sync, read_to_string, remove_file as remove_file_sync,
+    rename as rename_sync, write as write_sync, OpenOptions,
+};
+use std::io::{ErrorKind, Write};
+use std::process;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::time::sleep;
+
+/// Interval between attempts to take a held lock.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// Age after which a lock file without a process ID is considered stale.
+const UNOWNED_STALE_AGE: Duration = Duration::from_secs(10);
+const HOSTNAME_PATH: &str = "/proc/sys/kernel/hostname";
+const BOOT_ID_PATH: &str = "/proc/sys/kernel/random/boot_id";
+/// Suffix of the file held while a stale lock is taken over.
+pub(crate) const TAKEOVER_SUFFIX: &str = ".takeover";
+/// Suffix of the file written before it replaces a stale lock.
+pub(crate) const REPLACEMENT_SUFFIX: &str = ".new";
+/// Index of the start time in the fields of `/proc/<pid>/stat` after the command name.
+const STAT_START_TIME_INDEX: usize = 19;
+
+/// An advisory lock held by creating a file that identifies the owning process.
+///
+/// The file is removed when the lock is dropped. A lock left behind by a
+/// process that is no longer running on this host is taken over.
+#[derive(Debug)]
+pub(crate) struct LockFile {
+    path: PathBuf,
+}
+
+impl LockFile {
+    /// Take the lock, waiting while another process holds it.
+    ///
+    /// Without a timeout the wait is unlimited. A zero timeout fails fast.
+    pub(crate) async fn acquire(
+        path: PathBuf,
+        timeout: Option<Duration>,
+    ) -> Result<Self, LockError> {
+        let start = Instant::now();
+        let mut waiting = false;
+        loop {
+            let owner = match Self::try_acquire(&path)? {
+                Ok(lock) => return Ok(lock),
+                Err(owner) => owner,
+            };
+            if timeout.is_some_and(|timeout| start.elapsed() >= timeout) {
+                return Err(LockError::Locked(path, owner));
+            }
+            if !waiting {
+                info!(
+                    "{} for lock held by {}: {}",
+                    "Waiting".bold(),
+                    get_owner_name(owner.as_ref()),
+                    path.display()
+                );
+                waiting = true;
+            }
+            sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Try to take the lock once, taking it over if it is stale.
+    ///
+    /// Returns the owner if the lock is held.
+    fn try_acquire(path: &Path) -> Result<Result<Self, Option<LockOwner>>, LockError> {
+        if let Some(dir) = path.parent() {
+            create_dir_all_sync(dir).map_err(|e| LockError::Io(dir.to_path_buf(), e))?;
+        }
+        match OpenOptions::new().write(true).create_new(true).open(path) {
+            Ok(mut file) => {
+                write!(file, "{}", LockOwner::current().serialize())
+                    .map_err(|e| LockError::Io(path.to_path_buf(), e))?;
+                trace!("{} lock {}", "Acquired".bold(), path.display());
+                return Ok(Ok(Self::new(path)));
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(LockError::Io(path.to_path_buf(), e)),
+        }
+        let owner = get_owner(path);
+        if !is_stale(path, owner.as_ref()) {
+            return Ok(Err(owner));
+        }
+        if take_over(path, owner.as_ref())? {
+            warn!(
+                "{} stale lock of {}: {}",
+                "Took over".bold(),
+                get_owner_name(owner.as_ref()),
+                path.display()
+            );
+            return Ok(Ok(Self::new(path)));
+        }
+        Ok(Err(get_owner(path)))
+    }
+
+    fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+/// Replace a stale lock with one owned by this process.
+///
+/// The lock is never removed so it can't be created by another process in the
+/// meantime. Instead a takeover file is created exclusively so only one process
+/// replaces the lock, and the owner is read again under it so a lock that has
+/// already been taken over is left alone. The replacement is written in full
+/// then renamed over the lock atomically.
+///
+/// Returns false if another process is taking over or has taken over the lock.
+fn take_over(path: &Path, stale: Option<&LockOwner>) -> Result<bool, LockError> {
+    let guard = get_sibling_path(path, TAKEOVER_SUFFIX);
+    match OpenOptions::new().write(true).create_new(true).open(&guard) {
+        Ok(_) => {}
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+            if is_stale(&guard, None) {
+                warn!("{} stale takeover {}", "Removing".bold(), guard.display());
+                let _ = remove_file_sync(&guard);
+            }
+            return Ok(false);
+        }
+        Err(e) => return Err(LockError::Io(guard, e)),
+    }
+    let result = replace_if_owned_by(path, stale);
+    let _ = remove_file_sync(&guard);
+    result
+}
+
+/// Atomically replace the lock if it is still owned by the stale owner.
+fn replace_if_owned_by(path: &Path, stale: Option<&LockOwner>) -> Result<bool, LockError> {
+    if !path.exists() || get_owner(path).as_ref() != stale {
+        return Ok(false);
+    }
+    let replacement = get_sibling_path(path, REPLACEMENT_SUFFIX);
+    write_sync(&replacement, LockOwner::current().serialize())
+        .map_err(|e| LockError::Io(replacement.clone(), e))?;
+    rename_sync(&replacement, path).map_err(|e| LockError::Io(path.to_path_buf(), e))?;
+    Ok(true)
+}
+
+/// Get a path next to a lock with a suffix added to the file name.
+fn get_sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        if let Err(e) = remove_file_sync(&self.path) {
+            warn!(
+                "{} to release lock {}\n{e}",
+                "Failed".bold(),
+                self.path.display()
+            );
+        } else {
+            trace!("{} lock {}", "Released".bold(), self.path.display());
+        }
+    }
+}
+
+/// Process that holds a lock.
+///
+/// The host and boot are recorded so a process ID is only checked on the host and
+/// boot it belongs to. Hosts include containers which have their own process IDs.
+/// The start time of the process tells apart runs that reuse a process ID, such as
+/// each container run as process 1.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LockOwner {
+    pid: u32,
+    host: Option<String>,
+    boot_id: Option<String>,
+    started: Option<u64>,
+}
+
+impl LockOwner {
+    fn current() -> Self {
+        Self {
+            pid: process::id(),
+            host: read_identity(HOSTNAME_PATH),
+            boot_id: read_identity(BOOT_ID_PATH),
+            started: get_start_time(process::id()),
+        }
+    }
+
+    /// Write as lines of the process ID, host, boot ID and start time.
+    fn serialize(&self) -> String {
+        [
+            self.pid.to_string(),
+            self.host.clone().unwrap_or_default(),
+            self.boot_id.clone().unwrap_or_default(),
+            self.started
+                .map(|started| started.to_string())
+                .unwrap_or_default(),
+        ]
+        .join("\n")
+    }
+
+    /// Read the lines written by [`LockOwner::serialize`].
+    ///
+    /// A lock with only a process ID has an unknown host.
+    fn deserialize(contents: &str) -> Option<Self> {
+        let mut lines = contents.lines().map(str::trim);
+        let pid = lines.next()?.parse().ok()?;
+        let mut next = || {
+            lines
+                .next()
+                .filter(|line| !line.is_empty())
+                .map(ToOwned::to_owned)
+        };
+        Some(Self {
+            pid,
+            host: next(),
+            boot_id: next(),
+            started: next().and_then(|started| started.parse().ok()),
+        })
+    }
+
+    /// Is the process running?
+    ///
+    /// A process on another host can't be checked so is assumed to be running.
+    /// A process from an earlier boot of this host is not. Otherwise the process
+    /// with the ID must have the same start time, including this process.
+    fn is_running(&self) -> bool {
+        let current = Self::current();
+        if is_different(self.host.as_ref(), current.host.as_ref()) {
+            return true;
+        }
+        if is_different(self.boot_id.as_ref(), current.boot_id.as_ref()) {
+            return false;
+        }
+        if !cfg!(target_os = "linux") {
+            return true;
+        }
+        match self.started {
+            Some(started) => get_start_time(self.pid) == Some(started),
+            None => Path::new("/proc").join(self.pid.to_string()).exists(),
+        }
+    }
+}
+
+impl Display for LockOwner {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "process {}", self.pid)?;
+        if let Some(host) = &self.host {
+            write!(f, " on {host}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Get the owner written to a lock file.
+fn get_owner(path: &Path) -> Option<LockOwner> {
+    LockOwner::deserialize(&read_to_string(path).ok()?)
+}
+
+fn get_owner_name(owner: Option<&LockOwner>) -> String {
+    owner.map_or_else(|| "an unknown process".to_owned(), ToString::to_string)
+}
+
+/// Read the hostname or boot ID of this host.
+fn read_identity(path: &str) -> Option<String> {
+    let value = read_to_string(path).ok()?.trim().to_owned();
+    (!value.is_empty()).then_some(value)
+}
+
+/// Are both values known and different?
+fn is_different(owner: Option<&String>, current: Option<&String>) -> bool {
+    owner
+        .zip(current)
+        .is_some_and(|(owner, current)| owner != current)
+}
+
+/// Is the lock left by a process that is no longer running?
+///
+/// A lock without a process ID may still be being written so is only stale once old.
+fn is_stale(path: &Path, owner: Option<&LockOwner>) -> bool {
+    match owner {
+        Some(owner) => !owner.is_running(),
+        None => path
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age >= UNOWNED_STALE_AGE),
+    }
+}
+
+/// Get the start time of a process in clock ticks since boot.
+///
+/// Only available on Linux.
+fn get_start_time(pid: u32) -> Option<u64> {
+    let stat = read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    let (_, fields) = stat.rsplit_once(')')?;
+    fields
+        .split_whitespace()
+        .nth(STAT_START_TIME_INDEX)?
+        .parse()
+        .ok()
+}
+
+/// How long to wait for a lock held by another run.
+///
+/// Configured as a number of seconds or `forever`.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(try_from = "LockTimeoutValue", into = "LockTimeoutValue")]
+pub enum LockTimeout {
+    Seconds(u64),
+    Forever,
+}
+
+/// Seconds as a number or any value as text in a config file or environment variable.
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum LockTimeoutValue {
+    Seconds(u64),
+    Text(String),
+}
+
+impl LockTimeout {
+    /// Parse a number of seconds or `forever`.
+    pub(crate) fn parse(value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("forever") {
+            return Ok(LockTimeout::Forever);
+        }
+        value
+            .parse()
+            .map(LockTimeout::Seconds)
+            .map_err(|_| format!("Invalid lock timeout: {value}"))
+    }
+
+    /// Get the timeout to acquire with, or `None` to wait forever.
+    #[must_use]
+    pub(crate) fn to_duration(self) -> Option<Duration> {
+        match self {
+            LockTimeout::Seconds(seconds) => Some(Duration::from_secs(seconds)),
+            LockTimeout::Forever => None,
+        }
+    }
+}
+
+impl TryFrom<LockTimeoutValue> for LockTimeout {
+    type Error = String;
+
+    fn try_from(value: LockTimeoutValue) -> Result<Self, Self::Error> {
+        match value {
+            LockTimeoutValue::Seconds(seconds) => Ok(LockTimeout::Seconds(seconds)),
+            LockTimeoutValue::Text(text) => LockTimeout::parse(&text),
+        }
+    }
+}
+
+impl From<LockTimeout> for LockTimeoutValue {
+    fn from(timeout: LockTimeout) -> Self {
+        match timeout {
+            LockTimeout::Seconds(seconds) => LockTimeoutValue::Seconds(seconds),
+            LockTimeout::Forever => LockTimeoutValue::Text("forever".to_owned()),
+        }
+    }
+}
+
+#[derive(Debug)]
+#[allow(clippy::absolute_paths)]
+pub enum LockError {
+    Locked(PathBuf, Option<LockOwner>),
+    Io(PathBuf, std::io::Error),
+}
+
+impl Display for LockError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            LockError::Locked(path, owner) => write!(
+                f,
+                "Lock is held by {}\nPath: {}\nUse --lock-timeout to wait for it",
+                get_owner_name(owner.as_ref()),
+                path.display()
+            ),
+            LockError::Io(path, e) => {
+                write!(f, "An I/O error occurred.\nPath: {}\n{e}", path.display())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::write;
+
+    #[tokio::test]
+    async fn lock_file() {
+        // Arrange
+        let dir = TempDirectory::create("pura-lock");
+        let path = dir.join("podcast.lock");
+        let stale = dir.join("stale.lock");
+        write(&stale, u32::MAX.to_string()).assert_ok_debug();
+
+        // Act
+        let lock = LockFile::acquire(path.clone(), Some(Duration::ZERO)).await;
+        let held = LockFile::acquire(path.clone(), Some(Duration::from_millis(300))).await;
+        let taken_over = LockFile::acquire(stale.clone(), Some(Duration::ZERO)).await;
+
+        // Assert
+        let lock = lock.assert_ok_debug();
+        assert_eq!(get_owner(&path), Some(LockOwner::current()));
+        assert!(
+            matches!(held, Err(LockError::Locked(_, Some(owner))) if owner.pid == process::id())
+        );
+        drop(lock);
+        assert!(!path.exists());
+        let taken_over = taken_over.assert_ok_debug();
+        assert_eq!(get_owner(&stale), Some(LockOwner::current()));
+        drop(taken_over);
+    }
+
+    #[test]
+    fn lock_owner_on_other_host() {
+        // Arrange
+        let current = LockOwner::current();
+        let other_host = LockOwner {
+            pid: u32::MAX,
+            host: Some("other-host".to_owned()),
+            boot_id: None,
+            started: None,
+        };
+        let earlier_boot = LockOwner {
+            boot_id: Some("earlier-boot".to_owned()),
+            ..current.clone()
+        };
+        let earlier_run = LockOwner {
+            started: current.started.map(|started| started + 1),
+            ..current.clone()
+        };
+
+        // Act
+        let legacy = LockOwner::deserialize("42\n");
+        let round_trip = LockOwner::deserialize(&current.serialize());
+
+        // Assert
+        assert!(other_host.is_running());
+        assert!(current.is_running());
+        if current.boot_id.is_some() {
+            assert!(!earlier_boot.is_running());
+        }
+        if current.started.is_some() {
+            assert!(!earlier_run.is_running());
+        }
+        assert_eq!(
+            legacy,
+            Some(LockOwner {
+                pid: 42,
+                host: None,
+                boot_id: None,
+                started: None,
+            })
+        );
+        assert_eq!(round_trip, Some(current));
+    }
+
+    #[test]
+    fn take_over_only_once() {
+        // Arrange
+        let dir = TempDirectory::create("pura-lock-takeover");
+        let path = dir.join("podcast.lock");
+        let guarded = dir.join("guarded.lock");
+        write(&path, u32::MAX.to_string()).assert_ok_debug();
+        write(&guarded, u32::MAX.to_string()).assert_ok_debug();
+        write(get_sibling_path(&guarded, TAKEOVER_SUFFIX), "").assert_ok_debug();
+        let stale = get_owner(&path);
+
+        // Act
+        let first = take_over(&path, stale.as_ref());
+        let second = take_over(&path, stale.as_ref());
+        let during_takeover = LockFile::try_acquire(&guarded);
+
+        // Assert
+        assert!(first.assert_ok_debug());
+        assert!(!second.assert_ok_debug());
+        assert_eq!(get_owner(&path), Some(LockOwner::current()));
+        assert!(!get_sibling_path(&path, TAKEOVER_SUFFIX).exists());
+        assert!(matches!(during_takeover, Ok(Err(_))));
+        assert_eq!(get_owner(&guarded), stale);
+    }
+
+    #[test]
+    fn lock_timeout_parse() {
+        // Act
+        let seconds = LockTimeout::parse("30");
+        let forever = LockTimeout::parse("Forever");
+        let invalid = LockTimeout::parse("soon");
+        let from_number: Result<LockTimeout, _> = serde_yaml::from_str("30");
+        let from_text: Result<LockTimeout, _> = serde_yaml::from_str("forever");
+
+        // Assert
+        assert_eq!(seconds, Ok(LockTimeout::Seconds(30)));
+        assert_eq!(forever, Ok(LockTimeout::Forever));
+        assert!(invalid.is_err());
+        assert_eq!(from_number.assert_ok_debug(), LockTimeout::Seconds(30));
+        assert_eq!(from_text.assert_ok_debug(), LockTimeout::Forever);
+        assert_eq!(LockTimeout::Forever.to_duration(), None);
+    }
+}
@@ -0,0 +1,130 @@
+use crate::prelude::*;
+use serde_yaml::{Mapping, Value};
+
+/// Version of the podcast schema written by this build.
+pub(crate) const SCHEMA_VERSION: usize = 1;
+
+const VERSION_KEY: &str = "schema_version";
+
+/// Upgrade a document from the version at its index to the next version.
+type Migration = fn(&mut Mapping) -> Result<(), MigrationError>;
+
+/// Migrations in order, starting with the migration from version 0.
+const MIGRATIONS: [Migration; SCHEMA_VERSION] = [migrate_v0];
+
+/// Get the schema version of a podcast document.
+///
+/// Documents written before versioning have no `schema_version` and are version 0.
+pub(crate) fn get_document_version(document: &Value) -> Result<usize, MigrationError> {
+    let Some(value) = document.get(VERSION_KEY) else {
+        return Ok(0);
+    };
+    value
+        .as_u64()
+        .and_then(|version| usize::try_from(version).ok())
+        .ok_or_else(|| MigrationError::InvalidVersion(format!("{value:?}")))
+}
+
+/// Apply each migration from the document version to the current version.
+///
+/// Returns the version the document was migrated from.
+pub(crate) fn migrate(document: &mut Value) -> Result<usize, MigrationError> {
+    let version = get_document_version(document)?;
+    if version > SCHEMA_VERSION {
+        return Err(MigrationError::Unsupported(version));
+    }
+    let Value::Mapping(mapping) = document else {
+        return Err(MigrationError::NotMapping);
+    };
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        trace!("{} podcast schema from version {from}", "Migrating".bold());
+        migration(mapping)?;
+        mapping.insert(VERSION_KEY.into(), (from + 1).into());
+    }
+    Ok(version)
+}
+
+/// Version 0 to 1
+///
+/// Documents written before versioning only need the version set as every
+/// field added since the first release has a default.
+#[allow(clippy::unnecessary_wraps)]
+fn migrate_v0(_document: &mut Mapping) -> Result<(), MigrationError> {
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum MigrationError {
+    NotMapping,
+    InvalidVersion(String),
+    Unsupported(usize),
+}
+
+impl Display for MigrationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            MigrationError::NotMapping => write!(f, "Document is not a podcast"),
+            MigrationError::InvalidVersion(value) => {
+                write!(f, "Schema version is not a number: {value}")
+            }
+            MigrationError::Unsupported(version) => write!(
+                f,
+                "Schema version {version} is newer than the supported version {SCHEMA_VERSION}\nUpdate to the latest release"
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::read_to_string;
+
+    fn read_fixture(version: usize) -> Value {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join("schema")
+            .join(format!("v{version}.yml"));
+        let yaml = read_to_string(path).expect("fixture should be readable");
+        serde_yaml::from_str(&yaml).expect("fixture should be valid YAML")
+    }
+
+    #[test]
+    fn migrate_fixtures() {
+        for version in 0..=SCHEMA_VERSION {
+            // Arrange
+            let mut document = read_fixture(version);
+
+            // Act
+            let from = migrate(&mut document).assert_ok_debug();
+
+            // Assert
+            assert_eq!(from, version);
+            let podcast: Podcast = serde_yaml::from_value(document).assert_ok_debug();
+            assert_eq!(podcast.schema_version, SCHEMA_VERSION);
+            assert_eq!(podcast.id, "fixture");
+            assert_eq!(podcast.episodes.len(), 2);
+            if version >= 1 {
+                assert!(podcast
+                    .episodes
+                    .iter()
+                    .any(|episode| !episode.tracked_audio_urls.is_empty()));
+            }
+        }
+    }
+
+    #[test]
+    fn migrate_unsupported() {
+        // Arrange
+        let mut document = read_fixture(SCHEMA_VERSION);
+        if let Value::Mapping(mapping) = &mut document {
+            mapping.insert(VERSION_KEY.into(), (SCHEMA_VERSION + 1).into());
+        }
+
+        // Act
+        let result = migrate(&mut document);
+
+        // Assert
+        assert!(matches!(result, Err(MigrationError::Unsupported(_))));
+    }
+}
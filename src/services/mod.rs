@@ -1,13 +1,43 @@
+pub(crate) use backend::*;
+pub use cancel::*;
+pub(crate) use cookies::*;
+pub use headers::*;
 pub(crate) use http::*;
 pub(crate) use ipinfo::*;
-pub(crate) use options::*;
+pub use ipsource::*;
+pub use lock::*;
+pub use migrations::*;
+pub use options::*;
+pub use output::*;
 pub(crate) use paths::*;
 pub(crate) use podcasts::*;
 pub use provider::*;
+pub(crate) use recorder::*;
+pub(crate) use redirects::*;
+pub(crate) use sqlite::*;
+pub use store::*;
+pub(crate) use throttle::*;
+pub(crate) use watchdog::*;
+pub(crate) use yaml::*;
 
+mod backend;
+mod cancel;
+mod cookies;
+mod headers;
 mod http;
 mod ipinfo;
+mod ipsource;
+mod lock;
+mod migrations;
 mod options;
+mod output;
 mod paths;
 mod podcasts;
 mod provider;
+mod recorder;
+mod redirects;
+mod sqlite;
+mod store;
+mod throttle;
+mod watchdog;
+mod yaml;
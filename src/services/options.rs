@@ -26,15 +26,208 @@ pub struct AppOptions {
     ///
     /// Default: None
     pub expect_country: Option<String>,
+    /// Seconds between checks of the external IP while a command is running.
+    ///
+    /// Only applies if `expect_ip` or `expect_country` is set.
+    /// Requests are aborted if the IP stops matching. Set to 0 to only check
+    /// between batches.
+    ///
+    /// Default: 300
+    pub ip_check_interval: Option<u64>,
+    /// Comma separated IP check providers to try in order until one succeeds.
+    ///
+    /// Supports `ipinfo`, `ifconfig`, `text` and `custom`.
+    /// The `text` provider only reports the IP so is skipped if `expect_country` is set.
+    ///
+    /// Default: `ipinfo,ifconfig`
+    pub ip_providers: Option<String>,
+    /// URL of a plain text IP echo service for the `text` provider.
+    ///
+    /// Default: `https://icanhazip.com`
+    pub ip_text_url: Option<Url>,
+    /// URL of a JSON IP service for the `custom` provider.
+    ///
+    /// Default: None
+    pub ip_custom_url: Option<Url>,
+    /// Path of the IP address field in the `custom` provider response such as `$.data.ip`.
+    ///
+    /// Default: `ip`
+    pub ip_custom_ip_field: Option<String>,
+    /// Path of the ISO country code field in the `custom` provider response.
+    ///
+    /// Default: `country`
+    pub ip_custom_country_field: Option<String>,
+    /// Proxy URL for every HTTP request including the IP check.
+    ///
+    /// Supports `http`, `https`, `socks5` and `socks5h` schemes.
+    /// A podcast proxy takes precedence.
+    ///
+    /// Default: None
+    pub proxy: Option<Url>,
+    /// User-Agent header for every HTTP request.
+    ///
+    /// A header profile for the host takes precedence.
+    ///
+    /// Default: None
+    pub user_agent: Option<String>,
+    /// YAML file of header profiles matched by host.
+    ///
+    /// Each profile has a `host` pattern such as `*.example.com` and optional
+    /// `user_agent`, `referer`, `authorization` and `cookie` values.
+    /// Each header is taken from the first matching profile that sets it.
+    ///
+    /// Default: None
+    pub header_profiles: Option<PathBuf>,
+    /// YAML file to persist cookies set by responses between runs.
+    ///
+    /// Cookies are not stored if unset.
+    ///
+    /// Default: None
+    pub cookie_jar: Option<PathBuf>,
+    /// Comma separated tracking prefixes to remove from enclosure URLs when scraping.
+    ///
+    /// Each pattern is a host and path prefix such as `dts.podtrac.com/redirect.*`
+    /// where `*` in a segment matches any characters.
+    /// Set to an empty string to keep tracking prefixes.
+    ///
+    /// Default: podtrac, chartable, pdst.fm, op3.dev and other common trackers
+    pub tracking_prefixes: Option<String>,
+    /// Storage backend for podcasts, episodes, history and download state.
+    ///
+    /// Supports `yaml` and `sqlite`.
+    /// Use the `db import` and `db export` commands to move data between backends.
+    ///
+    /// Default: `yaml`
+    pub database: Option<DatabaseKind>,
+    /// Database file for the `sqlite` backend.
+    ///
+    /// Default: `podcasts.db` in the cache directory
+    pub database_path: Option<PathBuf>,
+    /// Maximum download rate across every request such as `10MB` per second.
+    ///
+    /// Default: None
+    pub max_rate: Option<String>,
+    /// Maximum download rate for each host such as `2MB` per second.
+    ///
+    /// Default: None
+    pub max_rate_per_host: Option<String>,
+    /// Comma separated local times that downloads may run such as `01:00-07:00`.
+    ///
+    /// Outside a window downloads pause between chunks and resume when a window opens.
+    /// A window ending before it starts runs over midnight.
+    ///
+    /// Default: None
+    pub download_windows: Option<String>,
+    /// Seconds to let work in progress finish after Ctrl-C or SIGTERM before aborting it.
+    ///
+    /// A second signal aborts immediately.
+    ///
+    /// Default: 30
+    pub shutdown_grace: Option<u64>,
+    /// Seconds to wait for a podcast locked by another run before failing, or `forever`.
+    ///
+    /// Commands that change a podcast hold a lock file containing their process ID.
+    /// A lock left by a process that is no longer running is taken over.
+    ///
+    /// Default: 0 to fail immediately
+    pub lock_timeout: Option<LockTimeout>,
+    /// Seconds to wait for a cache entry being downloaded by another run, or `forever`.
+    ///
+    /// Default: forever
+    pub cache_lock_timeout: Option<LockTimeout>,
+    /// Only serve HTTP responses from the cache.
+    ///
+    /// The IP check is skipped and any cache miss fails instead of using the network.
+    ///
+    /// Default: false
+    #[serde(default)]
+    pub offline: bool,
+    /// Directory of recorded HTTP responses to serve instead of using the network.
+    ///
+    /// Fixtures use the same layout as the HTTP cache directory.
+    ///
+    /// Default: None
+    pub replay_dir: Option<PathBuf>,
+    /// Directory to record HTTP responses to as fixtures.
+    ///
+    /// Ignored if `replay_dir` is set.
+    ///
+    /// Default: None
+    pub record_dir: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, Default, Args)]
+pub struct GlobalOptions {
+    /// Only serve HTTP responses from the cache and never use the network
+    #[arg(long, global = true)]
+    pub offline: bool,
+    /// Format of the command result
+    ///
+    /// `json` prints the result to stdout while logs continue on stderr.
+    #[arg(long, global = true, value_enum, default_value_t)]
+    pub output: OutputFormat,
+    /// Seconds to wait for a podcast locked by another run before failing, or `forever`
+    #[arg(long, global = true, value_name = "SECONDS", value_parser = LockTimeout::parse)]
+    pub lock_timeout: Option<LockTimeout>,
 }
 
 impl AppOptions {
     pub(crate) fn validate(&self) -> Result<(), Vec<ValidationError>> {
         let paths = PathProvider::new(self.clone());
-        paths.validate()
+        let mut errors = paths.validate().err().unwrap_or_default();
+        if let Some(proxy) = &self.proxy {
+            if let Err(e) = Validate::proxy(proxy) {
+                errors.push(ValidationError::String("Proxy".to_owned(), e));
+            }
+        }
+        if let Some(patterns) = &self.tracking_prefixes {
+            if let Err(e) = TrackingPrefixes::parse(patterns) {
+                errors.push(ValidationError::String("Tracking prefixes".to_owned(), e));
+            }
+        }
+        for (name, rate) in [
+            ("Max rate", &self.max_rate),
+            ("Max rate per host", &self.max_rate_per_host),
+        ] {
+            if let Some(rate) = rate {
+                if parse_bytes(rate).is_err() {
+                    errors.push(ValidationError::String(
+                        name.to_owned(),
+                        StringValidationError::Unsupported(rate.clone()),
+                    ));
+                }
+            }
+        }
+        if let Some(windows) = &self.download_windows {
+            if let Err(e) = DownloadWindow::parse_all(windows) {
+                errors.push(ValidationError::String("Download windows".to_owned(), e));
+            }
+        }
+        if let Some(path) = &self.header_profiles {
+            if !path.is_file() {
+                errors.push(ValidationError::Path(
+                    "Header profiles".to_owned(),
+                    PathValidationError::NotFile(path.clone()),
+                ));
+            }
+        }
+        if self.expect_ip.is_some() || self.expect_country.is_some() {
+            if let Err(e) = IpSource::from_options(self) {
+                errors.push(ValidationError::String("IP providers".to_owned(), e));
+            }
+        }
+        errors.to_result()
     }
 
     pub(crate) fn get() -> Result<Self, ConfigError> {
         EnvironmentOptionsProvider::get()
     }
+
+    /// Override with options set on the command line.
+    pub(crate) fn apply(&mut self, global: &GlobalOptions) {
+        self.offline |= global.offline;
+        if global.lock_timeout.is_some() {
+            self.lock_timeout = global.lock_timeout;
+        }
+    }
 }
@@ -0,0 +1,221 @@
+use crate::prelude::*;
+use serde_json::Value;
+use std::io::{stdout, Write};
+
+/// Exit status when a command fails to produce a result.
+pub const EXIT_FAILURE: i32 = 1;
+/// Exit status when a command produces a result but some items failed.
+pub const EXIT_PARTIAL: i32 = 2;
+/// Exit status when a command is stopped by Ctrl-C or SIGTERM.
+pub const EXIT_CANCELLED: i32 = 130;
+
+/// Format of the result printed by a command
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// Only log coloured text to stderr
+    #[default]
+    Text,
+    /// Also print the result as JSON to stdout
+    Json,
+}
+
+/// An error with a stable code that scripts can match on.
+pub trait ErrorCode: Display {
+    /// Get the code of the error.
+    ///
+    /// Codes are the command and the snake case variant, for example `download.ip_changed`.
+    fn get_code(&self) -> String;
+}
+
+/// An error in the JSON output of a command
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ErrorOutput {
+    /// Stable code of the error
+    pub code: String,
+    /// Human readable message of the error
+    pub message: String,
+    /// ID of the podcast the error applies to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub podcast: Option<String>,
+    /// ID of the episode the error applies to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub episode: Option<String>,
+}
+
+impl ErrorOutput {
+    pub fn new(error: &impl ErrorCode) -> Self {
+        Self {
+            code: error.get_code(),
+            message: error.to_string(),
+            podcast: None,
+            episode: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_podcast(mut self, id: &str) -> Self {
+        self.podcast = Some(id.to_owned());
+        self
+    }
+
+    #[must_use]
+    pub fn with_episode(mut self, episode: &Episode) -> Self {
+        self.episode = Some(episode.id.clone());
+        self
+    }
+}
+
+/// Result of a command printed to stdout with `--output json`.
+///
+/// A command either fails with a single error or returns a result along with
+/// the errors of any items that failed.
+#[derive(Debug, Serialize)]
+pub struct CommandOutput {
+    /// Did the command complete without any errors?
+    pub success: bool,
+    /// Result of the command if it completed
+    pub result: Option<Value>,
+    /// Errors of the command or of the items that failed
+    pub errors: Vec<ErrorOutput>,
+    /// Was the command stopped by Ctrl-C or SIGTERM?
+    pub cancelled: bool,
+    /// Did every item attempted fail?
+    #[serde(skip)]
+    failed: bool,
+}
+
+impl CommandOutput {
+    pub fn new<T: Serialize, E: ErrorCode>(result: Result<T, E>) -> Self {
+        match result {
+            Ok(result) => Self {
+                success: true,
+                result: Some(serde_json::to_value(result).unwrap_or(Value::Null)),
+                errors: Vec::new(),
+                cancelled: false,
+                failed: false,
+            },
+            Err(e) => Self {
+                success: false,
+                result: None,
+                errors: vec![ErrorOutput::new(&e)],
+                cancelled: false,
+                failed: false,
+            },
+        }
+    }
+
+    /// Add the errors of items that failed without failing the command.
+    #[must_use]
+    pub fn with_errors(mut self, mut errors: Vec<ErrorOutput>) -> Self {
+        self.success = self.success && errors.is_empty();
+        self.errors.append(&mut errors);
+        self
+    }
+
+    /// Mark the command as failed as none of the items attempted succeeded.
+    #[must_use]
+    pub fn with_failed(mut self, failed: bool) -> Self {
+        self.success = self.success && !failed;
+        self.failed = failed;
+        self
+    }
+
+    /// Mark the command as stopped by a signal.
+    #[must_use]
+    pub fn with_cancelled(mut self, cancelled: bool) -> Self {
+        self.success = self.success && !cancelled;
+        self.cancelled = cancelled;
+        self
+    }
+
+    /// Get the process exit status.
+    ///
+    /// `0` on success, [`EXIT_PARTIAL`] if some items failed, [`EXIT_FAILURE`]
+    /// if the command or every item failed and [`EXIT_CANCELLED`] if the command
+    /// was stopped.
+    #[must_use]
+    pub fn get_exit_code(&self) -> i32 {
+        if self.cancelled {
+            EXIT_CANCELLED
+        } else if self.result.is_none() || self.failed {
+            EXIT_FAILURE
+        } else if self.errors.is_empty() {
+            0
+        } else {
+            EXIT_PARTIAL
+        }
+    }
+
+    /// Print the output in the chosen format.
+    ///
+    /// Item errors have already been logged by the command so only a command
+    /// failure is logged as text.
+    pub fn print(&self, format: OutputFormat) {
+        match format {
+            OutputFormat::Text => {
+                if self.result.is_none() {
+                    for error in &self.errors {
+                        error!("{}", error.message);
+                    }
+                }
+            }
+            OutputFormat::Json => {
+                let json = serde_json::to_string_pretty(self).unwrap_or_default();
+                let mut stdout = stdout().lock();
+                if let Err(e) = writeln!(stdout, "{json}") {
+                    error!("{} to write output\n{e}", "Failed".bold());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct TestError;
+
+    impl Display for TestError {
+        fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+            write!(f, "Test error")
+        }
+    }
+
+    impl ErrorCode for TestError {
+        fn get_code(&self) -> String {
+            "test.error".to_owned()
+        }
+    }
+
+    #[test]
+    fn command_output_exit_codes() {
+        // Arrange
+        let episode = Episode::example();
+
+        // Act
+        let success = CommandOutput::new::<_, TestError>(Ok(1));
+        let partial = CommandOutput::new::<_, TestError>(Ok(1))
+            .with_errors(vec![ErrorOutput::new(&TestError).with_episode(&episode)]);
+        let failure = CommandOutput::new::<usize, _>(Err(TestError));
+        let all_failed = CommandOutput::new::<_, TestError>(Ok(1))
+            .with_errors(vec![ErrorOutput::new(&TestError).with_episode(&episode)])
+            .with_failed(true);
+        let cancelled = CommandOutput::new::<_, TestError>(Ok(1)).with_cancelled(true);
+
+        // Assert
+        assert_eq!(success.get_exit_code(), 0);
+        assert!(success.success);
+        assert_eq!(partial.get_exit_code(), EXIT_PARTIAL);
+        assert!(!partial.success);
+        assert_eq!(failure.get_exit_code(), EXIT_FAILURE);
+        assert_eq!(all_failed.get_exit_code(), EXIT_FAILURE);
+        assert_eq!(cancelled.get_exit_code(), EXIT_CANCELLED);
+        assert!(!cancelled.success);
+        let json = serde_json::to_value(&partial).assert_ok_debug();
+        let error = json.pointer("/errors/0").expect("should have an error");
+        assert_eq!(error.get("code"), Some(&Value::from("test.error")));
+        assert_eq!(error.get("episode"), Some(&Value::from(episode.id)));
+    }
+}
@@ -5,14 +5,17 @@ const DEFAULT_CACHE_DIR: &str = "cache";
 const DEFAULT_OUTPUT_DIR: &str = "output";
 const HTTP_DIR: &str = "http";
 const PODCASTS_DIR: &str = "podcasts";
+const REPORTS_DIR: &str = "reports";
 pub(crate) const HEAD_EXTENSION: &str = "head";
 pub(crate) const HTML_EXTENSION: &str = "html";
 pub(crate) const JSON_EXTENSION: &str = "json";
+pub(crate) const MOVED_EXTENSION: &str = "moved";
 pub(crate) const MP3_EXTENSION: &str = "mp3";
 pub(crate) const RSS_EXTENSION: &str = "rss";
 const RSS_FILE_NAME: &str = "feed.rss";
+const DATABASE_FILE_NAME: &str = "podcasts.db";
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct PathProvider {
     options: AppOptions,
 }
@@ -37,6 +40,18 @@ impl PathProvider {
         self.get_cache_dir().join(PODCASTS_DIR)
     }
 
+    /// Get the directory run reports are saved in.
+    pub(crate) fn get_reports_dir(&self) -> PathBuf {
+        self.get_cache_dir().join(REPORTS_DIR)
+    }
+
+    pub(crate) fn get_database_path(&self) -> PathBuf {
+        self.options
+            .database_path
+            .clone()
+            .unwrap_or_else(|| self.get_cache_dir().join(DATABASE_FILE_NAME))
+    }
+
     pub(crate) fn get_output_dir(&self) -> PathBuf {
         self.options
             .output_dir
@@ -115,6 +130,11 @@ impl PathProvider {
                 errors.push(ValidationError::Path(name.to_owned(), e));
             }
         }
+        if let Some(dir) = &self.options.replay_dir {
+            if let Err(e) = Validate::directory(dir.clone()) {
+                errors.push(ValidationError::Path("Replay directory".to_owned(), e));
+            }
+        }
         errors.to_result()
     }
 }
@@ -1,42 +1,359 @@
 use crate::prelude::*;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{
+    create_dir_all as create_dir_all_sync, read_dir, remove_dir_all as remove_dir_all_sync,
+    remove_file as remove_file_sync, rename as rename_sync, OpenOptions,
+};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::sync::Arc;
+use std::time::Duration;
 
+const CREDENTIALS_DIR: &str = ".credentials";
+const SNAPSHOTS_DIR: &str = ".snapshots";
+const LOCKS_DIR: &str = ".locks";
+const SNAPSHOT_EXTENSION: &str = "yml.gz";
+
+/// Podcasts and their download state in the configured [`PodcastStore`].
+///
+/// Credentials and snapshots are always files in the podcasts directory.
+#[derive(Clone)]
 pub struct PodcastProvider {
     dir: PathBuf,
+    store: Arc<dyn PodcastStore>,
+    lock_timeout: Option<Duration>,
 }
 
 impl PodcastProvider {
     pub(crate) fn new(dir: PathBuf) -> Self {
-        Self { dir }
+        Self {
+            store: Arc::new(YamlStore::new(dir.clone())),
+            dir,
+            lock_timeout: Some(Duration::ZERO),
+        }
+    }
+
+    /// Use the `database` backend from the options.
+    pub(crate) fn from_options(
+        paths: &PathProvider,
+        options: &AppOptions,
+    ) -> Result<Self, DatabaseError> {
+        let kind = options.database.unwrap_or_default();
+        let provider = Self::new(paths.get_podcast_dir());
+        if kind == DatabaseKind::Yaml {
+            return Ok(provider);
+        }
+        let store = kind.open(paths.get_database_path())?;
+        Ok(provider.with_store(store))
+    }
+
+    #[must_use]
+    pub(crate) fn with_store(mut self, store: Arc<dyn PodcastStore>) -> Self {
+        self.store = store;
+        self
+    }
+
+    /// Set how long to wait for a podcast locked by another run, or `None` to wait forever.
+    #[must_use]
+    pub(crate) fn with_lock_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.lock_timeout = timeout;
+        self
+    }
+
+    /// Lock a podcast so concurrent runs can't change it.
+    ///
+    /// The lock is released when dropped.
+    pub(crate) async fn lock(&self, id: &str) -> Result<LockFile, LockError> {
+        LockFile::acquire(self.get_lock_path(id), self.lock_timeout).await
     }
 
+    /// Lock a podcast for a short change, waiting however long another run holds it.
+    ///
+    /// Used by long runs that only lock the podcast around each change.
+    pub(crate) async fn lock_waiting(&self, id: &str) -> Result<LockFile, LockError> {
+        LockFile::acquire(self.get_lock_path(id), None).await
+    }
+
+    fn get_lock_path(&self, id: &str) -> PathBuf {
+        self.dir.join(LOCKS_DIR).join(id).with_extension("lock")
+    }
+
+    /// Get a podcast with the proxy password restored from its credentials.
     pub(crate) fn get(&self, id: &str) -> Result<Podcast, DatabaseError> {
-        let path = self.get_path(id);
+        let mut podcast = self.store.get(id)?;
+        if podcast.proxy.is_some() {
+            let password = self
+                .get_credentials(id)?
+                .and_then(|credentials| credentials.proxy_password);
+            if let Some(password) = password {
+                podcast.restore_proxy_password(&password);
+            }
+        }
+        Ok(podcast)
+    }
+
+    /// Save a podcast with passwords removed from its URLs.
+    ///
+    /// The proxy password is moved to the credentials of the podcast.
+    pub(crate) fn put(&self, podcast: &Podcast) -> Result<(), DatabaseError> {
+        let mut podcast = podcast.clone();
+        if let Some(password) = podcast.take_proxy_password() {
+            let mut credentials = self.get_credentials(&podcast.id)?.unwrap_or_default();
+            if credentials.proxy_password.as_ref() != Some(&password) {
+                credentials.proxy_password = Some(password);
+                self.put_credentials(&podcast.id, &credentials)?;
+            }
+        }
+        if podcast.redact() {
+            warn!(
+                "{} passwords from URLs of podcast: {}",
+                "Redacted".bold(),
+                podcast.id
+            );
+        }
+        self.store.put(&podcast)
+    }
+
+    /// Get a podcast exactly as stored, without restoring the proxy password.
+    pub(crate) fn get_stored(&self, id: &str) -> Result<Podcast, DatabaseError> {
+        self.store.get(id)
+    }
+
+    /// Save a podcast exactly as given, without moving or redacting passwords.
+    pub(crate) fn put_stored(&self, podcast: &Podcast) -> Result<(), DatabaseError> {
+        self.store.put(podcast)
+    }
+
+    /// Remove a podcast with its downloads, credentials and snapshots.
+    pub(crate) fn remove(&self, id: &str) -> Result<(), DatabaseError> {
+        self.store.remove(id)?;
+        let path = self.get_credentials_path(id);
+        if path.exists() {
+            remove_file_sync(&path).map_err(|e| DatabaseError::Io(path, e))?;
+        }
+        let dir = self.get_snapshot_dir(id);
+        if dir.exists() {
+            remove_dir_all_sync(&dir).map_err(|e| DatabaseError::Io(dir, e))?;
+        }
+        Ok(())
+    }
+
+    /// Move a podcast with its downloads, credentials and snapshots to a new ID.
+    ///
+    /// `update` is applied to each download record so the caller can move its path.
+    /// If any step fails the steps already taken are undone.
+    pub(crate) fn rename(
+        &self,
+        old: &str,
+        new: &str,
+        update: impl Fn(&mut DownloadRecord),
+    ) -> Result<Podcast, DatabaseError> {
+        let mut podcast = self.store.get(old)?;
+        podcast.id = new.to_owned();
+        let mut downloads = self.get_downloads(old)?;
+        downloads.iter_mut().for_each(update);
+        let mut moved = Vec::new();
+        let result = self.move_podcast(old, &podcast, &downloads, &mut moved);
+        if result.is_err() {
+            self.undo_move(new, &moved);
+        }
+        result.map(|()| podcast)
+    }
+
+    fn move_podcast(
+        &self,
+        old: &str,
+        podcast: &Podcast,
+        downloads: &[DownloadRecord],
+        moved: &mut Vec<(PathBuf, PathBuf)>,
+    ) -> Result<(), DatabaseError> {
+        let new = &podcast.id;
+        self.store.put(podcast)?;
+        self.put_downloads(new, downloads)?;
+        for (from, to) in [
+            (
+                self.get_credentials_path(old),
+                self.get_credentials_path(new),
+            ),
+            (self.get_snapshot_dir(old), self.get_snapshot_dir(new)),
+        ] {
+            if from.exists() {
+                rename_sync(&from, &to).map_err(|e| DatabaseError::Io(from.clone(), e))?;
+                moved.push((from, to));
+            }
+        }
+        self.store.remove(old)
+    }
+
+    /// Undo a partial move to a new ID.
+    ///
+    /// Failures are logged so the error that caused the undo is the one reported.
+    fn undo_move(&self, new: &str, moved: &[(PathBuf, PathBuf)]) {
+        for (from, to) in moved.iter().rev() {
+            if let Err(e) = rename_sync(to, from) {
+                warn!("{} to move back {}\n{e}", "Failed".bold(), to.display());
+            }
+        }
+        match self.store.remove(new) {
+            Ok(()) | Err(DatabaseError::NotFound(_)) => {}
+            Err(e) => warn!("{} to remove {new}\n{e}", "Failed".bold()),
+        }
+    }
+
+    pub(crate) fn get_all(&self) -> Result<Vec<Podcast>, DatabaseError> {
+        self.get_ids()?.iter().map(|id| self.get(id)).collect()
+    }
+
+    pub(crate) fn get_ids(&self) -> Result<Vec<String>, DatabaseError> {
+        self.store.get_ids()
+    }
+
+    /// Get the downloads recorded for a podcast.
+    pub(crate) fn get_downloads(&self, id: &str) -> Result<Vec<DownloadRecord>, DatabaseError> {
+        self.store.get_downloads(id)
+    }
+
+    /// Record completed downloads of a podcast.
+    pub(crate) fn put_downloads(
+        &self,
+        id: &str,
+        downloads: &[DownloadRecord],
+    ) -> Result<(), DatabaseError> {
+        if downloads.is_empty() {
+            return Ok(());
+        }
+        self.store.put_downloads(id, downloads)
+    }
+
+    /// Get the credentials of a private feed.
+    pub(crate) fn get_credentials(&self, id: &str) -> Result<Option<Credentials>, DatabaseError> {
+        let path = self.get_credentials_path(id);
         if !path.exists() {
-            return Err(DatabaseError::NotFound(id.to_owned()));
+            return Ok(None);
         }
         let file = File::open(&path).map_err(|e| DatabaseError::Io(path.clone(), e))?;
         let reader = BufReader::new(file);
-        serde_yaml::from_reader(reader).map_err(|e| DatabaseError::Deserialization(path.clone(), e))
+        serde_yaml::from_reader(reader)
+            .map(Some)
+            .map_err(|e| DatabaseError::Deserialization(path.clone(), e))
     }
 
-    pub(crate) fn put(&self, podcast: &Podcast) -> Result<(), DatabaseError> {
-        let path = self.get_path(&podcast.id);
-        let file = File::create(&path).map_err(|e| DatabaseError::Io(path.clone(), e))?;
+    /// Save the credentials of a private feed.
+    ///
+    /// Credentials are written to a separate file only readable by the current user.
+    pub(crate) fn put_credentials(
+        &self,
+        id: &str,
+        credentials: &Credentials,
+    ) -> Result<(), DatabaseError> {
+        let path = self.get_credentials_path(id);
+        let dir = self.dir.join(CREDENTIALS_DIR);
+        create_dir_all_sync(&dir).map_err(|e| DatabaseError::Io(dir.clone(), e))?;
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let file = options
+            .open(&path)
+            .map_err(|e| DatabaseError::Io(path.clone(), e))?;
         let writer = BufWriter::new(file);
-        serde_yaml::to_writer(writer, podcast)
+        serde_yaml::to_writer(writer, credentials)
             .map_err(|e| DatabaseError::Serialization(path.clone(), e))
     }
 
-    fn get_path(&self, id: &str) -> PathBuf {
-        self.dir.join(id).with_extension("yml")
+    /// Save a compressed snapshot of the raw source documents of a scrape.
+    ///
+    /// The token of a private feed is replaced before writing. The snapshot is skipped if
+    /// it has the same documents as the latest snapshot, returning `false`.
+    pub(crate) fn put_snapshot(
+        &self,
+        id: &str,
+        snapshot: &Snapshot,
+    ) -> Result<bool, DatabaseError> {
+        let mut snapshot = snapshot.clone();
+        if let Some(credentials) = self.get_credentials(id)? {
+            snapshot.strip_credentials(&credentials);
+        }
+        if let Some(name) = self.get_snapshot_names(id)?.pop() {
+            if self.get_snapshot(id, &name)?.is_same(&snapshot) {
+                return Ok(false);
+            }
+        }
+        let dir = self.get_snapshot_dir(id);
+        create_dir_all_sync(&dir).map_err(|e| DatabaseError::Io(dir.clone(), e))?;
+        let path = self.get_snapshot_path(id, &snapshot.get_name());
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(|e| DatabaseError::Io(path.clone(), e))?;
+        let mut encoder = GzEncoder::new(BufWriter::new(file), Compression::default());
+        serde_yaml::to_writer(&mut encoder, &snapshot)
+            .map_err(|e| DatabaseError::Serialization(path.clone(), e))?;
+        encoder
+            .finish()
+            .map_err(|e| DatabaseError::Io(path.clone(), e))?;
+        Ok(true)
+    }
+
+    /// Get a snapshot by name.
+    pub(crate) fn get_snapshot(&self, id: &str, name: &str) -> Result<Snapshot, DatabaseError> {
+        let path = self.get_snapshot_path(id, name);
+        if !path.exists() {
+            return Err(DatabaseError::NotFound(format!("{id} snapshot {name}")));
+        }
+        let file = File::open(&path).map_err(|e| DatabaseError::Io(path.clone(), e))?;
+        let decoder = GzDecoder::new(BufReader::new(file));
+        serde_yaml::from_reader(decoder)
+            .map_err(|e| DatabaseError::Deserialization(path.clone(), e))
+    }
+
+    /// Get the names of the snapshots of a podcast, oldest first.
+    pub(crate) fn get_snapshot_names(&self, id: &str) -> Result<Vec<String>, DatabaseError> {
+        let dir = self.get_snapshot_dir(id);
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let entries = read_dir(&dir).map_err(|e| DatabaseError::Io(dir.clone(), e))?;
+        let suffix = format!(".{SNAPSHOT_EXTENSION}");
+        let mut names = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|e| DatabaseError::Io(dir.clone(), e))?;
+            let file_name = entry.file_name();
+            if let Some(name) = file_name
+                .to_str()
+                .and_then(|name| name.strip_suffix(&suffix))
+            {
+                names.push(name.to_owned());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Get the directory the snapshots of a podcast are stored in.
+    pub(crate) fn get_snapshot_dir(&self, id: &str) -> PathBuf {
+        self.dir.join(SNAPSHOTS_DIR).join(id)
+    }
+
+    fn get_snapshot_path(&self, id: &str, name: &str) -> PathBuf {
+        self.get_snapshot_dir(id)
+            .join(format!("{name}.{SNAPSHOT_EXTENSION}"))
+    }
+
+    fn get_credentials_path(&self, id: &str) -> PathBuf {
+        self.dir
+            .join(CREDENTIALS_DIR)
+            .join(id)
+            .with_extension("yml")
     }
 }
 
 impl Default for PodcastProvider {
     fn default() -> Self {
-        Self {
-            dir: PathProvider::default().get_podcast_dir(),
-        }
+        Self::new(PathProvider::default().get_podcast_dir())
     }
 }
 
@@ -47,6 +364,10 @@ pub enum DatabaseError {
     Io(PathBuf, std::io::Error),
     Serialization(PathBuf, serde_yaml::Error),
     Deserialization(PathBuf, serde_yaml::Error),
+    Sqlite(PathBuf, rusqlite::Error),
+    Json(String, serde_json::Error),
+    InvalidValue(String, String),
+    Migration(PathBuf, MigrationError),
 }
 
 impl Display for DatabaseError {
@@ -68,6 +389,16 @@ impl Display for DatabaseError {
                     path.display()
                 )
             }
+            DatabaseError::Sqlite(path, e) => {
+                format!("A SQLite error occurred.\nPath: {}\n{e}", path.display())
+            }
+            DatabaseError::Json(id, e) => {
+                format!("A JSON error occurred for podcast: {id}\n{e}")
+            }
+            DatabaseError::InvalidValue(value, e) => format!("Invalid value: {value}\n{e}"),
+            DatabaseError::Migration(path, e) => {
+                format!("Unable to migrate.\nPath: {}\n{e}", path.display())
+            }
         };
         write!(f, "{message}")
     }
@@ -93,4 +424,40 @@ mod tests {
         let result = result.assert_ok();
         assert_eq!(podcast, result);
     }
+
+    #[tokio::test]
+    pub async fn put_keeps_proxy_password_with_credentials() {
+        // Arrange
+        let _ = init_logging();
+        let podcasts = PodcastProvider::new(TempDirectory::create("pura-podcasts"));
+        let (base, requests) =
+            TestServer::start_with_headers("text/plain", b"ok".to_vec(), "").await;
+        let mut podcast = Podcast::example();
+        let mut proxy = base.clone();
+        proxy.set_username("user").assert_ok_debug();
+        proxy.set_password(Some("secret")).assert_ok_debug();
+        podcast.proxy = Some(proxy.clone());
+
+        // Act
+        podcasts.put(&podcast).assert_ok();
+        let stored = podcasts.store.get(&podcast.id).assert_ok();
+        let result = podcasts.get(&podcast.id).assert_ok();
+        let http = HttpClient::new(TempDirectory::create("pura-http"))
+            .for_podcast_proxy(result.proxy.as_ref())
+            .await
+            .assert_ok();
+        let url = Url::parse("http://feeds.invalid/feed").expect("URL should parse");
+        http.get(&url, None).await.assert_ok();
+
+        // Assert
+        assert_eq!(stored.proxy.as_ref().and_then(Url::password), None);
+        assert_eq!(result.proxy, Some(proxy));
+        let requests = requests.lock().expect("lock should not be poisoned");
+        let request = requests
+            .first()
+            .expect("should be a request")
+            .to_lowercase();
+        // `user:secret` encoded as base64
+        assert!(request.contains("proxy-authorization: basic dxnlcjpzzwnyzxq="));
+    }
 }
@@ -1,35 +1,104 @@
 use crate::prelude::*;
+use std::time::Duration;
+use strum_macros::IntoStaticStr;
+
+const IP_CHECK_INTERVAL: u64 = 300;
+const SHUTDOWN_GRACE: u64 = 30;
 
 pub struct ServiceProvider {
     pub options: AppOptions,
     pub paths: PathProvider,
     pub http: HttpClient,
     pub podcasts: PodcastProvider,
+    pub cancellation: Cancellation,
 }
 
 impl ServiceProvider {
     pub async fn create() -> Result<ServiceProvider, ServiceError> {
-        let options = AppOptions::get().map_err(ServiceError::GetConfig)?;
+        Self::create_with(&GlobalOptions::default()).await
+    }
+
+    pub async fn create_with(global: &GlobalOptions) -> Result<ServiceProvider, ServiceError> {
+        let mut options = AppOptions::get().map_err(ServiceError::GetConfig)?;
+        options.apply(global);
+        Self::from_options(options).await
+    }
+
+    pub async fn from_options(options: AppOptions) -> Result<ServiceProvider, ServiceError> {
         options.validate().map_err(ServiceError::ValidateConfig)?;
         let paths = PathProvider::new(options.clone());
-        let http = HttpClient::new(paths.get_http_dir());
-        let ip = IpInfoProvider::new(options.clone(), http.clone());
-        ip.validate().await.map_err(ServiceError::ValidateIp)?;
-        let podcasts = PodcastProvider::new(paths.get_podcast_dir());
+        let backend = HttpBackend::from_options(&options);
+        let headers = match &options.header_profiles {
+            Some(path) => HeaderProfiles::read(path, options.user_agent.clone())
+                .map_err(ServiceError::GetHeaders)?,
+            None => HeaderProfiles::new(Vec::new(), options.user_agent.clone()),
+        };
+        let cancellation = Cancellation::default();
+        let mut http = HttpClient::new(paths.get_http_dir())
+            .with_offline(options.offline)
+            .with_headers(headers)
+            .with_backend(backend.clone())
+            .with_throttle(Throttle::from_options(&options))
+            .with_cancellation(cancellation.clone())
+            .with_lock_timeout(
+                options
+                    .cache_lock_timeout
+                    .unwrap_or(LockTimeout::Forever)
+                    .to_duration(),
+            )
+            .with_proxy(options.proxy.clone())
+            .map_err(ServiceError::CreateHttp)?;
+        if let Some(path) = &options.cookie_jar {
+            let cookies = CookieJar::read(path.clone()).map_err(ServiceError::GetHeaders)?;
+            http = http.with_cookies(cookies);
+        }
+        if options.offline {
+            debug!("{} IP validation in offline mode", "Skipping".bold());
+        } else if !backend.is_network() {
+            debug!(
+                "{} IP validation when replaying fixtures",
+                "Skipping".bold()
+            );
+        } else if options.expect_ip.is_some() || options.expect_country.is_some() {
+            let ip = IpInfoProvider::new(options.clone(), http.clone());
+            ip.validate().await.map_err(ServiceError::ValidateIp)?;
+            let interval = options.ip_check_interval.unwrap_or(IP_CHECK_INTERVAL);
+            let watchdog = IpWatchdog::new(ip, Duration::from_secs(interval));
+            watchdog.start();
+            http = http.with_watchdog(watchdog);
+        }
+        let lock_timeout = options
+            .lock_timeout
+            .unwrap_or(LockTimeout::Seconds(0))
+            .to_duration();
+        let podcasts = PodcastProvider::from_options(&paths, &options)
+            .map_err(ServiceError::OpenDatabase)?
+            .with_lock_timeout(lock_timeout);
         Ok(Self {
             options,
             paths,
             http,
             podcasts,
+            cancellation,
         })
     }
+
+    /// Stop gracefully on Ctrl-C or SIGTERM.
+    pub fn listen_for_signals(&self) {
+        let grace = self.options.shutdown_grace.unwrap_or(SHUTDOWN_GRACE);
+        self.cancellation.listen(Duration::from_secs(grace));
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
 pub enum ServiceError {
     GetConfig(ConfigError),
     ValidateConfig(Vec<ValidationError>),
     ValidateIp(Vec<ValidationError>),
+    CreateHttp(HttpError),
+    GetHeaders(HeaderError),
+    OpenDatabase(DatabaseError),
 }
 
 impl Display for ServiceError {
@@ -38,11 +107,23 @@ impl Display for ServiceError {
             ServiceError::GetConfig(_) => "read config",
             ServiceError::ValidateConfig(_) => "validate config",
             ServiceError::ValidateIp(_) => "validate IP",
+            ServiceError::CreateHttp(_) => "create HTTP client",
+            ServiceError::GetHeaders(_) => "read HTTP headers",
+            ServiceError::OpenDatabase(_) => "open database",
         };
         let reason = match self {
             ServiceError::GetConfig(e) => e.to_string(),
             ServiceError::ValidateConfig(errors) | ServiceError::ValidateIp(errors) => errors.log(),
+            ServiceError::CreateHttp(e) => e.to_string(),
+            ServiceError::GetHeaders(e) => e.to_string(),
+            ServiceError::OpenDatabase(e) => e.to_string(),
         };
         write!(f, "{} to {action}\n{reason}", "Failed".bold())
     }
 }
+
+impl ErrorCode for ServiceError {
+    fn get_code(&self) -> String {
+        format!("service.{}", <&str>::from(self))
+    }
+}
@@ -0,0 +1,61 @@
+use crate::prelude::*;
+use std::collections::BTreeMap;
+use std::io::Error as IoError;
+use std::path::Component;
+use std::sync::{Arc, Mutex};
+use tokio::fs::read;
+
+/// Records the cached responses served by an [`HttpClient`] so they can be
+/// stored as a [`Snapshot`].
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SnapshotRecorder {
+    paths: Arc<Mutex<BTreeMap<String, PathBuf>>>,
+}
+
+impl SnapshotRecorder {
+    /// Record a cached response by its path relative to the cache directory.
+    pub(crate) fn record(&self, dir: &Path, path: &Path) {
+        let Some(key) = get_key(dir, path) else {
+            return;
+        };
+        let mut paths = self.paths.lock().expect("lock should not be poisoned");
+        paths.insert(key, path.to_path_buf());
+    }
+
+    /// Read the recorded responses.
+    pub(crate) async fn read(&self) -> Result<BTreeMap<String, Document>, (PathBuf, IoError)> {
+        let paths = self
+            .paths
+            .lock()
+            .expect("lock should not be poisoned")
+            .clone();
+        let mut documents = BTreeMap::new();
+        for (key, path) in paths {
+            let bytes = read(&path).await.map_err(|e| (path, e))?;
+            documents.insert(key, Document::from_bytes(bytes));
+        }
+        Ok(documents)
+    }
+}
+
+/// Get the `/` separated path of a cached response relative to the cache directory.
+fn get_key(dir: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(dir).ok()?;
+    let segments: Vec<&str> = relative
+        .components()
+        .map(|component| match component {
+            Component::Normal(segment) => segment.to_str(),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    Some(segments.join("/"))
+}
+
+/// Get the path to extract a recorded response to, rejecting keys outside the directory.
+pub(crate) fn get_document_path(dir: &Path, key: &str) -> Option<PathBuf> {
+    let relative = PathBuf::from(key);
+    let is_normal = relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    is_normal.then(|| dir.join(relative))
+}
@@ -0,0 +1,43 @@
+use crate::prelude::*;
+use reqwest::redirect::Policy;
+use std::sync::{Arc, Mutex};
+
+/// Limit to the number of redirects followed by one request, matching reqwest.
+const MAX_REDIRECTS: usize = 10;
+
+/// Records the redirects followed by the requests of an [`HttpClient`].
+///
+/// reqwest only exposes the final URL of a response so the redirect policy of the
+/// client records each hop by the URL first requested.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RedirectLog {
+    hops: Arc<Mutex<HashMap<Url, Vec<Url>>>>,
+}
+
+impl RedirectLog {
+    /// Get a redirect policy that records each redirect before following it.
+    pub(crate) fn policy(&self) -> Policy {
+        let hops = self.hops.clone();
+        Policy::custom(move |attempt| {
+            if attempt.previous().len() > MAX_REDIRECTS {
+                return attempt.error("too many redirects");
+            }
+            if let Some((first, rest)) = attempt.previous().split_first() {
+                let mut chain = rest.to_vec();
+                chain.push(attempt.url().clone());
+                let mut hops = hops.lock().expect("lock should not be poisoned");
+                hops.insert(first.clone(), chain);
+            }
+            attempt.follow()
+        })
+    }
+
+    /// Take the redirects followed by the last request for a URL.
+    ///
+    /// Each URL is where the previous request was redirected, so the last is the
+    /// URL of the response. Empty if the request was not redirected.
+    pub(crate) fn take(&self, url: &Url) -> Vec<Url> {
+        let mut hops = self.hops.lock().expect("lock should not be poisoned");
+        hops.remove(url).unwrap_or_default()
+    }
+}
@@ -0,0 +1,438 @@
+use crate::prelude::*;
+use rusqlite::{params, Connection, Error as SqliteError, OptionalExtension, Transaction};
+use serde_yaml::Value;
+use std::fs::create_dir_all as create_dir_all_sync;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+/// Seconds to wait for another command to release a lock on the database.
+const BUSY_TIMEOUT: u64 = 30;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS podcasts (
+    id TEXT PRIMARY KEY,
+    title TEXT NOT NULL,
+    data TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS episodes (
+    podcast_id TEXT NOT NULL REFERENCES podcasts (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    id TEXT NOT NULL,
+    title TEXT NOT NULL,
+    published_at TEXT NOT NULL,
+    audio_url TEXT NOT NULL,
+    audio_file_size INTEGER NOT NULL,
+    removed_at TEXT,
+    data TEXT NOT NULL,
+    PRIMARY KEY (podcast_id, position)
+);
+CREATE INDEX IF NOT EXISTS episodes_id ON episodes (podcast_id, id);
+CREATE TABLE IF NOT EXISTS episode_history (
+    podcast_id TEXT NOT NULL REFERENCES podcasts (id) ON DELETE CASCADE,
+    episode_position INTEGER NOT NULL,
+    episode_id TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    changed_at TEXT NOT NULL,
+    field TEXT NOT NULL,
+    old TEXT,
+    new TEXT,
+    PRIMARY KEY (podcast_id, episode_position, position)
+);
+CREATE TABLE IF NOT EXISTS downloads (
+    podcast_id TEXT NOT NULL,
+    episode_id TEXT NOT NULL,
+    path TEXT NOT NULL,
+    audio_url TEXT NOT NULL,
+    audio_file_size INTEGER NOT NULL,
+    downloaded_at TEXT NOT NULL,
+    PRIMARY KEY (podcast_id, episode_id)
+);
+";
+
+/// Stores podcasts in a `SQLite` database with tables for podcasts, episodes,
+/// episode history and downloads.
+///
+/// Fields used for queries are columns and the remaining fields are JSON in `data`.
+/// The JSON is migrated to the current schema version when read.
+/// Each write is a transaction so concurrent commands wait rather than overwrite
+/// each other.
+pub(crate) struct SqliteStore {
+    path: PathBuf,
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    /// Open or create the database.
+    pub(crate) fn open(path: PathBuf) -> Result<Self, DatabaseError> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            create_dir_all_sync(dir).map_err(|e| DatabaseError::Io(dir.to_path_buf(), e))?;
+        }
+        let connection = Connection::open(&path).map_err(|e| sqlite_error(&path, e))?;
+        connection
+            .busy_timeout(Duration::from_secs(BUSY_TIMEOUT))
+            .map_err(|e| sqlite_error(&path, e))?;
+        connection
+            .pragma_update(None, "foreign_keys", "ON")
+            .map_err(|e| sqlite_error(&path, e))?;
+        connection
+            .execute_batch(SCHEMA)
+            .map_err(|e| sqlite_error(&path, e))?;
+        Ok(Self {
+            path,
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Connection> {
+        self.connection.lock().expect("lock should not be poisoned")
+    }
+
+    /// Get the JSON documents of the episodes of a podcast in order.
+    fn get_episode_documents(
+        &self,
+        connection: &Connection,
+        id: &str,
+    ) -> Result<Vec<Value>, DatabaseError> {
+        let mut statement = connection
+            .prepare("SELECT data FROM episodes WHERE podcast_id = ?1 ORDER BY position")
+            .map_err(|e| sqlite_error(&self.path, e))?;
+        let rows = statement
+            .query_map([id], |row| row.get::<_, String>(0))
+            .map_err(|e| sqlite_error(&self.path, e))?;
+        let mut episodes = Vec::new();
+        for data in rows {
+            let data = data.map_err(|e| sqlite_error(&self.path, e))?;
+            let episode: Value =
+                serde_json::from_str(&data).map_err(|e| DatabaseError::Json(id.to_owned(), e))?;
+            episodes.push(episode);
+        }
+        Ok(episodes)
+    }
+
+    /// Add the history of each episode by its position.
+    fn add_history(
+        &self,
+        connection: &Connection,
+        id: &str,
+        episodes: &mut [Episode],
+    ) -> Result<(), DatabaseError> {
+        let mut statement = connection
+            .prepare(
+                "SELECT episode_position, changed_at, field, old, new FROM episode_history
+                WHERE podcast_id = ?1 ORDER BY episode_position, position",
+            )
+            .map_err(|e| sqlite_error(&self.path, e))?;
+        let rows = statement
+            .query_map([id], |row| {
+                Ok((
+                    row.get::<_, usize>(0)?,
+                    EpisodeChange {
+                        changed_at: row.get(1)?,
+                        field: row.get(2)?,
+                        old: row.get(3)?,
+                        new: row.get(4)?,
+                    },
+                ))
+            })
+            .map_err(|e| sqlite_error(&self.path, e))?;
+        for row in rows {
+            let (position, change) = row.map_err(|e| sqlite_error(&self.path, e))?;
+            if let Some(episode) = episodes.get_mut(position) {
+                episode.history.push(change);
+            }
+        }
+        Ok(())
+    }
+
+    fn put_episodes(
+        &self,
+        transaction: &Transaction,
+        podcast: &Podcast,
+    ) -> Result<(), DatabaseError> {
+        for table in ["episodes", "episode_history"] {
+            transaction
+                .execute(
+                    &format!("DELETE FROM {table} WHERE podcast_id = ?1"),
+                    [&podcast.id],
+                )
+                .map_err(|e| sqlite_error(&self.path, e))?;
+        }
+        let mut insert_episode = transaction
+            .prepare(
+                "INSERT INTO episodes (podcast_id, position, id, title, published_at, audio_url,
+                audio_file_size, removed_at, data) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            )
+            .map_err(|e| sqlite_error(&self.path, e))?;
+        let mut insert_change = transaction
+            .prepare(
+                "INSERT INTO episode_history (podcast_id, episode_position, episode_id, position,
+                changed_at, field, old, new) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )
+            .map_err(|e| sqlite_error(&self.path, e))?;
+        for (position, episode) in podcast.episodes.iter().enumerate() {
+            let mut data = episode.clone();
+            let history = take(&mut data.history);
+            let data = serde_json::to_string(&data)
+                .map_err(|e| DatabaseError::Json(podcast.id.clone(), e))?;
+            insert_episode
+                .execute(params![
+                    podcast.id,
+                    position,
+                    episode.id,
+                    episode.title,
+                    episode.published_at,
+                    episode.audio_url.as_str(),
+                    episode.audio_file_size,
+                    episode.removed_at,
+                    data
+                ])
+                .map_err(|e| sqlite_error(&self.path, e))?;
+            for (change_position, change) in history.iter().enumerate() {
+                insert_change
+                    .execute(params![
+                        podcast.id,
+                        position,
+                        episode.id,
+                        change_position,
+                        change.changed_at,
+                        change.field,
+                        change.old,
+                        change.new
+                    ])
+                    .map_err(|e| sqlite_error(&self.path, e))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PodcastStore for SqliteStore {
+    fn get(&self, id: &str) -> Result<Podcast, DatabaseError> {
+        let connection = self.lock();
+        let data: Option<String> = connection
+            .query_row("SELECT data FROM podcasts WHERE id = ?1", [id], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map_err(|e| sqlite_error(&self.path, e))?;
+        let data = data.ok_or_else(|| DatabaseError::NotFound(id.to_owned()))?;
+        let mut document: Value =
+            serde_json::from_str(&data).map_err(|e| DatabaseError::Json(id.to_owned(), e))?;
+        let episodes = self.get_episode_documents(&connection, id)?;
+        if let Value::Mapping(mapping) = &mut document {
+            mapping.insert("episodes".into(), Value::Sequence(episodes));
+        }
+        let version =
+            migrate(&mut document).map_err(|e| DatabaseError::Migration(self.path.clone(), e))?;
+        if version != SCHEMA_VERSION {
+            trace!("{} {id} from schema version {version}", "Migrated".bold());
+        }
+        let mut podcast: Podcast = serde_yaml::from_value(document)
+            .map_err(|e| DatabaseError::Deserialization(self.path.clone(), e))?;
+        self.add_history(&connection, id, &mut podcast.episodes)?;
+        Ok(podcast)
+    }
+
+    fn put(&self, podcast: &Podcast) -> Result<(), DatabaseError> {
+        let mut data = podcast.clone();
+        data.episodes = Vec::new();
+        let data =
+            serde_json::to_string(&data).map_err(|e| DatabaseError::Json(podcast.id.clone(), e))?;
+        let mut connection = self.lock();
+        let transaction = connection
+            .transaction()
+            .map_err(|e| sqlite_error(&self.path, e))?;
+        transaction
+            .execute(
+                "INSERT INTO podcasts (id, title, data) VALUES (?1, ?2, ?3)
+                ON CONFLICT (id) DO UPDATE SET title = excluded.title, data = excluded.data",
+                params![podcast.id, podcast.title, data],
+            )
+            .map_err(|e| sqlite_error(&self.path, e))?;
+        self.put_episodes(&transaction, podcast)?;
+        transaction
+            .commit()
+            .map_err(|e| sqlite_error(&self.path, e))
+    }
+
+    fn remove(&self, id: &str) -> Result<(), DatabaseError> {
+        let mut connection = self.lock();
+        let transaction = connection
+            .transaction()
+            .map_err(|e| sqlite_error(&self.path, e))?;
+        for table in ["episode_history", "episodes", "downloads"] {
+            transaction
+                .execute(&format!("DELETE FROM {table} WHERE podcast_id = ?1"), [id])
+                .map_err(|e| sqlite_error(&self.path, e))?;
+        }
+        let count = transaction
+            .execute("DELETE FROM podcasts WHERE id = ?1", [id])
+            .map_err(|e| sqlite_error(&self.path, e))?;
+        if count == 0 {
+            return Err(DatabaseError::NotFound(id.to_owned()));
+        }
+        transaction
+            .commit()
+            .map_err(|e| sqlite_error(&self.path, e))
+    }
+
+    fn get_ids(&self) -> Result<Vec<String>, DatabaseError> {
+        let connection = self.lock();
+        let mut statement = connection
+            .prepare("SELECT id FROM podcasts ORDER BY id")
+            .map_err(|e| sqlite_error(&self.path, e))?;
+        let rows = statement
+            .query_map([], |row| row.get(0))
+            .map_err(|e| sqlite_error(&self.path, e))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| sqlite_error(&self.path, e))
+    }
+
+    fn get_downloads(&self, id: &str) -> Result<Vec<DownloadRecord>, DatabaseError> {
+        let connection = self.lock();
+        let mut statement = connection
+            .prepare(
+                "SELECT episode_id, path, audio_url, audio_file_size, downloaded_at FROM downloads
+                WHERE podcast_id = ?1 ORDER BY downloaded_at, episode_id",
+            )
+            .map_err(|e| sqlite_error(&self.path, e))?;
+        let rows = statement
+            .query_map([id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, u64>(3)?,
+                    row.get::<_, NaiveDateTime>(4)?,
+                ))
+            })
+            .map_err(|e| sqlite_error(&self.path, e))?;
+        let mut downloads = Vec::new();
+        for row in rows {
+            let (episode_id, path, audio_url, audio_file_size, downloaded_at) =
+                row.map_err(|e| sqlite_error(&self.path, e))?;
+            let audio_url = Url::parse(&audio_url)
+                .map_err(|e| DatabaseError::InvalidValue(audio_url.clone(), e.to_string()))?;
+            downloads.push(DownloadRecord {
+                episode_id,
+                path: PathBuf::from(path),
+                audio_url,
+                audio_file_size,
+                downloaded_at,
+            });
+        }
+        Ok(downloads)
+    }
+
+    fn put_downloads(&self, id: &str, downloads: &[DownloadRecord]) -> Result<(), DatabaseError> {
+        let mut connection = self.lock();
+        let transaction = connection
+            .transaction()
+            .map_err(|e| sqlite_error(&self.path, e))?;
+        {
+            let mut statement = transaction
+                .prepare(
+                    "INSERT OR REPLACE INTO downloads (podcast_id, episode_id, path, audio_url,
+                    audio_file_size, downloaded_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                )
+                .map_err(|e| sqlite_error(&self.path, e))?;
+            for download in downloads {
+                statement
+                    .execute(params![
+                        id,
+                        download.episode_id,
+                        download.path.to_string_lossy(),
+                        download.audio_url.as_str(),
+                        download.audio_file_size,
+                        download.downloaded_at
+                    ])
+                    .map_err(|e| sqlite_error(&self.path, e))?;
+            }
+        }
+        transaction
+            .commit()
+            .map_err(|e| sqlite_error(&self.path, e))
+    }
+}
+
+fn sqlite_error(path: &Path, error: SqliteError) -> DatabaseError {
+    DatabaseError::Sqlite(path.to_path_buf(), error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn put_then_get() {
+        // Arrange
+        let path = TempDirectory::create("pura-sqlite").join("podcasts.db");
+        let store = SqliteStore::open(path).assert_ok_debug();
+        let mut podcast = Podcast::example();
+        let mut episode = Episode::example();
+        episode.history.push(EpisodeChange {
+            changed_at: Utc::now().naive_utc(),
+            field: "title".to_owned(),
+            old: None,
+            new: Some(episode.title.clone()),
+        });
+        let mut duplicate = Episode::example();
+        duplicate.history.push(EpisodeChange {
+            changed_at: Utc::now().naive_utc(),
+            field: "audio_file_size".to_owned(),
+            old: None,
+            new: Some(duplicate.audio_file_size.to_string()),
+        });
+        podcast.episodes = vec![episode.clone(), duplicate, Episode::example()];
+        let download = DownloadRecord {
+            episode_id: episode.id.clone(),
+            path: PathBuf::from("output/example.mp3"),
+            audio_url: episode.audio_url.clone(),
+            audio_file_size: episode.audio_file_size,
+            downloaded_at: Utc::now().naive_utc(),
+        };
+
+        // Act
+        store.put(&podcast).assert_ok_debug();
+        store.put(&podcast).assert_ok_debug();
+        store
+            .put_downloads(&podcast.id, &[download.clone(), download.clone()])
+            .assert_ok_debug();
+        let result = store.get(&podcast.id);
+
+        // Assert
+        assert_eq!(result.assert_ok_debug(), podcast);
+        assert_eq!(store.get_ids().assert_ok_debug(), vec![podcast.id.clone()]);
+        assert_eq!(
+            store.get_downloads(&podcast.id).assert_ok_debug(),
+            vec![download]
+        );
+        assert!(matches!(
+            store.get("missing"),
+            Err(DatabaseError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn get_migrates_unversioned() {
+        // Arrange
+        let path = TempDirectory::create("pura-sqlite-migrate").join("podcasts.db");
+        let store = SqliteStore::open(path).assert_ok_debug();
+        let podcast = Podcast::example();
+        store.put(&podcast).assert_ok_debug();
+        store
+            .lock()
+            .execute(
+                "UPDATE podcasts SET data = json_remove(data, '$.schema_version')",
+                [],
+            )
+            .assert_ok_debug();
+
+        // Act
+        let result = store.get(&podcast.id);
+
+        // Assert
+        assert_eq!(result.assert_ok_debug().schema_version, SCHEMA_VERSION);
+    }
+}
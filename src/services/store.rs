@@ -0,0 +1,45 @@
+use crate::prelude::*;
+use std::sync::Arc;
+
+/// Storage backend for podcasts and their download state.
+pub(crate) trait PodcastStore: Send + Sync {
+    fn get(&self, id: &str) -> Result<Podcast, DatabaseError>;
+
+    fn put(&self, podcast: &Podcast) -> Result<(), DatabaseError>;
+
+    /// Remove a podcast and its downloads.
+    fn remove(&self, id: &str) -> Result<(), DatabaseError>;
+
+    /// Get the IDs of every stored podcast in order.
+    fn get_ids(&self) -> Result<Vec<String>, DatabaseError>;
+
+    /// Get the downloads recorded for a podcast.
+    fn get_downloads(&self, id: &str) -> Result<Vec<DownloadRecord>, DatabaseError>;
+
+    /// Record downloads of a podcast, replacing any previous record of the same episode.
+    fn put_downloads(&self, id: &str, downloads: &[DownloadRecord]) -> Result<(), DatabaseError>;
+}
+
+/// Storage backend for the podcast database
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DatabaseKind {
+    /// A YAML file per podcast in the podcasts cache directory
+    #[default]
+    Yaml,
+    /// A `SQLite` database file
+    Sqlite,
+}
+
+impl DatabaseKind {
+    /// Open a store of this kind.
+    ///
+    /// The path is the podcasts directory for YAML and the database file for `SQLite`.
+    pub(crate) fn open(self, path: PathBuf) -> Result<Arc<dyn PodcastStore>, DatabaseError> {
+        let store: Arc<dyn PodcastStore> = match self {
+            DatabaseKind::Yaml => Arc::new(YamlStore::new(path)),
+            DatabaseKind::Sqlite => Arc::new(SqliteStore::open(path)?),
+        };
+        Ok(store)
+    }
+}
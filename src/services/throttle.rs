@@ -0,0 +1,251 @@
+use crate::prelude::*;
+use chrono::{Local, NaiveTime, TimeDelta};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::time::{sleep, sleep_until};
+
+const TIME_FORMAT: &str = "%H:%M";
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+/// Limit the bandwidth and hours of streamed downloads.
+///
+/// Clones share their limits so a cap applies across concurrent downloads.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Throttle {
+    global: Option<RateLimiter>,
+    host_rate: Option<u64>,
+    hosts: Arc<Mutex<HashMap<String, RateLimiter>>>,
+    windows: Vec<DownloadWindow>,
+    clock: Clock,
+}
+
+/// Source of the local time of day that windows are checked against.
+#[derive(Clone, Debug, Default)]
+enum Clock {
+    #[default]
+    Local,
+    /// Time set by a test that moves forward instead of sleeping
+    #[cfg(test)]
+    Fixed(Arc<Mutex<NaiveTime>>),
+}
+
+/// Spread bytes over time so the average rate stays under a cap.
+#[derive(Clone, Debug)]
+struct RateLimiter {
+    rate: u64,
+    next: Arc<Mutex<Instant>>,
+}
+
+/// Local time of day that downloads may run.
+///
+/// A window that ends before it starts runs over midnight.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct DownloadWindow {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl Throttle {
+    /// Create a throttle from a global and per-host rate in bytes per second.
+    pub(crate) fn new(
+        global_rate: Option<u64>,
+        host_rate: Option<u64>,
+        windows: Vec<DownloadWindow>,
+    ) -> Self {
+        Self {
+            global: global_rate.map(RateLimiter::new),
+            host_rate,
+            hosts: Arc::default(),
+            windows,
+            clock: Clock::Local,
+        }
+    }
+
+    /// Check windows against a time set by the test rather than the local time.
+    #[cfg(test)]
+    pub(crate) fn with_fixed_clock(mut self, time: Arc<Mutex<NaiveTime>>) -> Self {
+        self.clock = Clock::Fixed(time);
+        self
+    }
+
+    pub(crate) fn from_options(options: &AppOptions) -> Self {
+        let parse = |value: &Option<String>| value.as_deref().and_then(|v| parse_bytes(v).ok());
+        let windows = options
+            .download_windows
+            .as_deref()
+            .map(DownloadWindow::parse_all)
+            .and_then(Result::ok)
+            .unwrap_or_default();
+        Self::new(
+            parse(&options.max_rate),
+            parse(&options.max_rate_per_host),
+            windows,
+        )
+    }
+
+    /// Wait until a download window is open.
+    pub(crate) async fn wait_for_window(&self) {
+        loop {
+            let now = self.clock.now();
+            let Some(wait) = get_wait(&self.windows, now) else {
+                return;
+            };
+            let opens = now + TimeDelta::from_std(wait).unwrap_or_default();
+            info!(
+                "{} downloads until {}",
+                "Pausing".bold(),
+                opens.format(TIME_FORMAT)
+            );
+            self.clock.sleep(wait).await;
+        }
+    }
+
+    /// Is a download window open, or are there no windows?
+    pub(crate) fn is_open(&self) -> bool {
+        get_wait(&self.windows, self.clock.now()).is_none()
+    }
+
+    /// Wait until a chunk of bytes from a URL fits within the rate limits.
+    pub(crate) async fn consume(&self, url: &Url, bytes: usize) {
+        let bytes = u64::try_from(bytes).unwrap_or(u64::MAX);
+        let mut deadline = self.global.as_ref().map(|limiter| limiter.reserve(bytes));
+        if let (Some(rate), Some(host)) = (self.host_rate, url.host_str()) {
+            let limiter = self
+                .hosts
+                .lock()
+                .expect("lock should not be poisoned")
+                .entry(host.to_owned())
+                .or_insert_with(|| RateLimiter::new(rate))
+                .clone();
+            let host_deadline = limiter.reserve(bytes);
+            deadline = Some(deadline.map_or(host_deadline, |d| d.max(host_deadline)));
+        }
+        if let Some(deadline) = deadline {
+            sleep_until(deadline.into()).await;
+        }
+    }
+}
+
+impl Clock {
+    fn now(&self) -> NaiveTime {
+        match self {
+            Clock::Local => Local::now().time(),
+            #[cfg(test)]
+            Clock::Fixed(time) => *time.lock().expect("lock should not be poisoned"),
+        }
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        match self {
+            Clock::Local => sleep(duration).await,
+            #[cfg(test)]
+            Clock::Fixed(time) => {
+                let mut time = time.lock().expect("lock should not be poisoned");
+                *time += TimeDelta::from_std(duration).unwrap_or_default();
+            }
+        }
+    }
+}
+
+impl RateLimiter {
+    fn new(rate: u64) -> Self {
+        Self {
+            rate: rate.max(1),
+            next: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Reserve time for bytes and get the instant the transfer is within the rate.
+    fn reserve(&self, bytes: u64) -> Instant {
+        let nanos = bytes.saturating_mul(NANOS_PER_SECOND).div_ceil(self.rate);
+        let mut next = self.next.lock().expect("lock should not be poisoned");
+        let start = (*next).max(Instant::now());
+        *next = start + Duration::from_nanos(nanos);
+        *next
+    }
+}
+
+impl DownloadWindow {
+    /// Parse a window such as `01:00-07:00`.
+    pub(crate) fn parse(value: &str) -> Result<Self, StringValidationError> {
+        let unsupported = || StringValidationError::Unsupported(value.to_owned());
+        let (start, end) = value.split_once('-').ok_or_else(unsupported)?;
+        let parse = |time: &str| NaiveTime::parse_from_str(time.trim(), TIME_FORMAT);
+        let start = parse(start).map_err(|_| unsupported())?;
+        let end = parse(end).map_err(|_| unsupported())?;
+        if start == end {
+            return Err(unsupported());
+        }
+        Ok(Self { start, end })
+    }
+
+    /// Parse comma separated windows such as `01:00-07:00,13:00-14:00`.
+    pub(crate) fn parse_all(value: &str) -> Result<Vec<Self>, StringValidationError> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|window| !window.is_empty())
+            .map(Self::parse)
+            .collect()
+    }
+
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    /// Get the time until the window next opens.
+    fn get_wait(&self, time: NaiveTime) -> TimeDelta {
+        let wait = self.start.signed_duration_since(time);
+        if wait < TimeDelta::zero() {
+            wait + TimeDelta::days(1)
+        } else {
+            wait
+        }
+    }
+}
+
+/// Get the time until the first window opens, or `None` if one is open.
+fn get_wait(windows: &[DownloadWindow], time: NaiveTime) -> Option<Duration> {
+    if windows.is_empty() || windows.iter().any(|window| window.contains(time)) {
+        return None;
+    }
+    windows
+        .iter()
+        .map(|window| window.get_wait(time))
+        .min()
+        .and_then(|wait| wait.to_std().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(value: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(value, TIME_FORMAT).expect("time should parse")
+    }
+
+    #[test]
+    fn download_windows() {
+        // Arrange
+        let windows = DownloadWindow::parse_all("01:00-07:00, 22:30-00:30").assert_ok_debug();
+
+        // Act
+        let night = get_wait(&windows, time("23:45"));
+        let morning = get_wait(&windows, time("03:00"));
+        let day = get_wait(&windows, time("12:00"));
+        let evening = get_wait(&windows, time("00:45"));
+
+        // Assert
+        assert_eq!(night, None);
+        assert_eq!(morning, None);
+        assert_eq!(day, Some(Duration::from_mins(10 * 60 + 30)));
+        assert_eq!(evening, Some(Duration::from_mins(15)));
+        assert!(DownloadWindow::parse("07:00-07:00").is_err());
+        assert!(DownloadWindow::parse("7am-9am").is_err());
+    }
+}
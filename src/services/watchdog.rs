@@ -0,0 +1,195 @@
+use crate::prelude::*;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+
+/// Number of attempts to get the external IP before the watchdog trips.
+const CHECK_ATTEMPTS: usize = 3;
+/// Delay between attempts to get the external IP.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Re-check the external IP and country while a command is running.
+///
+/// Once an expectation stops matching the watchdog trips and every
+/// [`HttpClient`] using it refuses further network requests, including
+/// downloads that are already in progress. Failing to get the external IP
+/// is retried and trips the watchdog once every attempt has failed.
+#[derive(Clone, Debug)]
+pub(crate) struct IpWatchdog {
+    ip: Arc<IpInfoProvider>,
+    interval: Duration,
+    retry_delay: Duration,
+    checking: Arc<Mutex<()>>,
+    tripped: Arc<RwLock<Option<String>>>,
+    proxies: Arc<Mutex<HashMap<Url, IpWatchdog>>>,
+}
+
+impl IpWatchdog {
+    pub(crate) fn new(ip: IpInfoProvider, interval: Duration) -> Self {
+        Self {
+            ip: Arc::new(ip),
+            interval,
+            retry_delay: RETRY_DELAY,
+            checking: Arc::default(),
+            tripped: Arc::default(),
+            proxies: Arc::default(),
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn with_retry_delay(mut self, retry_delay: Duration) -> Self {
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    /// Get a watchdog with the same expectations for requests made through a proxy.
+    ///
+    /// Each proxy has a single watchdog that is validated and started on first use,
+    /// then shared by every client of the proxy so background checks don't pile up.
+    pub(crate) async fn for_proxy(
+        &self,
+        proxy: &Url,
+        http: HttpClient,
+    ) -> Result<Self, Vec<ValidationError>> {
+        let mut proxies = self.proxies.lock().await;
+        if let Some(watchdog) = proxies.get(proxy) {
+            return Ok(watchdog.clone());
+        }
+        let mut watchdog = Self::new(self.ip.for_client(http), self.interval);
+        watchdog.retry_delay = self.retry_delay;
+        watchdog.validate().await?;
+        watchdog.start();
+        proxies.insert(proxy.clone(), watchdog.clone());
+        Ok(watchdog)
+    }
+
+    /// Validate the external IP without tripping.
+    pub(crate) async fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        self.ip.validate().await
+    }
+
+    /// Re-check on an interval in the background until the watchdog trips.
+    ///
+    /// A zero interval disables the background check.
+    pub(crate) fn start(&self) {
+        if self.interval.is_zero() {
+            debug!("{} background IP check", "Disabled".bold());
+            return;
+        }
+        let this = self.clone();
+        tokio::spawn(async move {
+            loop {
+                sleep(this.interval).await;
+                if this.check().await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Validate the external IP now and trip if it does not match.
+    ///
+    /// Only one check runs at a time. If the external IP can not be found
+    /// after retrying the watchdog trips as the IP is unknown.
+    pub(crate) async fn check(&self) -> Result<(), String> {
+        let _checking = self.checking.lock().await;
+        self.get_reason()?;
+        trace!("{} external IP", "Checking".bold());
+        let mut attempt = 1;
+        let info = loop {
+            match self.ip.get_info().await {
+                Ok(info) => break info,
+                Err(errors) if attempt < CHECK_ATTEMPTS => {
+                    debug!(
+                        "{} IP check after attempt {attempt} failed{}",
+                        "Retrying".bold(),
+                        errors.log()
+                    );
+                    attempt += 1;
+                    sleep(self.retry_delay).await;
+                }
+                Err(errors) => {
+                    let reason = format!(
+                        "\nThe external IP could not be found after {CHECK_ATTEMPTS} attempts{}",
+                        errors.log()
+                    );
+                    self.trip(reason.clone());
+                    return Err(reason);
+                }
+            }
+        };
+        if let Err(errors) = self.ip.compare(info) {
+            let reason = errors.log();
+            self.trip(reason.clone());
+            return Err(reason);
+        }
+        Ok(())
+    }
+
+    /// Get the reason the watchdog tripped.
+    pub(crate) fn get_reason(&self) -> Result<(), String> {
+        match self
+            .tripped
+            .read()
+            .expect("lock should not be poisoned")
+            .as_ref()
+        {
+            Some(reason) => Err(reason.clone()),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn trip(&self, reason: String) {
+        let mut tripped = self.tripped.write().expect("lock should not be poisoned");
+        if tripped.is_none() {
+            error!(
+                "{} all requests as the IP check failed{reason}",
+                "Aborting".bold()
+            );
+            *tripped = Some(reason);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future::join_all;
+
+    fn create_watchdog(url: Url) -> IpWatchdog {
+        let options = AppOptions {
+            expect_ip: Some("203.0.113.1".to_owned()),
+            ip_providers: Some("text".to_owned()),
+            ip_text_url: Some(url),
+            ..AppOptions::default()
+        };
+        let http = HttpClient::new(TempDirectory::create("pura-watchdog"));
+        IpWatchdog::new(IpInfoProvider::new(options, http), Duration::ZERO)
+            .with_retry_delay(Duration::ZERO)
+    }
+
+    #[tokio::test]
+    async fn check_trips_on_mismatch_or_unknown_ip() {
+        // Arrange
+        let _ = init_logging();
+        let matching =
+            create_watchdog(TestServer::start("text/plain", b"203.0.113.1".to_vec()).await);
+        let invalid = create_watchdog(TestServer::start("text/plain", b"not an ip".to_vec()).await);
+        let changed =
+            create_watchdog(TestServer::start("text/plain", b"198.51.100.1".to_vec()).await);
+
+        // Act
+        let concurrent = join_all((0..4).map(|_| matching.check())).await;
+        let unavailable = invalid.check().await;
+        let mismatched = changed.check().await;
+
+        // Assert
+        assert!(concurrent.iter().all(Result::is_ok));
+        assert!(matching.get_reason().is_ok());
+        assert!(unavailable.is_err());
+        assert!(invalid.get_reason().is_err());
+        assert!(mismatched.is_err());
+        assert!(changed.get_reason().is_err());
+    }
+}
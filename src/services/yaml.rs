@@ -0,0 +1,140 @@
+use crate::prelude::*;
+use serde_yaml::Value;
+use std::fs::{
+    copy as copy_sync, create_dir_all as create_dir_all_sync, read_dir,
+    remove_file as remove_file_sync, rename as rename_sync,
+};
+use std::io::Write;
+
+const DOWNLOADS_DIR: &str = ".downloads";
+
+/// Stores each podcast as `<id>.yml` with its downloads in `.downloads/<id>.yml`.
+#[derive(Clone, Debug)]
+pub(crate) struct YamlStore {
+    dir: PathBuf,
+}
+
+impl YamlStore {
+    pub(crate) fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Rewrite a podcast at the current schema version after copying the original
+    /// to `<id>.v<version>.yml.bak`.
+    ///
+    /// Returns the version migrated from or `None` if the podcast is current.
+    pub(crate) fn migrate(&self, id: &str) -> Result<Option<usize>, DatabaseError> {
+        let path = self.get_path(id);
+        let document: Value = read_yaml(&path)?;
+        let version = get_document_version(&document)
+            .map_err(|e| DatabaseError::Migration(path.clone(), e))?;
+        if version == SCHEMA_VERSION {
+            return Ok(None);
+        }
+        let podcast = self.get(id)?;
+        let backup = path.with_extension(format!("v{version}.yml.bak"));
+        copy_sync(&path, &backup).map_err(|e| DatabaseError::Io(backup.clone(), e))?;
+        self.put(&podcast)?;
+        Ok(Some(version))
+    }
+
+    fn get_path(&self, id: &str) -> PathBuf {
+        self.dir.join(id).with_extension("yml")
+    }
+
+    fn get_downloads_path(&self, id: &str) -> PathBuf {
+        self.dir.join(DOWNLOADS_DIR).join(id).with_extension("yml")
+    }
+}
+
+impl PodcastStore for YamlStore {
+    fn get(&self, id: &str) -> Result<Podcast, DatabaseError> {
+        let path = self.get_path(id);
+        if !path.exists() {
+            return Err(DatabaseError::NotFound(id.to_owned()));
+        }
+        let mut document: Value = read_yaml(&path)?;
+        let version =
+            migrate(&mut document).map_err(|e| DatabaseError::Migration(path.clone(), e))?;
+        if version != SCHEMA_VERSION {
+            trace!("{} {id} from schema version {version}", "Migrated".bold());
+        }
+        serde_yaml::from_value(document).map_err(|e| DatabaseError::Deserialization(path, e))
+    }
+
+    fn put(&self, podcast: &Podcast) -> Result<(), DatabaseError> {
+        write_yaml(&self.get_path(&podcast.id), podcast)
+    }
+
+    fn remove(&self, id: &str) -> Result<(), DatabaseError> {
+        let path = self.get_path(id);
+        if !path.exists() {
+            return Err(DatabaseError::NotFound(id.to_owned()));
+        }
+        remove_file_sync(&path).map_err(|e| DatabaseError::Io(path, e))?;
+        let path = self.get_downloads_path(id);
+        if path.exists() {
+            remove_file_sync(&path).map_err(|e| DatabaseError::Io(path, e))?;
+        }
+        Ok(())
+    }
+
+    fn get_ids(&self) -> Result<Vec<String>, DatabaseError> {
+        let entries = read_dir(&self.dir).map_err(|e| DatabaseError::Io(self.dir.clone(), e))?;
+        let mut ids = Vec::new();
+        for entry in entries {
+            let path = entry
+                .map_err(|e| DatabaseError::Io(self.dir.clone(), e))?
+                .path();
+            if path.extension().is_none_or(|extension| extension != "yml") {
+                continue;
+            }
+            if let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) {
+                ids.push(id.to_owned());
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+
+    fn get_downloads(&self, id: &str) -> Result<Vec<DownloadRecord>, DatabaseError> {
+        let path = self.get_downloads_path(id);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        read_yaml(&path)
+    }
+
+    fn put_downloads(&self, id: &str, downloads: &[DownloadRecord]) -> Result<(), DatabaseError> {
+        let mut records = self.get_downloads(id)?;
+        for download in downloads {
+            records.retain(|record| record.episode_id != download.episode_id);
+            records.push(download.clone());
+        }
+        let dir = self.dir.join(DOWNLOADS_DIR);
+        create_dir_all_sync(&dir).map_err(|e| DatabaseError::Io(dir.clone(), e))?;
+        write_yaml(&self.get_downloads_path(id), &records)
+    }
+}
+
+fn read_yaml<T: DeserializeOwned>(path: &Path) -> Result<T, DatabaseError> {
+    let file = File::open(path).map_err(|e| DatabaseError::Io(path.to_path_buf(), e))?;
+    let reader = BufReader::new(file);
+    serde_yaml::from_reader(reader)
+        .map_err(|e| DatabaseError::Deserialization(path.to_path_buf(), e))
+}
+
+/// Write to a temporary file then rename so an interrupted write can't leave a partial file.
+fn write_yaml<T: Serialize>(path: &Path, value: &T) -> Result<(), DatabaseError> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    let file = File::create(&temp).map_err(|e| DatabaseError::Io(temp.clone(), e))?;
+    let mut writer = BufWriter::new(file);
+    serde_yaml::to_writer(&mut writer, value)
+        .map_err(|e| DatabaseError::Serialization(path.to_path_buf(), e))?;
+    writer
+        .flush()
+        .map_err(|e| DatabaseError::Io(temp.clone(), e))?;
+    rename_sync(&temp, path).map_err(|e| DatabaseError::Io(path.to_path_buf(), e))
+}
@@ -0,0 +1,5 @@
+pub use options::*;
+pub use show::*;
+
+mod options;
+mod show;
@@ -0,0 +1,8 @@
+use crate::prelude::*;
+
+#[derive(Debug, Args)]
+pub struct ShowOptions {
+    /// ID of the podcast
+    #[arg(value_parser = Podcast::validate_id)]
+    pub podcast_id: String,
+}
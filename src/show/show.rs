@@ -0,0 +1,195 @@
+use crate::prelude::*;
+use strum_macros::{AsRefStr, IntoStaticStr};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+pub struct ShowCommand {
+    podcasts: PodcastProvider,
+    paths: PathProvider,
+}
+
+impl ShowCommand {
+    #[must_use]
+    pub fn new(podcasts: PodcastProvider, paths: PathProvider) -> Self {
+        Self { podcasts, paths }
+    }
+
+    /// Log the metadata and episodes of a podcast and return the status of each episode.
+    pub fn execute(&self, options: &ShowOptions) -> Result<Vec<EpisodeStatus>, ShowError> {
+        let podcast = self
+            .podcasts
+            .get(&options.podcast_id)
+            .map_err(ShowError::GetPodcast)?;
+        log_metadata(&podcast);
+        let statuses: Vec<EpisodeStatus> = podcast
+            .episodes
+            .iter()
+            .map(|episode| EpisodeStatus {
+                episode: episode.id.clone(),
+                title: episode.title.clone(),
+                status: self.get_status(&podcast.id, episode),
+            })
+            .collect();
+        for (episode, status) in podcast.episodes.iter().zip(&statuses) {
+            let status = status.status;
+            let number = episode
+                .number
+                .map(|number| number.to_string())
+                .unwrap_or_default();
+            info!(
+                "{} {} {number:>4} {}",
+                episode.published_at.format(DATE_FORMAT),
+                format!("{:<10}", status.as_ref()).bold(),
+                episode.title
+            );
+        }
+        let downloaded = statuses
+            .iter()
+            .filter(|episode| episode.status == DownloadStatus::Downloaded)
+            .count();
+        info!(
+            "{} {downloaded} of {} episodes",
+            "Downloaded".bold(),
+            statuses.len()
+        );
+        Ok(statuses)
+    }
+
+    fn get_status(&self, podcast_id: &str, episode: &Episode) -> DownloadStatus {
+        if self
+            .paths
+            .get_output_path_for_audio(podcast_id, episode)
+            .exists()
+        {
+            DownloadStatus::Downloaded
+        } else if episode.removed_at.is_some() {
+            DownloadStatus::Removed
+        } else {
+            DownloadStatus::Pending
+        }
+    }
+}
+
+fn log_metadata(podcast: &Podcast) {
+    info!("{} {}", podcast.id.bold(), podcast.title);
+    if let Some(author) = &podcast.author {
+        info!("{} {author}", "Author".bold());
+    }
+    info!("{} {}", "Link".bold(), podcast.link);
+    info!("{} {}", "Language".bold(), podcast.language);
+    if let Some(category) = &podcast.category {
+        let sub_category = podcast
+            .sub_category
+            .as_ref()
+            .map(|sub_category| format!(" > {sub_category}"))
+            .unwrap_or_default();
+        info!("{} {category}{sub_category}", "Category".bold());
+    }
+    if let Some(source) = &podcast.source {
+        info!(
+            "{} {} {} at {}",
+            "Source".bold(),
+            source.kind.as_ref(),
+            source.url,
+            source.scraped_at.format("%Y-%m-%d %H:%M")
+        );
+    }
+    if let Some(proxy) = &podcast.proxy {
+        info!("{} {proxy}", "Proxy".bold());
+    }
+}
+
+/// An episode and its download status
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EpisodeStatus {
+    /// ID of the episode
+    pub episode: String,
+    /// Title of the episode
+    pub title: String,
+    /// Download status of the episode
+    pub status: DownloadStatus,
+}
+
+/// Download status of an episode
+#[derive(AsRefStr, Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum DownloadStatus {
+    /// Audio is in the output directory
+    Downloaded,
+    /// Audio has not been downloaded
+    Pending,
+    /// Audio has not been downloaded and the episode is no longer in the source feed
+    Removed,
+}
+
+#[derive(Debug, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum ShowError {
+    GetPodcast(DatabaseError),
+}
+
+impl Display for ShowError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let reason = match self {
+            ShowError::GetPodcast(e) => format!("Unable to get podcast\n{e}"),
+        };
+        write!(f, "{} to show podcast\n{reason}", "Failed".bold())
+    }
+}
+
+impl ErrorCode for ShowError {
+    fn get_code(&self) -> String {
+        format!("show.{}", <&str>::from(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, write};
+
+    #[test]
+    fn show_command() {
+        // Arrange
+        let _ = init_logging();
+        let paths = PathProvider::new(create_temp_options("pura-show"));
+        let podcasts = PodcastProvider::new(paths.get_podcast_dir());
+        let mut podcast = Podcast::example();
+        let downloaded = Episode::example();
+        let mut pending = Episode::example();
+        pending.id = "pending".to_owned();
+        pending.title = "Pending".to_owned();
+        let mut removed = Episode::example();
+        removed.id = "removed".to_owned();
+        removed.title = "Removed".to_owned();
+        removed.removed_at = Some(NaiveDateTime::default());
+        podcast.episodes = vec![downloaded.clone(), pending, removed];
+        podcasts.put(&podcast).assert_ok_debug();
+        let path = paths.get_output_path_for_audio(&podcast.id, &downloaded);
+        create_dir_all(path.parent().expect("path should have a parent")).assert_ok_debug();
+        write(&path, []).assert_ok_debug();
+        let command = ShowCommand::new(podcasts, paths);
+        let options = ShowOptions {
+            podcast_id: podcast.id.clone(),
+        };
+
+        // Act
+        let result = command.execute(&options);
+
+        // Assert
+        let statuses: Vec<DownloadStatus> = result
+            .assert_ok_debug()
+            .iter()
+            .map(|episode| episode.status)
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                DownloadStatus::Downloaded,
+                DownloadStatus::Pending,
+                DownloadStatus::Removed
+            ]
+        );
+    }
+}
@@ -0,0 +1,5 @@
+pub use options::*;
+pub use snapshots::*;
+
+mod options;
+mod snapshots;
@@ -0,0 +1,19 @@
+use crate::prelude::*;
+
+#[derive(Debug, Default, Args)]
+pub struct SnapshotsOptions {
+    /// ID of the podcast
+    #[arg(value_parser = Podcast::validate_id)]
+    pub podcast_id: String,
+    /// Show the lines that differ between two snapshots
+    ///
+    /// Compares with the latest snapshot if only one is given.
+    #[arg(long, num_args = 1..=2, value_names = ["FROM", "TO"], conflicts_with = "rebuild")]
+    pub diff: Vec<String>,
+    /// Rebuild the podcast from a snapshot without any network requests
+    #[arg(long, value_name = "SNAPSHOT")]
+    pub rebuild: Option<String>,
+    /// Save the rebuilt podcast, merging it with the saved podcast
+    #[arg(long, requires = "rebuild")]
+    pub save: bool,
+}
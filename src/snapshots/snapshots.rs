@@ -0,0 +1,319 @@
+use crate::prelude::*;
+use strum_macros::IntoStaticStr;
+use tokio::fs::{remove_dir_all, write};
+
+pub struct SnapshotsCommand {
+    podcasts: PodcastProvider,
+    tracking: TrackingPrefixes,
+}
+
+impl SnapshotsCommand {
+    #[must_use]
+    pub fn new(podcasts: PodcastProvider) -> Self {
+        Self {
+            podcasts,
+            tracking: TrackingPrefixes::default(),
+        }
+    }
+
+    /// Set the tracking prefixes removed from enclosure URLs of a rebuilt podcast.
+    #[must_use]
+    pub fn with_tracking_prefixes(mut self, tracking: TrackingPrefixes) -> Self {
+        self.tracking = tracking;
+        self
+    }
+
+    #[allow(clippy::as_conversions)]
+    pub async fn execute(
+        &self,
+        options: SnapshotsOptions,
+    ) -> Result<SnapshotsReport, SnapshotsError> {
+        let id = &options.podcast_id;
+        if let Some(name) = &options.rebuild {
+            let _lock = self.podcasts.lock(id).await.map_err(SnapshotsError::Lock)?;
+            let mut podcast = self.rebuild(id, name).await?;
+            info!(
+                "{} {} episodes from snapshot {name}",
+                "Rebuilt".bold(),
+                podcast.episodes.len()
+            );
+            let mut changes = None;
+            if options.save {
+                let existing = self.podcasts.get(id).ok();
+                let summary = merge(existing, &mut podcast);
+                self.podcasts.put(&podcast).map_err(SnapshotsError::Save)?;
+                summary.log();
+                changes = Some(summary);
+            }
+            return Ok(SnapshotsReport::Rebuilt {
+                episodes: podcast.episodes.len(),
+                changes,
+            });
+        }
+        if !options.diff.is_empty() {
+            let lines = self.diff(id, &options.diff)?;
+            for line in &lines {
+                info!("{line}");
+            }
+            return Ok(SnapshotsReport::Diff(lines));
+        }
+        let names = self.list(id)?;
+        for name in &names {
+            let snapshot = self.get(id, name)?;
+            let size: usize = snapshot
+                .documents
+                .values()
+                .map(|document| document.as_bytes().len())
+                .sum();
+            info!(
+                "{name} {} documents {}",
+                snapshot.documents.len(),
+                format_bytes(size as u64)
+            );
+        }
+        info!("{} {} snapshots of {id}", "Found".bold(), names.len());
+        Ok(SnapshotsReport::List(names))
+    }
+
+    pub(crate) fn list(&self, id: &str) -> Result<Vec<String>, SnapshotsError> {
+        self.podcasts
+            .get_snapshot_names(id)
+            .map_err(SnapshotsError::GetSnapshot)
+    }
+
+    fn get(&self, id: &str, name: &str) -> Result<Snapshot, SnapshotsError> {
+        self.podcasts
+            .get_snapshot(id, name)
+            .map_err(SnapshotsError::GetSnapshot)
+    }
+
+    /// Get the documents added, removed or changed between two snapshots.
+    ///
+    /// Changed documents are followed by their removed and added lines.
+    pub(crate) fn diff(&self, id: &str, names: &[String]) -> Result<Vec<String>, SnapshotsError> {
+        let from = names.first().ok_or(SnapshotsError::NoSnapshots)?;
+        let to = match names.get(1) {
+            Some(name) => name.clone(),
+            None => self.list(id)?.pop().ok_or(SnapshotsError::NoSnapshots)?,
+        };
+        let from = self.get(id, from)?;
+        let to = self.get(id, &to)?;
+        let mut lines = Vec::new();
+        for (key, before) in &from.documents {
+            match to.documents.get(key) {
+                None => lines.push(format!("{} {key}", "Removed".bold())),
+                Some(after) if after != before => {
+                    lines.push(format!("{} {key}", "Changed".bold()));
+                    lines.append(&mut diff_lines(&before.to_text(), &after.to_text()));
+                }
+                Some(_) => {}
+            }
+        }
+        for key in to.documents.keys() {
+            if !from.documents.contains_key(key) {
+                lines.push(format!("{} {key}", "Added".bold()));
+            }
+        }
+        Ok(lines)
+    }
+
+    /// Rebuild a podcast by replaying the documents of a snapshot through the scraper.
+    pub(crate) async fn rebuild(&self, id: &str, name: &str) -> Result<Podcast, SnapshotsError> {
+        let snapshot = self.get(id, name)?;
+        let source = snapshot
+            .source
+            .clone()
+            .ok_or_else(|| SnapshotsError::NoSource(name.to_owned()))?;
+        let dir = self.podcasts.get_snapshot_dir(id).join(name);
+        extract(&snapshot, &dir).await?;
+        let http = HttpClient::new(dir.clone()).with_offline(true);
+        let scrape = ScrapeCommand::new(http, self.podcasts.clone())
+            .with_tracking_prefixes(self.tracking.clone());
+        let options = ScrapeOptions {
+            podcast_id: id.to_owned(),
+            url: source.url.clone(),
+            proxy: None,
+            username: None,
+            password: None,
+            auth_hosts: Vec::new(),
+            token: None,
+            concurrency: SCRAPE_CONCURRENCY,
+        };
+        let result = scrape.fetch(&options).await;
+        if let Err(e) = remove_dir_all(&dir).await {
+            warn!("{} to remove {}\n{e}", "Failed".bold(), dir.display());
+        }
+        let (mut podcast, _errors) = result.map_err(SnapshotsError::Rebuild)?;
+        let credentials = self
+            .podcasts
+            .get_credentials(id)
+            .map_err(SnapshotsError::GetSnapshot)?
+            .unwrap_or_default();
+        podcast.strip_credentials(&credentials);
+        podcast.source = Some(source);
+        Ok(podcast)
+    }
+}
+
+/// Result of the snapshots command
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotsReport {
+    /// Names of the snapshots, oldest first
+    List(Vec<String>),
+    /// Documents and lines that differ between two snapshots
+    Diff(Vec<String>),
+    /// Podcast rebuilt from a snapshot
+    Rebuilt {
+        /// Number of rebuilt episodes
+        episodes: usize,
+        /// Changes to episodes if the rebuilt podcast was saved
+        changes: Option<MergeSummary>,
+    },
+}
+
+/// Write the documents of a snapshot to a directory laid out like the HTTP cache.
+async fn extract(snapshot: &Snapshot, dir: &Path) -> Result<(), SnapshotsError> {
+    for (key, body) in &snapshot.documents {
+        let Some(path) = get_document_path(dir, key) else {
+            warn!("{} document outside the cache: {key}", "Skipping".bold());
+            continue;
+        };
+        create_parent_dir_if_not_exist(&path)
+            .await
+            .map_err(|e| SnapshotsError::Io(path.clone(), e))?;
+        write(&path, body.as_bytes())
+            .await
+            .map_err(|e| SnapshotsError::Io(path.clone(), e))?;
+    }
+    Ok(())
+}
+
+/// Get the lines only in `before` prefixed with `-` and the lines only in `after` prefixed with `+`.
+fn diff_lines(before: &str, after: &str) -> Vec<String> {
+    let removed = get_unmatched(before, after)
+        .into_iter()
+        .map(|line| format!("- {line}"));
+    let added = get_unmatched(after, before)
+        .into_iter()
+        .map(|line| format!("+ {line}"));
+    removed.chain(added).collect()
+}
+
+/// Get the lines of `a` without a matching line in `b`, in order.
+fn get_unmatched<'a>(a: &'a str, b: &str) -> Vec<&'a str> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for line in b.lines() {
+        *counts.entry(line.trim()).or_default() += 1;
+    }
+    a.lines()
+        .filter(|line| match counts.get_mut(line.trim()) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                false
+            }
+            _ => !line.trim().is_empty(),
+        })
+        .map(str::trim)
+        .collect()
+}
+
+#[allow(clippy::absolute_paths)]
+#[derive(Debug, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum SnapshotsError {
+    GetSnapshot(DatabaseError),
+    NoSnapshots,
+    NoSource(String),
+    Io(PathBuf, std::io::Error),
+    Rebuild(ScrapeError),
+    Save(DatabaseError),
+    Lock(LockError),
+}
+
+impl Display for SnapshotsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let reason = match self {
+            SnapshotsError::GetSnapshot(e) => format!("Unable to get snapshot\n{e}"),
+            SnapshotsError::NoSnapshots => "Podcast has no snapshots".to_owned(),
+            SnapshotsError::NoSource(name) => {
+                format!("Snapshot does not record its source: {name}")
+            }
+            SnapshotsError::Io(path, e) => {
+                format!("An I/O error occurred.\nPath: {}\n{e}", path.display())
+            }
+            SnapshotsError::Rebuild(e) => format!("Unable to rebuild podcast\n{e}"),
+            SnapshotsError::Save(e) => format!("Unable to save: {e}"),
+            SnapshotsError::Lock(e) => format!("Unable to lock podcast\n{e}"),
+        };
+        write!(f, "{} to process snapshots\n{reason}", "Failed".bold())
+    }
+}
+
+impl ErrorCode for SnapshotsError {
+    fn get_code(&self) -> String {
+        format!("snapshots.{}", <&str>::from(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    #[tokio::test]
+    pub async fn snapshots_command_replay() {
+        // Arrange
+        let _ = init_logging();
+        let services = create_fixture_services("pura-snapshots").await;
+        let scraped = scrape_fixture(&services).await;
+        let command = SnapshotsCommand::new(services.podcasts.clone());
+        let names = command.list(FIXTURE_PODCAST_ID).assert_ok_debug();
+        let name = names
+            .first()
+            .expect("scrape should save a snapshot")
+            .clone();
+        let mut changed = command.get(FIXTURE_PODCAST_ID, &name).assert_ok_debug();
+        changed.taken_at += TimeDelta::hours(1);
+        let (key, body) = changed
+            .documents
+            .iter_mut()
+            .find(|(key, _)| Path::new(key).extension() == Some(RSS_EXTENSION.as_ref()))
+            .expect("snapshot should contain the feed");
+        let key = key.clone();
+        *body = Document::Text(body.to_text().replacen("<title>", "<title>Edited ", 1));
+        let unchanged = command.get(FIXTURE_PODCAST_ID, &name).assert_ok_debug();
+        let saved_unchanged = services
+            .podcasts
+            .put_snapshot(FIXTURE_PODCAST_ID, &unchanged)
+            .assert_ok_debug();
+        let saved_changed = services
+            .podcasts
+            .put_snapshot(FIXTURE_PODCAST_ID, &changed)
+            .assert_ok_debug();
+
+        // Act
+        let rebuilt = command.rebuild(FIXTURE_PODCAST_ID, &name).await;
+        let diff = command.diff(FIXTURE_PODCAST_ID, &[name]);
+
+        // Assert
+        assert!(!saved_unchanged);
+        assert!(saved_changed);
+        let rebuilt = rebuilt.assert_ok_debug();
+        assert_eq!(rebuilt.title, scraped.title);
+        assert_eq!(rebuilt.episodes.len(), scraped.episodes.len());
+        assert_eq!(rebuilt.source, scraped.source);
+        let diff = diff.assert_ok_debug();
+        assert!(diff.first().is_some_and(|line| line.ends_with(&key)));
+        assert!(diff.iter().any(|line| line.contains("+ <title>Edited ")));
+    }
+
+    #[test]
+    fn diff_lines_unordered() {
+        // Act
+        let lines = diff_lines("a\nb\nc\nb", "b\na\nd\nb");
+
+        // Assert
+        assert_eq!(lines, vec!["- c", "+ d"]);
+    }
+}
@@ -0,0 +1,74 @@
+const UNITS: [(&str, u64); 9] = [
+    ("TIB", 1024 * 1024 * 1024 * 1024),
+    ("GIB", 1024 * 1024 * 1024),
+    ("MIB", 1024 * 1024),
+    ("KIB", 1024),
+    ("TB", 1_000_000_000_000),
+    ("GB", 1_000_000_000),
+    ("MB", 1_000_000),
+    ("KB", 1_000),
+    ("B", 1),
+];
+
+/// Parse a human readable size such as `500MB`, `20 GB` or `1GiB` into bytes.
+///
+/// A value without a unit is interpreted as bytes.
+pub(crate) fn parse_bytes(value: &str) -> Result<u64, String> {
+    let value = value.trim().to_uppercase();
+    let (number, multiplier) = UNITS
+        .iter()
+        .find_map(|(unit, multiplier)| {
+            value
+                .strip_suffix(unit)
+                .map(|number| (number.trim().to_owned(), *multiplier))
+        })
+        .unwrap_or((value.clone(), 1));
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid size: {value}"))?;
+    if number.is_sign_negative() || !number.is_finite() {
+        return Err(format!("Invalid size: {value}"));
+    }
+    #[allow(
+        clippy::as_conversions,
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// Format bytes as a human readable decimal size such as `1.50 GB`.
+#[allow(clippy::as_conversions, clippy::cast_precision_loss)]
+pub(crate) fn format_bytes(bytes: u64) -> String {
+    for (unit, multiplier) in UNITS.iter().skip(4) {
+        if bytes >= *multiplier && *multiplier > 1 {
+            return format!("{:.2} {unit}", bytes as f64 / *multiplier as f64);
+        }
+    }
+    format!("{bytes} B")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(parse_bytes("123"), Ok(123));
+        assert_eq!(parse_bytes("2KB"), Ok(2_000));
+        assert_eq!(parse_bytes("1.5 mb"), Ok(1_500_000));
+        assert_eq!(parse_bytes("20GB"), Ok(20_000_000_000));
+        assert_eq!(parse_bytes("1GiB"), Ok(1_073_741_824));
+        assert!(parse_bytes("abc").is_err());
+        assert!(parse_bytes("-1MB").is_err());
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(999), "999 B");
+        assert_eq!(format_bytes(1_500), "1.50 KB");
+        assert_eq!(format_bytes(20_000_000_000), "20.00 GB");
+    }
+}
@@ -0,0 +1,80 @@
+#![allow(dead_code)]
+use crate::prelude::*;
+use std::fs::create_dir_all;
+
+pub(crate) const FIXTURE_PODCAST_ID: &str = "fixture";
+pub(crate) const FIXTURE_FEED_URL: &str = "https://feeds.example.com/fixture";
+pub(crate) const SIMPLECAST_FIXTURE_PODCAST_ID: &str = "simplecast";
+pub(crate) const SIMPLECAST_FIXTURE_URL: &str = "https://simplecast.example.com/";
+pub(crate) const SIMPLECAST_FIXTURE_FEED_URL: &str = "https://feeds.simplecast.com/fixture";
+
+/// Get the directory of checked-in HTTP fixtures.
+pub(crate) fn get_fixtures_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures")
+        .join("http")
+}
+
+/// Create isolated options in a temporary directory.
+pub(crate) fn create_temp_options(sub_dir_name: &str) -> AppOptions {
+    let dir = TempDirectory::create(sub_dir_name);
+    let cache_dir = dir.join("cache");
+    let output_dir = dir.join("output");
+    for dir in [
+        cache_dir.join("http"),
+        cache_dir.join("podcasts"),
+        output_dir.clone(),
+    ] {
+        create_dir_all(dir).expect("should be able to create temp dir");
+    }
+    AppOptions {
+        cache_dir: Some(cache_dir),
+        output_dir: Some(output_dir),
+        ..AppOptions::default()
+    }
+}
+
+/// Create services in a temporary directory that replay the checked-in HTTP fixtures.
+pub(crate) async fn create_fixture_services(sub_dir_name: &str) -> ServiceProvider {
+    let options = AppOptions {
+        replay_dir: Some(get_fixtures_dir()),
+        ..create_temp_options(sub_dir_name)
+    };
+    ServiceProvider::from_options(options)
+        .await
+        .expect("ServiceProvider should not fail")
+}
+
+/// Scrape the fixture podcast.
+pub(crate) async fn scrape_fixture(services: &ServiceProvider) -> Podcast {
+    scrape_fixture_from(services, FIXTURE_PODCAST_ID, FIXTURE_FEED_URL).await
+}
+
+/// Scrape the Simplecast fixture podcast from its page and the Simplecast API.
+pub(crate) async fn scrape_simplecast_fixture(services: &ServiceProvider) -> Podcast {
+    scrape_fixture_from(
+        services,
+        SIMPLECAST_FIXTURE_PODCAST_ID,
+        SIMPLECAST_FIXTURE_URL,
+    )
+    .await
+}
+
+async fn scrape_fixture_from(services: &ServiceProvider, podcast_id: &str, url: &str) -> Podcast {
+    let command = ScrapeCommand::new(services.http.clone(), services.podcasts.clone());
+    let options = ScrapeOptions {
+        podcast_id: podcast_id.to_owned(),
+        url: Url::parse(url).expect("URL should parse"),
+        proxy: None,
+        username: None,
+        password: None,
+        auth_hosts: Vec::new(),
+        token: None,
+        concurrency: SCRAPE_CONCURRENCY,
+    };
+    command
+        .execute(options)
+        .await
+        .expect("scrape fixture should not fail")
+        .podcast
+}
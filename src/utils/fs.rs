@@ -1,4 +1,5 @@
 use crate::prelude::*;
+use std::fs::read_dir;
 use std::io::Error;
 
 pub async fn create_parent_dir_if_not_exist(path: &Path) -> Result<(), Error> {
@@ -9,3 +10,23 @@ pub async fn create_parent_dir_if_not_exist(path: &Path) -> Result<(), Error> {
     }
     Ok(())
 }
+
+/// Get the total size in bytes of the files in a directory and its subdirectories.
+///
+/// Entries that can't be read are skipped.
+pub(crate) fn get_dir_size(dir: &Path) -> u64 {
+    let Ok(entries) = read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            if metadata.is_dir() {
+                Some(get_dir_size(&entry.path()))
+            } else {
+                Some(metadata.len())
+            }
+        })
+        .sum()
+}
@@ -1,15 +1,41 @@
+use crate::utils::suspend_progress;
+use colored::control::set_override;
+use log::{set_boxed_logger, set_max_level, LevelFilter, Log, Metadata, Record};
 use rogue_logging::Verbosity::*;
 use rogue_logging::{Logger, LoggerBuilder};
 use std::sync::Arc;
 
 #[must_use]
 pub fn init_logging() -> Arc<Logger> {
-    LoggerBuilder::new()
+    let logger = LoggerBuilder::new()
         .with_exclude_filter("reqwest".to_owned())
         .with_exclude_filter("cookie".to_owned())
         .with_exclude_filter("html5ever".to_owned())
         .with_exclude_filter("lofty".to_owned())
         .with_exclude_filter("selectors".to_owned())
         .with_verbosity(Debug)
-        .create()
+        .without_init()
+        .create();
+    set_override(true);
+    if set_boxed_logger(Box::new(ProgressLogger(logger.clone()))).is_ok() {
+        set_max_level(LevelFilter::Debug);
+    }
+    logger
+}
+
+/// Log through the progress bars so lines are printed above them rather than through them.
+struct ProgressLogger(Arc<Logger>);
+
+impl Log for ProgressLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        suspend_progress(|| self.0.log(record));
+    }
+
+    fn flush(&self) {
+        self.0.flush();
+    }
 }
@@ -1,10 +1,16 @@
 #[cfg(test)]
 pub(crate) use assertions::*;
+pub(crate) use bytes::*;
+#[cfg(test)]
+pub(crate) use fixtures::*;
 pub(crate) use fs::*;
 pub use logging::*;
 pub(crate) use progress::*;
+pub(crate) use redact::*;
 pub(crate) use resize::*;
 pub(crate) use sanitizer::*;
+#[cfg(test)]
+pub(crate) use server::*;
 pub(crate) use tag::*;
 #[cfg(test)]
 #[allow(unused_imports)]
@@ -13,11 +19,17 @@ pub(crate) use url::*;
 pub(crate) use validation::*;
 #[cfg(test)]
 mod assertions;
+mod bytes;
+#[cfg(test)]
+mod fixtures;
 mod fs;
 mod logging;
 mod progress;
+mod redact;
 mod resize;
 mod sanitizer;
+#[cfg(test)]
+mod server;
 mod tag;
 #[cfg(test)]
 mod temp;
@@ -1,26 +1,164 @@
-use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use crate::prelude::*;
+use indicatif::{
+    HumanBytes, HumanDuration, MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle,
+};
 use rogue_logging::Colors;
+use std::io::{stderr, IsTerminal};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+/// Interval between progress log lines when stderr is not a terminal.
+const LOG_INTERVAL: Duration = Duration::from_secs(30);
+const NAME_WIDTH: usize = 30;
+
+/// Bars currently drawn to the terminal so log lines can be printed above them.
+static ACTIVE: Mutex<Option<MultiProgress>> = Mutex::new(None);
+
+/// Progress of a batch of items with a bar for each download in flight.
+///
+/// If stderr is not a terminal the bars are hidden and progress is logged periodically.
 #[derive(Clone, Debug)]
 pub struct Progress {
+    multi: MultiProgress,
+    bar: ProgressBar,
+    is_terminal: bool,
+    logged_at: Arc<Mutex<Instant>>,
+}
+
+/// Progress of the bytes of a single download.
+///
+/// The bar is removed when dropped.
+pub(crate) struct DownloadProgress {
     bar: ProgressBar,
+    name: String,
+    is_terminal: bool,
+    logged_at: Instant,
 }
 
 impl Progress {
     pub(crate) fn new(length: u64) -> Progress {
-        let bar = ProgressBar::new(length);
-        bar.set_draw_target(ProgressDrawTarget::stderr());
+        let is_terminal = stderr().is_terminal();
+        let target = if is_terminal {
+            ProgressDrawTarget::stderr()
+        } else {
+            ProgressDrawTarget::hidden()
+        };
+        let multi = MultiProgress::with_draw_target(target);
+        let bar = multi.add(ProgressBar::new(length));
         bar.set_style(create_progress_style());
-        Self { bar }
+        if is_terminal {
+            *ACTIVE.lock().expect("lock should not be poisoned") = Some(multi.clone());
+        }
+        Self {
+            multi,
+            bar,
+            is_terminal,
+            logged_at: Arc::new(Mutex::new(Instant::now())),
+        }
     }
 
     pub(crate) fn update(&self) {
         self.bar.inc(1);
+        if self.is_terminal
+            || !is_due(&mut self.logged_at.lock().expect("lock should not be poisoned"))
+        {
+            return;
+        }
+        info!(
+            "{} {}/{} {} elapsed, {} remain",
+            "Progress".bold(),
+            self.bar.position(),
+            self.bar.length().unwrap_or_default(),
+            HumanDuration(self.bar.elapsed()),
+            HumanDuration(self.bar.eta())
+        );
     }
 
     pub(crate) fn finish(&self) {
         self.bar.finish();
+        ACTIVE.lock().expect("lock should not be poisoned").take();
+    }
+
+    /// Add a bar for the bytes of a download.
+    pub(crate) fn add_download(&self, name: &str, length: Option<u64>) -> DownloadProgress {
+        let bar = match length {
+            Some(length) => ProgressBar::new(length).with_style(create_bytes_style()),
+            None => ProgressBar::no_length().with_style(create_unknown_bytes_style()),
+        };
+        let bar = self.multi.add(bar);
+        bar.set_message(truncate(name));
+        DownloadProgress {
+            bar,
+            name: name.to_owned(),
+            is_terminal: self.is_terminal,
+            logged_at: Instant::now(),
+        }
+    }
+}
+
+impl DownloadProgress {
+    pub(crate) fn update(&mut self, bytes: usize) {
+        self.bar.inc(u64::try_from(bytes).unwrap_or(u64::MAX));
+        if self.is_terminal || !is_due(&mut self.logged_at) {
+            return;
+        }
+        let total = self
+            .bar
+            .length()
+            .map(|length| format!("/{}", HumanBytes(length)))
+            .unwrap_or_default();
+        info!(
+            "{} {} {}{total} at {}/s, {} remain",
+            "Downloading".bold(),
+            self.name,
+            HumanBytes(self.bar.position()),
+            HumanBytes(self.get_rate()),
+            HumanDuration(self.bar.eta())
+        );
+    }
+
+    /// Get the average bytes per second.
+    fn get_rate(&self) -> u64 {
+        let position = self.bar.position();
+        position
+            .checked_div(self.bar.elapsed().as_secs())
+            .unwrap_or(position)
+    }
+}
+
+impl Drop for DownloadProgress {
+    fn drop(&mut self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+/// Run a function that writes to stderr with the active bars hidden.
+///
+/// Without active bars the function runs directly.
+pub(crate) fn suspend_progress<R>(f: impl FnOnce() -> R) -> R {
+    let multi = ACTIVE.lock().expect("lock should not be poisoned").clone();
+    match multi {
+        Some(multi) => multi.suspend(f),
+        None => f(),
+    }
+}
+
+/// Is a log line due, resetting the time if so?
+fn is_due(logged_at: &mut Instant) -> bool {
+    if logged_at.elapsed() < LOG_INTERVAL {
+        return false;
     }
+    *logged_at = Instant::now();
+    true
+}
+
+fn truncate(name: &str) -> String {
+    if name.chars().count() <= NAME_WIDTH {
+        return name.to_owned();
+    }
+    let mut name: String = name.chars().take(NAME_WIDTH - 1).collect();
+    name.push('…');
+    name
 }
 
 fn create_progress_style() -> ProgressStyle {
@@ -33,3 +171,47 @@ fn create_progress_style() -> ProgressStyle {
         .template(&template)
         .expect("Progress style should compile")
 }
+
+fn create_bytes_style() -> ProgressStyle {
+    let template = format!(
+        "{{bar:50}} {{msg:{NAME_WIDTH}}} {}",
+        "{bytes:>10}/{total_bytes} {bytes_per_sec}, {eta} remain".gray()
+    );
+    ProgressStyle::default_bar()
+        .template(&template)
+        .expect("Progress style should compile")
+}
+
+fn create_unknown_bytes_style() -> ProgressStyle {
+    let template = format!(
+        "{{spinner}} {{msg:{NAME_WIDTH}}} {}",
+        "{bytes:>10} {bytes_per_sec}".gray()
+    );
+    ProgressStyle::default_spinner()
+        .template(&template)
+        .expect("Progress style should compile")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_downloads() {
+        // Arrange
+        let progress = Progress::new(2);
+
+        // Act
+        let mut known = progress.add_download("episode-1.mp3", Some(100));
+        let mut unknown = progress.add_download(&"a".repeat(40), None);
+        known.update(60);
+        unknown.update(10);
+        progress.update();
+
+        // Assert
+        assert_eq!(known.bar.position(), 60);
+        assert_eq!(unknown.bar.length(), None);
+        assert_eq!(unknown.bar.message().chars().count(), NAME_WIDTH);
+        assert_eq!(progress.bar.position(), 1);
+    }
+}
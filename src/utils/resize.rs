@@ -156,11 +156,11 @@ mod tests {
     use super::*;
 
     #[tokio::test]
-    #[ignore = "uses httpbin.org"]
     pub async fn resize_jpeg() {
         // Arrange
         let _ = init_logging();
-        let http = HttpClient::default();
+        let http = HttpClient::new(TempDirectory::create("pura-resize"))
+            .with_backend(HttpBackend::Replay(get_fixtures_dir()));
         let formats = vec!["jpeg", "png", "webp"];
         for format in formats {
             eprintln!("format: {format}");
@@ -0,0 +1,143 @@
+#![allow(dead_code)]
+use crate::prelude::*;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpListener;
+use tokio::time::sleep;
+
+/// Head of each request received by a [`TestServer`].
+pub(crate) type TestRequests = Arc<Mutex<Vec<String>>>;
+
+/// A local HTTP server that responds to every request with the same body.
+///
+/// A request with a `Range` from an offset is answered with the rest of the body.
+pub(crate) struct TestServer;
+
+impl TestServer {
+    /// Start the server and get its base URL.
+    pub(crate) async fn start(content_type: &str, body: Vec<u8>) -> Url {
+        Self::start_with_headers(content_type, body, "").await.0
+    }
+
+    /// Start the server with additional response headers.
+    ///
+    /// Each header must end with `\r\n`.
+    pub(crate) async fn start_with_headers(
+        content_type: &str,
+        body: Vec<u8>,
+        headers: &str,
+    ) -> (Url, TestRequests) {
+        Self::start_with_status(content_type, body, headers, |_| 200).await
+    }
+
+    /// Start the server with the response status chosen from the head of each request.
+    ///
+    /// The body is only sent with a success status.
+    pub(crate) async fn start_with_status(
+        content_type: &str,
+        body: Vec<u8>,
+        headers: &str,
+        status: fn(&str) -> u16,
+    ) -> (Url, TestRequests) {
+        Self::spawn(content_type, body, headers, status, None).await
+    }
+
+    /// Start the server, pausing halfway through each body so it arrives in two chunks.
+    pub(crate) async fn start_with_pause(
+        content_type: &str,
+        body: Vec<u8>,
+        pause: Duration,
+    ) -> (Url, TestRequests) {
+        Self::spawn(content_type, body, "", |_| 200, Some(pause)).await
+    }
+
+    async fn spawn(
+        content_type: &str,
+        body: Vec<u8>,
+        headers: &str,
+        status: fn(&str) -> u16,
+        pause: Option<Duration>,
+    ) -> (Url, TestRequests) {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("should bind test server");
+        let address = listener.local_addr().expect("should have local address");
+        let content_type = content_type.to_owned();
+        let headers = headers.to_owned();
+        let requests = TestRequests::default();
+        let received = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let content_type = content_type.clone();
+                let body = body.clone();
+                let headers = headers.clone();
+                let received = received.clone();
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buffer = [0; 1024];
+                    while !request.ends_with(b"\r\n\r\n") {
+                        match stream.read(&mut buffer).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(count) => {
+                                let bytes = buffer.get(..count).unwrap_or_default();
+                                request.extend_from_slice(bytes);
+                            }
+                        }
+                    }
+                    let request = String::from_utf8_lossy(&request).into_owned();
+                    received
+                        .lock()
+                        .expect("lock should not be poisoned")
+                        .push(request.clone());
+                    let mut status = status(&request);
+                    let is_success = (200..300).contains(&status);
+                    let mut headers = headers.clone();
+                    let mut body = body.as_slice();
+                    if let Some(offset) = get_range_start(&request).filter(|_| is_success) {
+                        status = 206;
+                        headers = format!(
+                            "{headers}Content-Range: bytes {offset}-{}/{}\r\n",
+                            body.len().saturating_sub(1),
+                            body.len()
+                        );
+                        body = body.get(offset..).unwrap_or_default();
+                    }
+                    let length = if is_success { body.len() } else { 0 };
+                    let head = format!(
+                        "HTTP/1.1 {status} Test\r\nContent-Type: {content_type}\r\nContent-Length: {length}\r\n{headers}Connection: close\r\n\r\n"
+                    );
+                    let _ = stream.write_all(head.as_bytes()).await;
+                    if is_success && !request.starts_with("HEAD") {
+                        let (first, rest) = body.split_at(body.len() >> 1);
+                        let _ = stream.write_all(first).await;
+                        let _ = stream.flush().await;
+                        if let Some(pause) = pause {
+                            sleep(pause).await;
+                        }
+                        let _ = stream.write_all(rest).await;
+                    }
+                    let _ = stream.flush().await;
+                });
+            }
+        });
+        let url = Url::parse(&format!("http://{address}/")).expect("URL should be valid");
+        (url, requests)
+    }
+}
+
+/// Get the start of a `Range: bytes=<start>-` request header.
+fn get_range_start(request: &str) -> Option<usize> {
+    request.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if !name.eq_ignore_ascii_case("range") {
+            return None;
+        }
+        value
+            .trim()
+            .strip_prefix("bytes=")?
+            .strip_suffix('-')?
+            .parse()
+            .ok()
+    })
+}
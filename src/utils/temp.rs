@@ -2,8 +2,11 @@
 use std::env::temp_dir;
 use std::fs::create_dir_all;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::SystemTime;
 
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
 pub struct TempDirectory;
 
 impl TempDirectory {
@@ -12,9 +15,11 @@ impl TempDirectory {
         let timestamp = SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
             .expect("Duration should be valid")
-            .as_millis()
-            .to_string();
-        temp_dir().join(sub_dir_name).join(timestamp)
+            .as_millis();
+        let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+        temp_dir()
+            .join(sub_dir_name)
+            .join(format!("{timestamp}-{count}"))
     }
 
     #[must_use]
@@ -13,6 +13,20 @@ impl Validate {
         Ok(())
     }
 
+    pub(crate) fn proxy(url: &Url) -> Result<(), StringValidationError> {
+        match url.scheme() {
+            "http" | "https" | "socks5" | "socks5h" => Ok(()),
+            scheme => Err(StringValidationError::Unsupported(scheme.to_owned())),
+        }
+    }
+
+    /// Parse a proxy URL from a command line argument.
+    pub(crate) fn parse_proxy(value: &str) -> Result<Url, String> {
+        let url = Url::parse(value).map_err(|e| e.to_string())?;
+        Validate::proxy(&url).map_err(|e| format!("Proxy scheme {}", e.log()))?;
+        Ok(url)
+    }
+
     pub(crate) fn expect(expected: &str, actual: &str) -> Result<(), StringValidationError> {
         if expected != actual {
             return Err(StringValidationError::Expected(
@@ -29,12 +43,14 @@ pub enum ValidationError {
     String(String, StringValidationError),
     Path(String, PathValidationError),
     Http(HttpError),
+    IpSource(String, IpSourceError),
 }
 
 #[derive(Debug)]
 pub enum StringValidationError {
     Required,
     Expected(String, String),
+    Unsupported(String),
 }
 
 #[derive(Debug)]
@@ -57,6 +73,9 @@ impl Display for ValidationError {
             ValidationError::Http(e) => {
                 write!(f, "{e}")
             }
+            ValidationError::IpSource(name, e) => {
+                write!(f, "IP provider {name} failed: {e}")
+            }
         }
     }
 }
@@ -68,6 +87,7 @@ impl StringValidationError {
             StringValidationError::Expected(expected, actual) => {
                 format!("did not match.\nexpected: {expected}\nactual: {actual}")
             }
+            StringValidationError::Unsupported(value) => format!("is not supported: {value}"),
         }
     }
 }